@@ -335,6 +335,9 @@ asa* checked_malloc() {
         exit(1);
     }
     
+    extern int yylineno;
+    p->line = yylineno;
+    
     return p;
 }
 
@@ -262,6 +262,11 @@ typedef struct asa {
      */
     int ninst;
     
+    /**
+     * La ligne du code source où le noeud a été lu.
+     */
+    int line;
+    
     union {
         /**
          * La valeur d'un noeud `TagInt`.
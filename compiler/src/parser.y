@@ -10,6 +10,7 @@
   
   const char *infile;
   FILE *outfile;
+  FILE *srcmap;
 %}
 
 %define parse.error verbose
@@ -229,6 +230,15 @@ static void arc_compile_yy(const char *_infile, const char *_outpath, FILE *_out
         exit(1);
     }
     
+    // La table de correspondance est facultative
+    char *srcmap_path = malloc(strlen(_outpath) + sizeof(".map"));
+    if(srcmap_path) {
+        strcpy(srcmap_path, _outpath);
+        strcat(srcmap_path, ".map");
+        srcmap = fopen(srcmap_path, "w");
+        free(srcmap_path);
+    }
+    
     st_pop_push_empty();
 
     yyin = f;
@@ -236,6 +246,11 @@ static void arc_compile_yy(const char *_infile, const char *_outpath, FILE *_out
     yylex_destroy();
     fclose(f);
     fclose(outfile);
+    
+    if(srcmap) {
+        fclose(srcmap);
+        srcmap = NULL;
+    }
 }
 
 // Called by the C driver
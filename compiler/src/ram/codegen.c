@@ -7,6 +7,8 @@
 #pragma GCC diagnostic ignored "-Wunused-function"
 
 extern FILE *outfile;
+extern FILE *srcmap;
+extern const char *infile;
 
 /**
  * Renvoie l'instruction de la machine RAM associée à un opérateur binaire.
@@ -85,6 +87,37 @@ static int dyn_jump_adr;
  */
 static void add_dyn_jump_adr(int adr);
 
+/**
+ * La fonction en cours de génération.
+ */
+static const char *srcmap_fn = NULL;
+
+/**
+ * La ligne de la dernière entrée de la table de correspondance.
+ */
+static int srcmap_line = 0;
+
+/**
+ * Ajoute une entrée à la table de correspondance : les instructions à partir
+ * de `ip` proviennent de la ligne `line` du code source, dans la fonction en cours.
+ *
+ * Une ligne nulle indique des instructions sans ligne correspondante.
+ */
+static void srcmap_add(int ip, int line) {
+    if(!srcmap || line == srcmap_line) {
+        return;
+    }
+    
+    if(line > 0) {
+        fprintf(srcmap, "%i\t%s\t%i\t%s\n", ip, infile, line, srcmap_fn ? srcmap_fn : "");
+    }
+    else {
+        fprintf(srcmap, "%i\n", ip);
+    }
+    
+    srcmap_line = line;
+}
+
 /**
  * Génère le code pour la machine RAM correspondant à l'arbre syntaxique abstrait spécifié.
  * Cette fonction est récursive.
@@ -106,6 +139,14 @@ void codegen_nc(asa *p, int *ip) {
     
     const int before_codegen_ip = *ip;
     
+    const int parent_line = srcmap_line;
+    if(p->tag == TagFn) {
+        srcmap_fn = p->tag_fn.identifier;
+        srcmap_line = 0;
+    }
+    
+    srcmap_add(*ip, p->line);
+    
     switch(p->tag) {
         case TagInt: {
             fprintf(outfile, "LOAD #%i\n", p->tag_int.value);
@@ -741,6 +782,9 @@ void codegen_nc(asa *p, int *ip) {
         }
     }
     
+    // Les instructions suivantes proviennent du noeud parent
+    srcmap_add(*ip, p->tag == TagFn ? 0 : parent_line);
+    
     if((before_codegen_ip + p->ninst) != *ip) {
         fprintf(stderr, "error: generated %i instructions for current node (`", *ip - before_codegen_ip);
         fprint_asa(stderr, p);
@@ -1,6 +1,9 @@
 use clap::{Parser, ValueHint};
 use std::path::PathBuf;
-use rame_driver::Driver;
+use rame_driver::{parse, DiagnosticArgs, Driver, Emit, EmitArgs};
+
+#[cfg(feature = "optimizer")]
+use rame_driver::OptArgs;
 
 /// Compiles an algorithmic program into a RAM one.
 #[derive(Parser)]
@@ -13,19 +16,37 @@ struct Cli {
     /// Where to place the compiled program.
     #[arg(short = 'o', value_name = "outfile", default_value = "a.out", value_hint = ValueHint::FilePath)]
     outfile: PathBuf,
-    
-    /// Turn on all optimizations.
-    #[arg(short = 'O', default_value_t = false)]
-    optimize: bool,
+
+    #[command(flatten)]
+    emit: EmitArgs,
+
+    #[command(flatten)]
+    diag: DiagnosticArgs,
+
+    #[command(flatten)]
+    #[cfg(feature = "optimizer")]
+    opt: OptArgs,
 }
 
 fn main() {
-    let cli = Cli::parse();
+    let cli: Cli = parse();
+    let format = cli.diag.error_format;
 
-    Driver::new()
+    let mut driver = Driver::new();
+    driver
         .infile(&cli.infile)
-        .outfile(&cli.outfile)
         .compile(true)
-        .optimize(cli.optimize)
-        .drive();
+        .error_format(format);
+
+    if cli.emit.format == Emit::Ram {
+        driver.outfile(&cli.outfile);
+    }
+
+    #[cfg(feature = "optimizer")]
+    driver
+        .passes(cli.opt.passes(None, format))
+        .pass_stats(cli.opt.stats);
+
+    let code = driver.drive();
+    cli.emit.emit(&code, &cli.outfile, format);
 }
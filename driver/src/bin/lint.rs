@@ -0,0 +1,44 @@
+use clap::{Parser, ValueHint};
+use rame::analysis::lint;
+use rame::diagnostic::Level;
+use rame_driver::{DiagnosticArgs, Driver};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+/// Find problems in an algorithmic or RAM program without running it.
+#[derive(Parser)]
+#[command(version, arg_required_else_help = true)]
+struct Cli {
+    /// The program to check.
+    #[arg(value_name = "infile", value_hint = ValueHint::FilePath)]
+    infile: PathBuf,
+
+    #[command(flatten)]
+    diag: DiagnosticArgs,
+
+    /// Compile the algorithmic program as a first step.
+    #[arg(short = 'c', default_value_t = false)]
+    #[cfg(feature = "compiler")]
+    compile: bool,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let format = cli.diag.error_format;
+
+    #[cfg(feature = "compiler")] let compile = cli.compile;
+    #[cfg(not(feature = "compiler"))] let compile = false;
+
+    let code = Driver::new()
+        .infile(&cli.infile)
+        .compile(compile)
+        .error_format(format)
+        .drive();
+
+    let findings = lint(&code);
+    for finding in &findings {
+        finding.diagnostic(&code).emit(format);
+    }
+
+    if findings.iter().any(|finding| finding.lint.level() == Level::Error) { ExitCode::FAILURE } else { ExitCode::SUCCESS }
+}
@@ -1,6 +1,6 @@
 use clap::{Parser, ValueHint};
 use std::path::PathBuf;
-use rame_driver::Driver;
+use rame_driver::{parse, DiagnosticArgs, Driver, Emit, EmitArgs, OptArgs, OptLevel};
 
 /// Optimize a RAM program.
 #[derive(Parser)]
@@ -13,14 +13,47 @@ struct Cli {
     /// Where to place the optimized program.
     #[arg(short = 'o', value_name = "outfile", default_value = "a.out", value_hint = ValueHint::FilePath)]
     outfile: PathBuf,
+
+    #[command(flatten)]
+    emit: EmitArgs,
+
+    #[command(flatten)]
+    diag: DiagnosticArgs,
+
+    #[command(flatten)]
+    opt: OptArgs,
+
+    /// Check the optimized program against the original one on random inputs.
+    #[arg(long, default_value_t = false)]
+    verify: bool,
+
+    /// Write the optimized program with labels instead of addresses.
+    #[arg(long, default_value_t = false)]
+    labels: bool,
+
+    /// Write the optimized program with labels, and annotate each instruction with its IR and basic block.
+    #[arg(long, default_value_t = false)]
+    annotate: bool,
 }
 
 fn main() {
-    let cli = Cli::parse();
+    let cli: Cli = parse();
+    let format = cli.diag.error_format;
 
-    Driver::new()
+    let mut driver = Driver::new();
+    driver
         .infile(&cli.infile)
-        .outfile(&cli.outfile)
-        .optimize(true)
-        .drive();
+        .error_format(format)
+        .passes(cli.opt.passes(Some(OptLevel::default()), format))
+        .pass_stats(cli.opt.stats)
+        .verify(cli.verify)
+        .labels(cli.labels)
+        .annotate(cli.annotate);
+
+    if cli.emit.format == Emit::Ram {
+        driver.outfile(&cli.outfile);
+    }
+
+    let code = driver.drive();
+    cli.emit.emit(&code, &cli.outfile, format);
 }
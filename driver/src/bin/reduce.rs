@@ -0,0 +1,156 @@
+use clap::{Parser, ValueEnum, ValueHint};
+use rame::model::{Integer, RoCode};
+use rame::optimizer::{reduce, Outcome, PassManager, Validator};
+use rame::runner::RunError;
+use rame::diagnostic::Diagnostic;
+use rame_driver::{cvt, fatal, parse, Bits, DiagnosticArgs, Driver, OptArgs, OptLevel};
+use std::fmt::{Debug, Display};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+
+/// Shrink a RAM program while it still fails.
+///
+/// By default, the program is reduced while its optimized version misbehaves on the given input.
+#[derive(Parser)]
+#[command(version, arg_required_else_help = true)]
+struct Cli {
+    /// The program to reduce.
+    #[arg(value_name = "infile", value_hint = ValueHint::FilePath)]
+    infile: PathBuf,
+
+    /// The program's input.
+    #[arg(value_name = "args", value_delimiter = ',', num_args = 0..)]
+    args: Vec<i128>,
+
+    /// Where to place the reduced program.
+    #[arg(short = 'o', value_name = "outfile", default_value = "a.out", value_hint = ValueHint::FilePath)]
+    outfile: PathBuf,
+
+    /// The integers' width.
+    #[arg(short, long, default_value = "16")]
+    bits: Bits,
+
+    /// Reduce the program while it fails with this error, instead of while it's miscompiled.
+    #[arg(long, value_name = "error")]
+    error: Option<ErrorKind>,
+
+    /// Maximum count of instructions to run the program for.
+    #[arg(long, default_value_t = 100_000)]
+    steps: usize,
+
+    #[command(flatten)]
+    diag: DiagnosticArgs,
+
+    #[command(flatten)]
+    opt: OptArgs,
+
+    /// Compile the algorithmic program as a first step.
+    #[arg(short = 'c', default_value_t = false)]
+    #[cfg(feature = "compiler")]
+    compile: bool,
+}
+
+/// The kinds of [`RunError`].
+#[derive(ValueEnum, Copy, Clone, Debug)]
+enum ErrorKind {
+    ReadEof,
+    ReadUninit,
+    InvalidAddress,
+    MemoryLimit,
+    ReadOnly,
+    IntegerOverflow,
+    InexistentJump,
+    #[cfg(feature = "indirect_jumps")]
+    InvalidJump,
+    Eof,
+}
+
+impl ErrorKind {
+    fn matches<T: Integer>(self, err: &RunError<T>) -> bool {
+        match self {
+            ErrorKind::ReadEof => matches!(err, RunError::ReadEof),
+            ErrorKind::ReadUninit => matches!(err, RunError::ReadUninit { .. }),
+            ErrorKind::InvalidAddress => matches!(err, RunError::InvalidAddress { .. }),
+            ErrorKind::MemoryLimit => matches!(err, RunError::MemoryLimit { .. }),
+            ErrorKind::ReadOnly => matches!(err, RunError::ReadOnly { .. }),
+            ErrorKind::IntegerOverflow => matches!(err, RunError::IntegerOverfow),
+            ErrorKind::InexistentJump => matches!(err, RunError::InexistentJump),
+            #[cfg(feature = "indirect_jumps")]
+            ErrorKind::InvalidJump => matches!(err, RunError::InvalidJump { .. }),
+            ErrorKind::Eof => matches!(err, RunError::Eof),
+        }
+    }
+}
+
+/// Returns `true` iff the program fails with the specified kind of error.
+fn fails<T: Integer + TryFrom<i128>>(code: &RoCode<i128>, input: &[T], kind: ErrorKind, steps: usize) -> bool {
+    let Ok(code) = code.try_cast::<T>() else {
+        return false;
+    };
+
+    matches!(Outcome::of(&code, input, steps), Outcome::Failed { err, .. } if kind.matches(&err))
+}
+
+/// Returns `true` iff the optimized program doesn't behave as the program.
+fn miscompiled<T: Integer + TryFrom<i128>>(code: &RoCode<i128>, input: &[T], passes: &PassManager<i128>, steps: usize) -> bool {
+    // Passes panicking on the smaller programs are another bug
+    let Ok(optimized) = panic::catch_unwind(AssertUnwindSafe(|| passes.clone().run(code))) else {
+        return false;
+    };
+
+    let (Ok(code), Ok(optimized)) = (code.try_cast::<T>(), optimized.try_cast::<T>()) else {
+        return false;
+    };
+
+    let mut validator = Validator::new(&code, &optimized);
+    validator.steps(steps).check(input).is_err()
+}
+
+fn poly<T: Integer + TryFrom<i128, Error: Display + Debug>>(code: &RoCode<i128>, cli: &Cli) -> Option<RoCode<i128>> {
+    let input: Vec<T> = cvt(&cli.args, cli.diag.error_format);
+
+    match cli.error {
+        Some(kind) => reduce(code, |code| fails(code, &input, kind, cli.steps)),
+        None => {
+            let passes = cli.opt.passes(Some(OptLevel::default()), cli.diag.error_format).unwrap_or_default();
+            reduce(code, |code| miscompiled(code, &input, &passes, cli.steps))
+        },
+    }
+}
+
+fn main() {
+    let cli: Cli = parse();
+    let format = cli.diag.error_format;
+
+    #[cfg(feature = "compiler")] let compile = cli.compile;
+    #[cfg(not(feature = "compiler"))] let compile = false;
+
+    let code = Driver::new()
+        .infile(&cli.infile)
+        .compile(compile)
+        .error_format(format)
+        .drive();
+
+    // Silence the panics caught while reducing
+    panic::set_hook(Box::new(|_| {}));
+
+    let reduced = match cli.bits {
+        Bits::Int8   => poly::<i8>  (&code, &cli),
+        Bits::Int16  => poly::<i16> (&code, &cli),
+        Bits::Int32  => poly::<i32> (&code, &cli),
+        Bits::Int64  => poly::<i64> (&code, &cli),
+        Bits::Int128 => poly::<i128>(&code, &cli),
+    };
+
+    let _ = panic::take_hook();
+
+    let Some(reduced) = reduced else {
+        fatal(Diagnostic::error(format_args!("the program doesn't fail with input {:?}", cli.args)).at(cli.infile.display()), format);
+    };
+
+    if let Err(e) = reduced.write_to_file(&cli.outfile) {
+        fatal(Diagnostic::error(e).at(cli.outfile.display()), format);
+    }
+
+    eprintln!("{}: reduced {} instructions to {}", env!("CARGO_BIN_NAME"), code.len(), reduced.len());
+}
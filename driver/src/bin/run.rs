@@ -1,10 +1,14 @@
 use std::fmt::{Debug, Display};
 use clap::{Parser, ValueHint};
-use rame::runner::Ram;
-use rame_driver::{cvt, Bits, Driver, Stdin};
+use rame::runner::{Access, Ram, DEFAULT_MEMORY_LIMIT};
+use rame_driver::{cvt, parse, Bits, DiagnosticArgs, Driver, Memory, Stdin};
+use std::ops::Range;
 use std::path::PathBuf;
 use rame::model::{Integer, RoCode};
 
+#[cfg(feature = "optimizer")]
+use rame_driver::OptArgs;
+
 /// Run an algorithmic or RAM program.
 #[derive(Parser)]
 #[command(version, arg_required_else_help = true)]
@@ -21,10 +25,36 @@ struct Cli {
     #[arg(short, long, default_value = "16")]
     bits: Bits,
 
-    /// Optimize the RAM program before running it.
-    #[arg(short = 'O', default_value_t = false)]
+    /// Report where the addresses of uninitialized indirect reads come from.
+    #[arg(long, default_value_t = false)]
+    memcheck: bool,
+
+    /// How to store the program's memory.
+    #[arg(long, value_name = "layout", default_value = "auto")]
+    memory: Memory,
+
+    /// The maximum count of registers the program may use.
+    #[arg(long, value_name = "registers", default_value_t = DEFAULT_MEMORY_LIMIT)]
+    memory_limit: usize,
+
+    /// Make writing to the registers from `start` to `end` (excluded) an error.
+    #[arg(long, value_name = "start..end", value_parser = parse_range)]
+    read_only: Vec<Range<usize>>,
+
+    /// Report the writes to the register.
+    #[arg(long, value_name = "register")]
+    watch: Vec<usize>,
+
+    /// Report the reads of the register.
+    #[arg(long, value_name = "register")]
+    watch_read: Vec<usize>,
+
+    #[command(flatten)]
+    diag: DiagnosticArgs,
+
+    #[command(flatten)]
     #[cfg(feature = "optimizer")]
-    optimize: bool,
+    opt: OptArgs,
 
     /// Compile the algorithmic program as a first step.
     #[arg(short = 'c', default_value_t = false)]
@@ -32,35 +62,65 @@ struct Cli {
     compile: bool,
 }
 
-fn poly<T: Integer + TryFrom<i128, Error: Display + Debug>>(code: &RoCode<i128>, args: &[i128]) {
-    let args: Vec<T> = cvt(&args);
+fn parse_range(s: &str) -> Result<Range<usize>, String> {
+    let (start, end) = s.split_once("..").ok_or_else(|| format!("expected `start..end`, got `{s}`"))?;
+    let start = start.parse().map_err(|e| format!("invalid start `{start}`: {e}"))?;
+    let end = end.parse().map_err(|e| format!("invalid end `{end}`: {e}"))?;
+    Ok(start..end)
+}
+
+fn poly<T: Integer + TryFrom<i128, Error: Display + Debug>>(code: &RoCode<i128>, cli: &Cli) {
+    let format = cli.diag.error_format;
+    let args: Vec<T> = cvt(&cli.args, format);
     let offset = args.len();
-    let args = args.into_iter().chain(Stdin::new(|i| print!("E{} = ", i + offset)));
-    let mut ram = Ram::new(code.try_cast().unwrap(), args);
+    let args = args.into_iter().chain(Stdin::new(|i| print!("E{} = ", i + offset), format));
+    let mut ram = Ram::new(code.try_cast().unwrap(), args)
+        .with_error_format(format)
+        .with_memcheck(cli.memcheck)
+        .with_memory(cli.memory.into())
+        .with_memory_limit(cli.memory_limit);
+
+    for range in &cli.read_only {
+        ram = ram.with_read_only(range.clone());
+    }
+
+    for &adr in &cli.watch_read {
+        ram = ram.with_watchpoint(adr, Access::Read);
+    }
+
+    for &adr in &cli.watch {
+        let access = if cli.watch_read.contains(&adr) { Access::ReadWrite } else { Access::Write };
+        ram = ram.with_watchpoint(adr, access);
+    }
 
     println!("Output = {:?}", ram.run());
 }
 
 fn main() {
-    let cli = Cli::parse();
-
-    #[cfg(feature = "optimizer")] let optimize = cli.optimize;
-    #[cfg(not(feature = "optimizer"))] let optimize = false;
+    let cli: Cli = parse();
+    let format = cli.diag.error_format;
 
     #[cfg(feature = "compiler")] let compile = cli.compile;
     #[cfg(not(feature = "compiler"))] let compile = false;
 
-    let code = Driver::new()
+    let mut driver = Driver::new();
+    driver
         .infile(&cli.infile)
-        .optimize(optimize)
         .compile(compile)
-        .drive();
+        .error_format(format);
+
+    #[cfg(feature = "optimizer")]
+    driver
+        .passes(cli.opt.passes(None, format))
+        .pass_stats(cli.opt.stats);
+
+    let code = driver.drive();
 
     match cli.bits {
-        Bits::Int8   => poly::<i8>  (&code, &cli.args),
-        Bits::Int16  => poly::<i16> (&code, &cli.args),
-        Bits::Int32  => poly::<i32> (&code, &cli.args),
-        Bits::Int64  => poly::<i64> (&code, &cli.args),
-        Bits::Int128 => poly::<i128>(&code, &cli.args),
+        Bits::Int8   => poly::<i8>  (&code, &cli),
+        Bits::Int16  => poly::<i16> (&code, &cli),
+        Bits::Int32  => poly::<i32> (&code, &cli),
+        Bits::Int64  => poly::<i64> (&code, &cli),
+        Bits::Int128 => poly::<i128>(&code, &cli),
     }
 }
@@ -2,17 +2,21 @@ use std::any::type_name;
 use clap::Parser;
 use rame::model::{Integer, RoCode};
 use rame::runner::Ram;
-use rame_driver::{Bits, Driver};
+use rame::diagnostic::{Diagnostic, Format};
+use rame_driver::{fatal, Bits, DiagnosticArgs, Driver};
 use std::fmt::{self, Debug, Display, Formatter};
 use std::fs::{self, File};
 use std::io::{BufRead, BufReader};
 use std::path::{Path, PathBuf};
-use std::process::{exit, ExitCode, Termination};
+use std::process::{ExitCode, Termination};
 use std::str::FromStr;
 
 #[cfg(feature = "optimizer")]
 use std::io::{self, Write};
 
+#[cfg(feature = "jit")]
+use rame::jit::{Jit, Options};
+
 /// Test an algorithmic program.
 #[derive(Parser)]
 #[command(version, arg_required_else_help = !cfg!(feature = "compiler"))]
@@ -25,6 +29,9 @@ struct Cli {
     #[arg(short, long, default_value = "16")]
     bits: Bits,
 
+    #[command(flatten)]
+    diag: DiagnosticArgs,
+
     /// The files to test.
     #[arg(value_name = "infile", default_value = "tests")]
     infiles: Vec<PathBuf>,
@@ -38,12 +45,29 @@ struct UnitTest<T: Integer> {
 impl<T: Integer> UnitTest<T> {
     /// Returns `Some` with the current output if the test failed.
     #[must_use]
-    fn run(&self, code: RoCode<T>) -> Option<Vec<T>> {
-        let mut ram = Ram::new(code, self.input.iter().copied());
+    fn run(&self, code: RoCode<T>, format: Format) -> Option<Vec<T>> {
+        let mut ram = Ram::new(code, self.input.iter().copied()).with_error_format(format);
         let out = ram.run();
 
         (out != &self.output).then_some(ram.into())
     }
+    
+    /// Returns `Some` with the current output, and the error if any, if the test failed.
+    ///
+    /// Tests whose values don't fit in an `i64` are skipped.
+    #[cfg(feature = "jit")]
+    #[must_use]
+    fn run_jit(&self, jit: &Jit) -> Option<String> {
+        let input = self.input.iter().map(T::to_i64).collect::<Option<Vec<_>>>()?;
+        let output = self.output.iter().map(T::to_i64).collect::<Option<Vec<_>>>()?;
+        
+        let mut input = input.into_iter();
+        let mut out = Vec::new();
+        match jit.run(|| input.next(), |v| out.push(v)) {
+            Ok(()) => (out != output).then(|| format!("{out:?}")),
+            Err(e) => Some(format!("{out:?} then `{e}` at instruction {}", e.ir)),
+        }
+    }
 }
 
 impl<T: Integer> Display for UnitTest<T> {
@@ -52,35 +76,28 @@ impl<T: Integer> Display for UnitTest<T> {
     }
 }
 
-fn parse_headers<T: Integer>(path: &Path) -> Vec<UnitTest<T>> {
+fn parse_headers<T: Integer>(path: &Path, format: Format) -> Vec<UnitTest<T>> {
     let f = match File::open(path) {
         Ok(f) => f,
-        Err(e) => {
-            eprintln!("{}: {e}", path.display());
-            exit(1);
-        },
+        Err(e) => fatal(Diagnostic::error(e).at(path.display()), format),
     };
     
     let r = BufReader::new(f);
     let mut tests = Vec::new();
     
-    for l in r.lines() {
+    for (i, l) in r.lines().enumerate() {
         let l = match l {
             Ok(l) => l,
-            Err(e) => {
-                eprintln!("{}: {e}", path.display());
-                exit(1);
-            }
+            Err(e) => fatal(Diagnostic::error(e).at(path.display()), format),
         };
         
         if l.trim_start().starts_with("# TEST: ") {
             let Some((input, output)) = &l.trim()["# TEST: ".len()..].split_once("=>") else {
-                eprintln!("{}: bad test", path.display());
-                exit(1);
+                fatal(Diagnostic::error("bad test").at(format_args!("{}:{}", path.display(), i + 1)).line(i + 1, l.trim()).help("expected `# TEST: [<input>] => [<output>]`"), format);
             };
 
-            let input = parse_vec(input, path);
-            let output = parse_vec(output, path);
+            let input = parse_vec(input, path, format);
+            let output = parse_vec(output, path, format);
 
             tests.push(UnitTest {
                 input,
@@ -92,7 +109,7 @@ fn parse_headers<T: Integer>(path: &Path) -> Vec<UnitTest<T>> {
     tests
 }
 
-fn parse_vec<T: FromStr<Err: Display>>(s: &str, path: &Path) -> Vec<T> {
+fn parse_vec<T: FromStr<Err: Display>>(s: &str, path: &Path, format: Format) -> Vec<T> {
     let s = s.trim().strip_prefix('[').expect("missing `[`").strip_suffix(']').expect("missing `]`");
 
     if s.is_empty() {
@@ -105,10 +122,7 @@ fn parse_vec<T: FromStr<Err: Display>>(s: &str, path: &Path) -> Vec<T> {
     for elem in iter {
         v.push(match T::from_str(elem.trim()) {
             Ok(v) => v,
-            Err(e) => {
-                eprintln!("error: {}: parsing {elem:?} as {}: {e}", path.display(), type_name::<T>());
-                exit(1)
-            }
+            Err(e) => fatal(Diagnostic::error(format_args!("parsing {elem:?} as {}: {e}", type_name::<T>())).at(path.display()), format),
         });
     }
 
@@ -116,18 +130,18 @@ fn parse_vec<T: FromStr<Err: Display>>(s: &str, path: &Path) -> Vec<T> {
 }
 
 #[must_use]
-fn scan_file<T: Integer + TryFrom<i128, Error: Debug>>(p: &Path, cc: &Option<PathBuf>) -> bool {
+fn scan_file<T: Integer + TryFrom<i128, Error: Debug>>(p: &Path, cc: &Option<PathBuf>, format: Format) -> bool {
     match fs::metadata(p) {
         Ok(m) => if m.is_dir() {
             let mut ok = true;
             for entry in fs::read_dir(p).unwrap() {
-                ok &= scan_file::<T>(&entry.unwrap().path(), cc);
+                ok &= scan_file::<T>(&entry.unwrap().path(), cc, format);
             }
 
             return ok;
         },
         Err(e) => {
-            eprintln!("{}: {}: {e}", env!("CARGO_BIN_NAME"), p.display());
+            Diagnostic::error(e).at(p.display()).emit(format);
             return false;
         },
     }
@@ -135,9 +149,9 @@ fn scan_file<T: Integer + TryFrom<i128, Error: Debug>>(p: &Path, cc: &Option<Pat
     print!("{}... ", p.display());
     _ = io::stdout().flush();
 
-    let tests = parse_headers::<T>(p);
+    let tests = parse_headers::<T>(p, format);
     if tests.is_empty() {
-        eprintln!("{}: {}: no test", env!("CARGO_BIN_NAME"), p.display());
+        Diagnostic::warning("no test").at(p.display()).help("add a `# TEST: [<input>] => [<output>]` line").emit(format);
         return false;
     }
 
@@ -145,42 +159,61 @@ fn scan_file<T: Integer + TryFrom<i128, Error: Debug>>(p: &Path, cc: &Option<Pat
         .infile(p)
         .compile(true)
         .compiler(cc.as_ref())
+        .error_format(format)
         .try_drive();
 
     let code = match code {
         Ok(code) => code,
-        Err(e) => {
-            eprintln!("error: failed to compiled `{}`: {e}", p.display());
+        Err(errors) => {
+            for e in errors {
+                e.diagnostic(None).note(format_args!("while compiling `{}`", p.display())).emit(format);
+            }
             return false;
         }
     };
 
     #[cfg(feature = "optimizer")]
     let opt = code.optimize();
+    
+    #[cfg(feature = "jit")]
+    let jit = code.try_cast::<i64>().ok().map(|code| match Jit::compile(&code, Options::default()) {
+        Ok(jit) => jit,
+        Err(e) => fatal(Diagnostic::error(e).note("while mapping the machine code"), format),
+    });
 
     let mut ok = true;
 
     for test in tests {
-        if let Some(out) = test.run(code.try_cast().unwrap()) {
+        if let Some(out) = test.run(code.try_cast().unwrap(), format) {
             if ok {
                 println!("failed");
                 ok = false;
             }
 
-            eprintln!(" {test}: got {out:?} instead");
+            Diagnostic::error(format_args!("{test}: got {out:?} instead")).kind("TestFailed").at(p.display()).emit(format);
             continue;
         }
 
         #[cfg(feature = "optimizer")]
-        if let Some(out) = test.run(opt.try_cast().unwrap()) {
+        if let Some(out) = test.run(opt.try_cast().unwrap(), format) {
             if ok {
                 println!("failed");
                 ok = false;
             }
             
-            eprintln!("opt: {test}: got {out:?} instead");
+            Diagnostic::error(format_args!("{test}: got {out:?} instead")).kind("TestFailed").at(p.display()).note("in the optimized code").emit(format);
             continue;
         }
+        
+        #[cfg(feature = "jit")]
+        if let Some(out) = jit.as_ref().and_then(|jit| test.run_jit(jit)) {
+            if ok {
+                println!("failed");
+                ok = false;
+            }
+            
+            Diagnostic::error(format_args!("{test}: got {out} instead")).kind("TestFailed").at(p.display()).note("in the machine code").emit(format);
+        }
     }
 
     if ok {
@@ -191,13 +224,14 @@ fn scan_file<T: Integer + TryFrom<i128, Error: Debug>>(p: &Path, cc: &Option<Pat
 
 fn main() -> impl Termination {
     let cli = Cli::parse();
+    let format = cli.diag.error_format;
 
     let ok = match cli.bits {
-        Bits::Int8 => cli.infiles.iter().all(|p| scan_file::<i8>(&p, &cli.compiler)),
-        Bits::Int16 => cli.infiles.iter().all(|p| scan_file::<i16>(&p, &cli.compiler)),
-        Bits::Int32 => cli.infiles.iter().all(|p| scan_file::<i32>(&p, &cli.compiler)),
-        Bits::Int64 => cli.infiles.iter().all(|p| scan_file::<i64>(&p, &cli.compiler)),
-        Bits::Int128 => cli.infiles.iter().all(|p| scan_file::<i128>(&p, &cli.compiler)),
+        Bits::Int8 => cli.infiles.iter().all(|p| scan_file::<i8>(&p, &cli.compiler, format)),
+        Bits::Int16 => cli.infiles.iter().all(|p| scan_file::<i16>(&p, &cli.compiler, format)),
+        Bits::Int32 => cli.infiles.iter().all(|p| scan_file::<i32>(&p, &cli.compiler, format)),
+        Bits::Int64 => cli.infiles.iter().all(|p| scan_file::<i64>(&p, &cli.compiler, format)),
+        Bits::Int128 => cli.infiles.iter().all(|p| scan_file::<i128>(&p, &cli.compiler, format)),
     };
     
     if ok { ExitCode::SUCCESS } else { ExitCode::FAILURE }
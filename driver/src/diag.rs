@@ -0,0 +1,10 @@
+use clap::Args;
+use rame::diagnostic::Format;
+
+/// The diagnostic options shared by the binaries.
+#[derive(Args, Copy, Clone, Debug, Default)]
+pub struct DiagnosticArgs {
+    /// How to print errors.
+    #[arg(long, value_name = "format", default_value = "human")]
+    pub error_format: Format,
+}
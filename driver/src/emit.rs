@@ -0,0 +1,114 @@
+use crate::{fatal, Bits};
+use clap::{Args, ValueEnum};
+use rame::diagnostic::{Diagnostic, Format};
+use rame::emit::{CInteger, Llvm, Options, WasmInteger, Wat, C};
+use rame::model::{Integer, RoCode};
+use rame::runner::DEFAULT_MEMORY_LIMIT;
+use std::fmt::Display;
+use std::fs;
+use std::path::Path;
+
+/// What a program is written as.
+#[derive(ValueEnum, Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum Emit {
+    /// RAM code.
+    #[default]
+    Ram,
+
+    /// A standalone C program, run like `rame-run`.
+    C,
+
+    /// An LLVM IR module, calling `rame_read` and `rame_write`.
+    Llvm,
+
+    /// A WebAssembly text module, importing `rame.read` and `rame.write`.
+    Wat,
+}
+
+/// The emission options shared by the binaries.
+#[derive(Args, Clone, Debug)]
+pub struct EmitArgs {
+    /// What to write the program as.
+    #[arg(long = "emit", value_name = "format", default_value = "ram")]
+    pub format: Emit,
+
+    /// The integers' width, unless writing RAM code.
+    #[arg(short, long, default_value = "16")]
+    pub bits: Bits,
+
+    /// Wrap around on overflow instead of failing, unless writing RAM code.
+    #[arg(long, default_value_t = false)]
+    pub no_overflow_checks: bool,
+
+    /// Read uninitialized registers as `0` instead of failing, unless writing RAM code.
+    #[arg(long, default_value_t = false)]
+    pub no_uninit_checks: bool,
+
+    /// The maximum count of registers the program may use, unless writing RAM code.
+    #[arg(long, value_name = "registers", default_value_t = DEFAULT_MEMORY_LIMIT)]
+    pub memory_limit: usize,
+}
+
+impl EmitArgs {
+    /// Writes a program into `outfile` as `--emit`, except RAM code, which is written by the [`Driver`](crate::Driver).
+    pub fn emit(&self, code: &RoCode<i128>, outfile: &Path, error_format: Format) {
+        let emitted = match self.format {
+            Emit::Ram => return,
+            Emit::C => match self.bits {
+                Bits::Int8   => self.c(&cast::<i8>  (code, error_format)),
+                Bits::Int16  => self.c(&cast::<i16> (code, error_format)),
+                Bits::Int32  => self.c(&cast::<i32> (code, error_format)),
+                Bits::Int64  => self.c(&cast::<i64> (code, error_format)),
+                Bits::Int128 => self.c(&cast::<i128>(code, error_format)),
+            },
+            Emit::Llvm => match self.bits {
+                Bits::Int8   => self.llvm(&cast::<i8>  (code, error_format)),
+                Bits::Int16  => self.llvm(&cast::<i16> (code, error_format)),
+                Bits::Int32  => self.llvm(&cast::<i32> (code, error_format)),
+                Bits::Int64  => self.llvm(&cast::<i64> (code, error_format)),
+                Bits::Int128 => self.llvm(&cast::<i128>(code, error_format)),
+            },
+            Emit::Wat => match self.bits {
+                Bits::Int8   => self.wat(&cast::<i8> (code, error_format), error_format),
+                Bits::Int16  => self.wat(&cast::<i16>(code, error_format), error_format),
+                Bits::Int32  => self.wat(&cast::<i32>(code, error_format), error_format),
+                Bits::Int64  => self.wat(&cast::<i64>(code, error_format), error_format),
+                Bits::Int128 => fatal(Diagnostic::error("WebAssembly has no 128-bit integers").help("lower `--bits`"), error_format),
+            },
+        };
+
+        if let Err(e) = fs::write(outfile, emitted) {
+            fatal(Diagnostic::error(e).at(outfile.display()), error_format);
+        }
+    }
+
+    fn options(&self) -> Options {
+        Options::new()
+            .overflow_checks(!self.no_overflow_checks)
+            .uninit_checks(!self.no_uninit_checks)
+            .memory_limit(self.memory_limit)
+    }
+
+    fn c<T: CInteger>(&self, code: &RoCode<T>) -> String {
+        C::new(code).options(self.options()).to_string()
+    }
+
+    fn llvm<T: Integer>(&self, code: &RoCode<T>) -> String {
+        Llvm::new(code).options(self.options()).to_string()
+    }
+
+    fn wat<T: WasmInteger>(&self, code: &RoCode<T>, error_format: Format) -> String {
+        match Wat::new(code).options(self.options()) {
+            Ok(wat) => wat.to_string(),
+            Err(e) => fatal(Diagnostic::error(e).help("lower `--memory-limit`"), error_format),
+        }
+    }
+}
+
+/// Casts the constants of `code` to `T`, exiting if one doesn't fit.
+fn cast<T: Integer + TryFrom<i128, Error: Display>>(code: &RoCode<i128>, error_format: Format) -> RoCode<T> {
+    match code.try_cast() {
+        Ok(code) => code,
+        Err(e) => fatal(Diagnostic::error(format_args!("invalid constant: {e}")).help("raise `--bits`"), error_format),
+    }
+}
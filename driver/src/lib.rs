@@ -1,17 +1,32 @@
-use clap::ValueEnum;
-use rame::model::{Integer, ParseCodeError, RoCode};
-use std::ffi::{c_char, CString};
+use clap::{Command as ClapCommand, Parser, ValueEnum};
+use rame::diagnostic::{Diagnostic, Format};
+use rame::model::{Integer, ParseCodeError, RoCode, SourceMap};
+use rame::runner::MemoryLayout;
+use std::env;
+use std::ffi::{c_char, CString, OsString};
 use std::fmt::Display;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io;
-use std::io::BufWriter;
+use std::io::{BufReader, BufWriter};
+use std::mem;
 use std::os::unix::ffi::OsStrExt;
 use std::path::{Path, PathBuf};
 use std::process::{exit, Command};
 
+#[cfg(feature = "optimizer")]
+use rame::optimizer::{PassManager, Validator};
+
+mod diag;
+mod emit;
+#[cfg(feature = "optimizer")]
+mod opt;
 mod stdin;
 mod tmp;
 
+pub use diag::DiagnosticArgs;
+pub use emit::{Emit, EmitArgs};
+#[cfg(feature = "optimizer")]
+pub use opt::{OptArgs, OptLevel};
 pub use stdin::Stdin;
 pub use tmp::TempFile;
 
@@ -28,7 +43,17 @@ pub struct Driver {
     outfile: Option<PathBuf>,
     compile: bool,
     compiler: Option<PathBuf>,
-    optimize: bool,
+    error_format: Format,
+    #[cfg(feature = "optimizer")]
+    passes: Option<PassManager<i128>>,
+    #[cfg(feature = "optimizer")]
+    pass_stats: bool,
+    #[cfg(feature = "optimizer")]
+    verify: bool,
+    #[cfg(feature = "optimizer")]
+    labels: bool,
+    #[cfg(feature = "optimizer")]
+    annotate: bool,
 }
 
 impl Driver {
@@ -56,32 +81,79 @@ impl Driver {
         self
     }
 
+    /// Sets how errors are printed, [`Format::Human`] by default.
+    pub fn error_format(&mut self, error_format: Format) -> &mut Self {
+        self.error_format = error_format;
+        self
+    }
+
+    #[cfg(feature = "optimizer")]
     pub fn optimize(&mut self, optimize: bool) -> &mut Self {
-        self.optimize = optimize;
+        self.passes = optimize.then(PassManager::new);
+        self
+    }
+
+    #[cfg(feature = "optimizer")]
+    pub fn passes(&mut self, passes: Option<PassManager<i128>>) -> &mut Self {
+        self.passes = passes;
+        self
+    }
+
+    #[cfg(feature = "optimizer")]
+    pub fn pass_stats(&mut self, pass_stats: bool) -> &mut Self {
+        self.pass_stats = pass_stats;
         self
     }
 
-    pub fn try_drive(&self) -> Result<RoCode<i128>, ParseCodeError<i128>> {
+    /// Checks the optimized code against the original code on random inputs.
+    #[cfg(feature = "optimizer")]
+    pub fn verify(&mut self, verify: bool) -> &mut Self {
+        self.verify = verify;
+        self
+    }
+
+    /// Writes the optimized code with labels, see [`RoCode::disassemble`].
+    #[cfg(feature = "optimizer")]
+    pub fn labels(&mut self, labels: bool) -> &mut Self {
+        self.labels = labels;
+        self
+    }
+
+    /// Writes the optimized code with labels, and annotates each instruction with its IR and basic block.
+    #[cfg(feature = "optimizer")]
+    pub fn annotate(&mut self, annotate: bool) -> &mut Self {
+        self.annotate = annotate;
+        self
+    }
+
+    /// Returns `true` iff the code will be optimized.
+    const fn optimizes(&self) -> bool {
+        #[cfg(feature = "optimizer")] { self.passes.is_some() }
+        #[cfg(not(feature = "optimizer"))] { false }
+    }
+
+    /// Returns the code, or the errors of all its invalid lines.
+    pub fn try_drive(&self) -> Result<RoCode<i128>, Vec<ParseCodeError<i128>>> {
         let infile = self.infile.as_ref().map(|pb| pb.as_path());
         let outfile = self.outfile.as_ref().map(|pb| pb.as_path());
+        let format = self.error_format;
 
         let code = if !self.compile {
             // The code is already in RAM format;
             infile.map_or_else(
-                || RoCode::try_from(Stdin::new(|i| print!("{i} | ")).fuse().collect::<Vec<_>>()),
-                |path| RoCode::parse(open(path))
+                || RoCode::try_from(Stdin::new(|i| print!("{i} | "), format).fuse().collect::<Vec<_>>()).map_err(|e| vec![e]),
+                |path| RoCode::parse_all(BufReader::new(open(path, format))).map(|code| with_source_map(code.with_path(path), path, false, format))
             )
         }
         else {
             // Compile algorithmic code to RAM/LLVM.
-            let create_temp_file = || TempFile::new(outfile.or(infile).unwrap_or("stdin".as_ref()));
+            let create_temp_file = || TempFile::new(outfile.or(infile).unwrap_or("stdin".as_ref()), format);
             
             let stdin = infile.is_none().then(|| {
                 let temp_file = create_temp_file();
                 let mut writer = BufWriter::new(&temp_file.file);
                 if let Err(e) = io::copy(&mut io::stdin().lock(), &mut writer) {
-                    eprintln!("error: failed to read stdin: {e}");
-                    exit(1);
+                    fatal(Diagnostic::error(format_args!("failed to read stdin: {e}")), format);
                 }
                 
                 drop(writer);
@@ -90,9 +162,10 @@ impl Driver {
             
             let infile = infile.unwrap_or_else(|| stdin.as_ref().unwrap().as_ref());
 
-            let unoptimized = self.optimize.then(create_temp_file);
+            let unoptimized = self.optimizes().then(create_temp_file);
             let outfile = unoptimized.as_ref().map(|tf| tf.as_ref()).or(outfile);
             let compiled = outfile.is_none().then(create_temp_file);
+            let temporary = compiled.is_some() || unoptimized.is_some();
             let compiled = outfile.unwrap_or_else(|| compiled.as_ref().unwrap().as_ref());
 
             match self.compiler.as_ref() {
@@ -102,72 +175,136 @@ impl Driver {
 
                     match cmd.status() {
                         Ok(s) if s.success() => {},
-                        Ok(s) => { eprintln!("error: {cmd:?}: {s}"); exit(1) },
-                        Err(e) => { eprintln!("error: `{}`: {e}", cc.display()); exit(1) },
+                        Ok(s) => fatal(Diagnostic::error(format_args!("{cmd:?}: {s}")), format),
+                        Err(e) => fatal(Diagnostic::error(e).at(cc.display()), format),
                     }
                 },
 
                 None => {
-                    let infile = to_cstring(infile);
-                    let outfile = to_cstring(compiled);
+                    let infile = to_cstring(infile, format);
+                    let outfile = to_cstring(compiled, format);
 
                     unsafe { arc_compile_file(infile.as_ptr(), outfile.as_ptr()) };
                 }
             }
 
-            RoCode::parse(open(compiled))
+            // Temporary files are named after the output, not worth reporting
+            RoCode::parse(open(compiled, format))
+                .map_err(|e| vec![e])
+                .map(|code| if temporary { code } else { code.with_path(compiled) })
+                .map(|code| with_source_map(code, compiled, temporary, format))
         };
 
-        if !self.optimize {
-            return code;
-        };
+        #[cfg(feature = "optimizer")]
+        if let Some(passes) = &self.passes {
+            let code = code?;
+            let mut passes = passes.clone();
+            let optimized = passes.run(&code);
 
-        let optimized = code?.optimize();
+            if self.pass_stats {
+                eprintln!("{}", passes.report());
+            }
 
-        if let Some(outfile) = outfile {
-            if let Err(e) = optimized.write_to_file(outfile) {
-                eprintln!("{}: {}: {e}", env!("CARGO_PKG_NAME"), outfile.display());
-                exit(1);
+            if self.verify {
+                if let Err(e) = Validator::new(&code, &optimized).check_random(1000, 0) {
+                    fatal(Diagnostic::error(format_args!("miscompilation: {e}")).kind("Miscompilation"), format);
+                }
             }
+
+            if let Some(outfile) = outfile {
+                let written = if self.labels || self.annotate {
+                    fs::write(outfile, optimized.disassemble().irs(self.annotate).blocks(self.annotate).to_string())
+                }
+                else {
+                    optimized.write_to_file(outfile)
+                };
+
+                if let Err(e) = written {
+                    fatal(Diagnostic::error(e).at(outfile.display()), format);
+                }
+
+                let map = optimized.source_map();
+                if !map.is_empty() {
+                    let path = source_map_path(outfile);
+                    if let Err(e) = map.write_to_file(&path) {
+                        fatal(Diagnostic::error(e).at(path.display()), format);
+                    }
+                }
+            }
+
+            return Ok(optimized);
         }
 
-        Ok(optimized)
+        code
     }
 
     pub fn drive(&self) -> RoCode<i128> {
         match self.try_drive() {
             Ok(c) => c,
-            Err(e) => {
-                eprintln!("{}: {e}", env!("CARGO_PKG_NAME"));
-                exit(1);
+            Err(errors) => {
+                // Compiled code is parsed from a file that may be temporary
+                let path = self.infile.as_deref().filter(|_| !self.compile);
+                let (last, errors) = errors.split_last().expect("an error is reported");
+                for e in errors {
+                    e.diagnostic(path).emit(self.error_format);
+                }
+                fatal(last.diagnostic(path), self.error_format);
             },
         }
     }
 }
 
+/// Returns the path of the [`SourceMap`] written alongside some RAM code, i.e. `<path>.map`.
+#[must_use]
+pub fn source_map_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    let mut map = path.as_ref().as_os_str().to_owned();
+    map.push(".map");
+    map.into()
+}
+
+/// Locates some code with the [`SourceMap`] found alongside it, if any.
+///
+/// The source map is removed once read if `remove` is `true`, e.g. if the code was compiled into a temporary file.
+fn with_source_map(code: RoCode<i128>, path: &Path, remove: bool, format: Format) -> RoCode<i128> {
+    let path = source_map_path(path);
+    let Ok(f) = File::open(&path) else {
+        return code;
+    };
+
+    let code = match SourceMap::parse(f) {
+        Ok(map) => code.with_source_map(&map),
+        Err(e) => {
+            Diagnostic::warning(e).at(path.display()).note("the source map is ignored").emit(format);
+            code
+        },
+    };
+
+    if remove {
+        if let Err(e) = fs::remove_file(&path) {
+            Diagnostic::warning(format_args!("failed to remove {}: {e}", path.display())).emit(format);
+        }
+    }
+
+    code
+}
+
 /// Open a file, handling potential errors.
 #[must_use]
-pub fn open<P: AsRef<Path>>(path: P) -> File {
+pub fn open<P: AsRef<Path>>(path: P, format: Format) -> File {
     let path = path.as_ref();
     match File::open(path) {
         Ok(f) => f,
-        Err(e) => {
-            eprintln!("{}: {}: {e}", env!("CARGO_PKG_NAME"), path.display());
-            exit(1);
-        },
+        Err(e) => fatal(Diagnostic::error(e).at(path.display()), format),
     }
 }
 
 /// Converts a [`Path`] into a [`CString`].
 #[must_use]
-pub fn to_cstring<P: AsRef<Path>>(path: P) -> CString {
+pub fn to_cstring<P: AsRef<Path>>(path: P, format: Format) -> CString {
     let path = path.as_ref();
     match CString::new(OsStrExt::as_bytes(path.as_os_str())) {
         Ok(s) => s,
-        Err(e) => {
-            eprintln!("{}: {}: {e}", env!("CARGO_PKG_NAME"), path.display());
-            exit(1);
-        },
+        Err(e) => fatal(Diagnostic::error(e).at(path.display()), format),
     }
 }
 
@@ -181,13 +318,90 @@ pub enum Bits {
     #[clap(name = "128")] Int128,
 }
 
+/// How the memory of a program is stored.
+#[derive(ValueEnum, Copy, Clone, Debug, Default)]
+pub enum Memory {
+    /// Dense, until a register far past the others is accessed.
+    #[default]
+    Auto,
+
+    /// Up to the highest register accessed.
+    Dense,
+
+    /// Only the pages of registers accessed, for programs using far-apart addresses.
+    Sparse,
+}
+
+impl From<Memory> for MemoryLayout {
+    fn from(memory: Memory) -> Self {
+        match memory {
+            Memory::Auto => MemoryLayout::Auto,
+            Memory::Dense => MemoryLayout::Dense,
+            Memory::Sparse => MemoryLayout::Sparse,
+        }
+    }
+}
+
+/// Prints an error in `format`, then exits.
+pub fn fatal(diag: Diagnostic, format: Format) -> ! {
+    diag.emit(format);
+    exit(1);
+}
+
 /// Convert CLI args
-pub fn cvt<T: Integer + TryFrom<i128, Error: Display>>(args: &[i128]) -> Vec<T> {
+pub fn cvt<T: Integer + TryFrom<i128, Error: Display>>(args: &[i128], format: Format) -> Vec<T> {
     args.iter().copied().map(|v| match T::try_from(v) {
         Ok(v) => v,
-        Err(e) => {
-            eprintln!("invalid integer {v}: {e}");
-            exit(1);
+        Err(e) => fatal(Diagnostic::error(format_args!("invalid integer {v}: {e}")), format),
+    }).collect()
+}
+
+/// Parses the command line, `-O` without a level meaning `-O2`.
+pub fn parse<P: Parser>() -> P {
+    P::parse_from(with_opt_level(P::command(), env::args_os()))
+}
+
+/// Appends the default level to a bare `-O` or `--opt-level`, so that clap doesn't take the next argument as the level.
+///
+/// `-O` can end a group of flags, e.g. `-cO`; option values and the arguments after `--` are left as they are.
+fn with_opt_level<I: IntoIterator<Item = OsString>>(mut cmd: ClapCommand, args: I) -> Vec<OsString> {
+    cmd.build();
+    let takes_value = |short: Option<char>, long: Option<&str>| cmd.get_arguments()
+        .find(|arg| short.is_some_and(|c| arg.get_short() == Some(c)) || long.is_some_and(|l| arg.get_long() == Some(l)))
+        .map(|arg| arg.get_action().takes_values());
+
+    // Whether the previous argument is an option waiting for its value
+    let mut value = false;
+    let mut raw = false;
+
+    args.into_iter().map(|arg| {
+        let is_value = mem::take(&mut value);
+        let Some(s) = arg.to_str().filter(|_| !is_value && !raw) else {
+            return arg;
+        };
+
+        if s == "--" {
+            raw = true;
+        }
+        else if let Some(long) = s.strip_prefix("--") {
+            if long == "opt-level" && takes_value(Some('O'), Some(long)).is_some() {
+                return OsString::from("--opt-level=2");
+            }
+            value = !long.contains('=') && takes_value(None, Some(long)) == Some(true);
         }
+        else if let Some(flags) = s.strip_prefix('-') {
+            for (i, c) in flags.char_indices() {
+                match takes_value(Some(c), None) {
+                    Some(false) => {},
+                    // The rest of the group is the option's value
+                    Some(true) if i + 1 < flags.len() => break,
+                    Some(true) if c == 'O' => return OsString::from(format!("{s}2")),
+                    Some(true) => value = true,
+                    None => break,
+                }
+            }
+        }
+
+        arg
     }).collect()
 }
@@ -0,0 +1,124 @@
+use crate::fatal;
+use clap::{Args, ValueEnum};
+use rame::diagnostic::{Diagnostic, Format};
+use rame::optimizer::{self, PassManager, UnknownPass};
+
+/// How hard should the program be optimized.
+#[derive(ValueEnum, Copy, Clone, Debug, Default)]
+pub enum OptLevel {
+    #[clap(name = "0")] O0,
+    #[clap(name = "1")] O1,
+    #[clap(name = "2")] #[default] O2,
+    #[clap(name = "s")] Os,
+}
+
+/// The optimizer options shared by the binaries.
+#[derive(Args, Clone, Debug, Default)]
+pub struct OptArgs {
+    /// Optimize the RAM program, `-O` alone meaning `-O2`.
+    #[arg(short = 'O', long = "opt-level", value_name = "level", num_args = 0..=1, default_missing_value = "2")]
+    pub level: Option<OptLevel>,
+
+    /// Only run the specified passes, in order.
+    #[arg(long, value_name = "pass", value_delimiter = ',')]
+    pub passes: Option<Vec<String>>,
+
+    /// Don't run the specified passes.
+    #[arg(long = "disable-pass", value_name = "pass", value_delimiter = ',')]
+    pub disabled: Vec<String>,
+
+    /// Print per-pass statistics.
+    #[arg(long = "pass-stats", default_value_t = false)]
+    pub stats: bool,
+}
+
+impl OptArgs {
+    /// Returns the requested passes, or `None` if the program shouldn't be optimized.
+    #[must_use]
+    pub fn passes(&self, default: Option<OptLevel>, format: Format) -> Option<PassManager<i128>> {
+        let level = self.level.or(default);
+        if level.is_none() && self.passes.is_none() {
+            return None;
+        }
+
+        let mut pm = PassManager::with_level(level.unwrap_or_default().into());
+        if let Err(e) = self.configure(&mut pm) {
+            fatal(Diagnostic::error(e), format);
+        }
+
+        Some(pm)
+    }
+
+    /// Applies `--passes` and `--disable-pass`.
+    fn configure(&self, pm: &mut PassManager<i128>) -> Result<(), UnknownPass> {
+        if let Some(passes) = &self.passes {
+            pm.select(passes)?;
+        }
+
+        for pass in &self.disabled {
+            pm.disable(pass)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl From<OptLevel> for optimizer::OptLevel {
+    fn from(level: OptLevel) -> Self {
+        match level {
+            OptLevel::O0 => optimizer::OptLevel::O0,
+            OptLevel::O1 => optimizer::OptLevel::O1,
+            OptLevel::O2 => optimizer::OptLevel::O2,
+            OptLevel::Os => optimizer::OptLevel::Os,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{with_opt_level, OptArgs, OptLevel};
+    use clap::{CommandFactory, Parser};
+    use std::iter;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        opt: OptArgs,
+
+        #[arg(short)]
+        compile: bool,
+
+        #[arg(short = 'o')]
+        outfile: Option<String>,
+
+        args: Vec<String>,
+    }
+
+    fn parse(args: &[&str]) -> Cli {
+        let args = iter::once(&"rame").chain(args).map(Into::into);
+        Cli::try_parse_from(with_opt_level(Cli::command(), args)).unwrap()
+    }
+
+    #[test]
+    fn opt_level() {
+        let level = |args: &[&str]| parse(args).opt.level;
+
+        assert!(level(&["f.ram"]).is_none());
+        assert!(matches!(level(&["-O", "f.ram", "3"]), Some(OptLevel::O2)));
+        assert!(matches!(level(&["-cO", "-", "20"]), Some(OptLevel::O2)));
+        assert!(matches!(level(&["f.ram", "-O"]), Some(OptLevel::O2)));
+        assert!(matches!(level(&["-O0", "f.ram"]), Some(OptLevel::O0)));
+        assert!(matches!(level(&["-cOs", "f.ram"]), Some(OptLevel::Os)));
+        assert!(matches!(level(&["--opt-level=1", "f.ram"]), Some(OptLevel::O1)));
+        assert!(matches!(level(&["--opt-level", "f.ram"]), Some(OptLevel::O2)));
+
+        // Values and arguments after `--` aren't levels
+        let cli = parse(&["-o", "x", "-O", "f.ram", "--", "-O"]);
+        assert!(matches!(cli.opt.level, Some(OptLevel::O2)));
+        assert_eq!(cli.args, ["f.ram", "-O"]);
+
+        let cli = parse(&["-oO", "f.ram"]);
+        assert!(cli.opt.level.is_none());
+        assert_eq!(cli.outfile.as_deref(), Some("O"));
+    }
+}
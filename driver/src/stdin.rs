@@ -1,3 +1,4 @@
+use rame::diagnostic::{Diagnostic, Format};
 use std::fmt::Display;
 use std::io::{self, Write};
 use std::marker::PhantomData;
@@ -16,15 +17,19 @@ pub struct Stdin<T, P: Fn(usize)> {
     /// The prompt is called with the number of elements already returned.
     prompt: P,
 
+    /// How the invalid lines are reported.
+    error_format: Format,
+
     _phantom: PhantomData<T>,
 }
 
 impl<T, P: Fn(usize)> Stdin<T, P> {
-    pub fn new(prompt: P) -> Stdin<T, P> {
+    pub fn new(prompt: P, error_format: Format) -> Stdin<T, P> {
         Stdin {
             buf: String::new(),
             i: 0,
             prompt,
+            error_format,
             _phantom: PhantomData,
         }
     }
@@ -46,7 +51,7 @@ impl<T: FromStr<Err: Display>, P: Fn(usize)> Iterator for Stdin<T, P> {
                 },
                 Ok(_) => {}
                 Err(e) => {
-                    eprintln!("error: failed to read stdin: {e}");
+                    Diagnostic::error(format_args!("failed to read stdin: {e}")).emit(self.error_format);
                     return None;
                 }
             };
@@ -57,7 +62,7 @@ impl<T: FromStr<Err: Display>, P: Fn(usize)> Iterator for Stdin<T, P> {
                     break Some(v)
                 },
                 Err(_) if self.buf.trim_ascii_end().is_empty() => return None,
-                Err(e) => eprintln!("error: {e}"),
+                Err(e) => Diagnostic::error(e).emit(self.error_format),
             }
         }
     }
@@ -1,3 +1,4 @@
+use rame::diagnostic::{Diagnostic, Format};
 use std::ffi::{c_int, CString, OsStr};
 use std::fs::File;
 use std::{fs, io};
@@ -10,11 +11,14 @@ use std::path::Path;
 pub struct TempFile {
     pub file: File,
     pub path: CString,
+
+    /// How a failure to remove the file is reported.
+    error_format: Format,
 }
 
 impl AsRef<Path> for TempFile {
     fn as_ref(&self) -> &Path {
-        Path::new(OsStr::from_bytes(self.path.as_bytes_with_nul()))
+        Path::new(OsStr::from_bytes(self.path.as_bytes()))
     }
 }
 
@@ -23,13 +27,13 @@ impl Drop for TempFile {
         let path = self.as_ref();
         
         if let Err(e) = fs::remove_file(path) {
-            eprintln!("warning: failed to remove {}: {e}", path.display());
+            Diagnostic::warning(format_args!("failed to remove {}: {e}", path.display())).emit(self.error_format);
         }
     }
 }
 
 impl TempFile {
-    pub fn new<P: AsRef<Path>>(model: P) -> TempFile {
+    pub fn new<P: AsRef<Path>>(model: P, error_format: Format) -> TempFile {
         let prefix = model.as_ref().file_stem().unwrap_or(OsStr::new("a")).as_bytes();
         let infix = OsStr::new("XXXXXX").as_bytes();
         let suffix = OsStr::new("tmp").as_bytes();
@@ -47,13 +51,14 @@ impl TempFile {
         let template = unsafe { CString::from_vec_with_nul_unchecked(buf) };
 
         // The roundtrip is for Miri.
-        let mut template = template.into_bytes();
+        let mut template = template.into_bytes_with_nul();
 
         // More checks.
         let suffix_len: c_int = ('.'.len_utf8() + suffix.len()).try_into().expect("file extension too large");
-        debug_assert_eq!(&template[template.len() - suffix_len as usize - 6..template.len() - suffix_len as usize], b"XXXXXX");
+        let end = template.len() - '\0'.len_utf8() - suffix_len as usize;
+        debug_assert_eq!(&template[end - 6..end], b"XXXXXX");
 
-        // SAFETY: `template` is writable, we have `prefixXXXXXXsuffix`
+        // SAFETY: `template` is writable and nul-terminated, we have `prefixXXXXXXsuffix`
         let fd = unsafe {
             libc::mkstemps(
                 template.as_mut_ptr().cast(),
@@ -68,12 +73,31 @@ impl TempFile {
         // SAFETY: the fd is ours.
         let fd = unsafe { OwnedFd::from_raw_fd(fd) };
 
-        // SAFETY: `mkstemps` should have preserved our safety rules
-        let path = unsafe { CString::from_vec_with_nul_unchecked(template) };
+        let path = CString::from_vec_with_nul(template).expect("`mkstemps` only replaces the `XXXXXX`");
 
         TempFile {
             file: File::from(fd),
             path,
+            error_format,
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::TempFile;
+    use rame::diagnostic::Format;
+
+    #[test]
+    fn path() {
+        let tmp = TempFile::new("a.ram", Format::Human);
+        let path = tmp.as_ref().to_owned();
+
+        assert_eq!(path.extension().and_then(|ext| ext.to_str()), Some("tmp"));
+        assert!(path.file_name().unwrap().to_str().unwrap().starts_with("a."));
+        assert!(path.exists());
+
+        drop(tmp);
+        assert!(!path.exists());
+    }
+}
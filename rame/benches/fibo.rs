@@ -0,0 +1,43 @@
+//! Runs a recursive `fibo` with [`Ram::run`] and [`Ram::step`], and compiled with `jit`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rame::model::RoCode;
+use rame::runner::Ram;
+use std::hint::black_box;
+use std::str::FromStr;
+
+#[cfg(feature = "jit")]
+use rame::jit::{Jit, Options};
+
+fn fibo(c: &mut Criterion) {
+    let code = RoCode::<i32>::from_str(include_str!("fibo.ram")).unwrap();
+    let mut group = c.benchmark_group("fibo");
+    
+    for n in [15, 20] {
+        group.bench_with_input(BenchmarkId::new("run", n), &n, |b, &n| b.iter(|| {
+            let mut ram = Ram::new(code.clone(), [black_box(n)]);
+            ram.run().to_owned()
+        }));
+        
+        group.bench_with_input(BenchmarkId::new("step", n), &n, |b, &n| b.iter(|| {
+            let mut ram = Ram::new(code.clone(), [black_box(n)]);
+            assert_eq!(ram.run_for(usize::MAX), Ok(true));
+            Vec::from(ram)
+        }));
+        
+        #[cfg(feature = "jit")]
+        {
+            let jit = Jit::compile(&code.try_cast().unwrap(), Options::default()).unwrap();
+            group.bench_with_input(BenchmarkId::new("jit", n), &n, |b, &n| b.iter(|| {
+                let mut output = Vec::new();
+                jit.run(|| Some(black_box(n.into())), |v| output.push(v)).unwrap();
+                output
+            }));
+        }
+    }
+    
+    group.finish();
+}
+
+criterion_group!(benches, fibo);
+criterion_main!(benches);
@@ -0,0 +1,338 @@
+//! Finds problems in some code without running it, see [`lint`].
+
+use crate::diagnostic::{Diagnostic, Level};
+use crate::model::{Instruction, Integer, Ir, Loc, Register, RoCode, RoLoc, Value, WoLoc};
+use std::collections::BTreeSet;
+use std::fmt::{self, Display, Formatter};
+
+/// A kind of problem.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Lint {
+    /// The register, `0` being ACC, is read before being written on every path,
+    /// which fails with [`RunError::ReadUninit`](crate::runner::RunError::ReadUninit).
+    UninitRead(usize),
+    
+    /// The execution may run past the last instruction,
+    /// which fails with [`RunError::Eof`](crate::runner::RunError::Eof).
+    MissingStop,
+    
+    /// The jump target is past the end of the program,
+    /// which fails with [`RunError::InexistentJump`](crate::runner::RunError::InexistentJump).
+    InexistentJump(Ir),
+    
+    /// The specified count of instructions can't be executed.
+    Unreachable(usize),
+    
+    /// `DIV #0` or `MOD #0`, which fails with [`RunError::IntegerOverfow`](crate::runner::RunError::IntegerOverfow).
+    DivByZero,
+    
+    /// The value of `READ` is overwritten by the next instruction.
+    OverwrittenRead,
+}
+
+impl Lint {
+    /// Returns [`Level::Error`] if the program fails when it runs into `self`, else [`Level::Warning`].
+    #[must_use]
+    pub const fn level(self) -> Level {
+        match self {
+            Lint::UninitRead(_) | Lint::MissingStop | Lint::InexistentJump(_) => Level::Error,
+            Lint::Unreachable(_) | Lint::DivByZero | Lint::OverwrittenRead => Level::Warning,
+        }
+    }
+    
+    /// Returns the name of `self`'s variant, e.g. `MissingStop`.
+    #[must_use]
+    pub const fn kind(self) -> &'static str {
+        match self {
+            Lint::UninitRead(_) => "UninitRead",
+            Lint::MissingStop => "MissingStop",
+            Lint::InexistentJump(_) => "InexistentJump",
+            Lint::Unreachable(_) => "Unreachable",
+            Lint::DivByZero => "DivByZero",
+            Lint::OverwrittenRead => "OverwrittenRead",
+        }
+    }
+}
+
+impl Display for Lint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Lint::UninitRead(0) => f.write_str("ACC is read before being written"),
+            Lint::UninitRead(n) => write!(f, "R{n} is read before being written"),
+            Lint::MissingStop => f.write_str("the program may run past its end"),
+            Lint::InexistentJump(adr) => write!(f, "jumping to inexistent instruction {adr}"),
+            Lint::Unreachable(1) => f.write_str("unreachable instruction"),
+            Lint::Unreachable(n) => write!(f, "{n} unreachable instructions"),
+            Lint::DivByZero => f.write_str("division by zero"),
+            Lint::OverwrittenRead => f.write_str("the value read is overwritten by the next instruction"),
+        }
+    }
+}
+
+/// A problem found at an instruction, see [`lint`].
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Finding {
+    pub ir: Ir,
+    pub lint: Lint,
+    
+    /// How to fix the problem.
+    pub fix: String,
+}
+
+impl Finding {
+    /// Reports `self`, showing the code around its instruction.
+    pub fn diagnostic<T: Integer>(&self, code: &RoCode<T>) -> Diagnostic {
+        Diagnostic::new(self.lint.level(), self.lint)
+            .code(code, self.ir)
+            .kind(self.lint.kind())
+            .help(&self.fix)
+    }
+}
+
+/// The registers that may have been written, right before executing an instruction.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+struct Written {
+    regs: BTreeSet<usize>,
+    /// Any register may have been written.
+    all: bool,
+}
+
+impl Written {
+    fn contains(&self, loc: usize) -> bool {
+        self.all || self.regs.contains(&loc)
+    }
+    
+    fn write<L: Loc>(&mut self, reg: Register<L>) {
+        match reg {
+            Register::Direct(loc) => { self.regs.insert(loc.raw()); },
+            Register::Indirect(_) => self.all = true,
+        }
+    }
+    
+    /// Merges `other` into `self`, returning `true` iff `self` changed.
+    fn join(&mut self, other: &Written) -> bool {
+        let len = self.regs.len();
+        let all = self.all;
+        
+        self.regs.extend(other.regs.iter().copied());
+        self.all |= other.all;
+        
+        self.regs.len() != len || self.all != all
+    }
+}
+
+/// Returns the registers read by an instruction, `0` being ACC.
+///
+/// Only the pointer of an indirect register is returned, as the register it points to isn't known.
+fn reads<T: Integer>(inst: Instruction<T>) -> Vec<usize> {
+    let loc = |reg: Register<RoLoc>| match reg {
+        Register::Direct(loc) => loc.raw(),
+        Register::Indirect(ptr) => ptr.raw(),
+    };
+    
+    let acc = matches!(inst,
+        Instruction::Write | Instruction::Store(_)
+        | Instruction::Add(_) | Instruction::Sub(_) | Instruction::Mul(_) | Instruction::Div(_) | Instruction::Mod(_)
+        | Instruction::JumpZero(_) | Instruction::JumpLtz(_) | Instruction::JumpGtz(_)
+    );
+    
+    let mut reads = Vec::with_capacity(2);
+    if acc {
+        reads.push(0);
+    }
+    
+    if let Some(reg) = inst.register() {
+        reads.push(loc(reg));
+    }
+    
+    if let Instruction::Store(Register::Indirect(ptr)) = inst {
+        reads.push(ptr.raw());
+    }
+    
+    reads
+}
+
+/// Returns the instructions that may be executed right after the specified one.
+fn successors<T: Integer>(code: &RoCode<T>, ir: Ir, inst: Instruction<T>) -> Vec<Ir> {
+    let len = code.len();
+    
+    #[cfg(feature = "indirect_jumps")]
+    if inst.indirect_jump().is_some() {
+        // May jump anywhere
+        return (0..len).map(Ir::new).collect();
+    }
+    
+    let mut next = Vec::with_capacity(2);
+    if let Some(adr) = inst.target().filter(|adr| *adr < len) {
+        next.push(adr);
+    }
+    
+    if !matches!(inst, Instruction::Stop | Instruction::Jump(_)) && ir.inner() + 1 < len {
+        next.push(ir + 1);
+    }
+    
+    next
+}
+
+/// Finds the problems of some code without running it, in order.
+///
+/// Are reported:
+/// - the reads of registers that aren't written on any path leading to them;
+/// - the last instruction if the execution may run past it;
+/// - the jumps past the end of the program;
+/// - the unreachable code, except lone `STOP`s guarding the end of the program;
+/// - `DIV #0` and `MOD #0`;
+/// - `READ`s whose value is immediately overwritten.
+///
+/// Only the reachable instructions are checked, the other ones being reported as unreachable.
+#[must_use]
+pub fn lint<T: Integer>(code: &RoCode<T>) -> Vec<Finding> {
+    let len = code.len();
+    let mut findings = Vec::new();
+    
+    // The registers that may have been written before each reachable instruction
+    let mut states = vec![None::<Written>; len];
+    let mut worklist = vec![Ir::default()];
+    states[0] = Some(Written::default());
+    
+    while let Some(ir) = worklist.pop() {
+        let inst = code.get(ir).expect("only existing instructions are queued");
+        let mut state = states[ir.inner()].clone().expect("only reachable instructions are queued");
+        
+        match inst {
+            Instruction::Read | Instruction::Load(_) => state.write(Register::Direct(WoLoc::from(0))),
+            Instruction::Store(reg) => state.write(reg),
+            Instruction::Increment(reg) | Instruction::Decrement(reg) => state.write(reg),
+            _ => {},
+        }
+        
+        for next in successors(code, ir, inst) {
+            let changed = match &mut states[next.inner()] {
+                Some(next) => next.join(&state),
+                next @ None => {
+                    *next = Some(state.clone());
+                    true
+                },
+            };
+            
+            if changed {
+                worklist.push(next);
+            }
+        }
+    }
+    
+    let mut unreachable = 0;
+    for (ir, inst) in code.enumerate() {
+        let Some(state) = &states[ir.inner()] else {
+            unreachable += 1;
+            
+            // Report the unreachable instructions once, at the first of them
+            let last = !states.get(ir.inner() + 1).is_some_and(Option::is_none);
+            if last {
+                let first = Ir::new(ir.inner() + 1 - unreachable);
+                let guard = code.iter().skip(first.inner()).take(unreachable).all(|inst| inst == Instruction::Stop);
+                if !guard {
+                    let fix = if unreachable == 1 { "remove it, or jump to it" } else { "remove them, or jump to the first of them" };
+                    findings.push(Finding { ir: first, lint: Lint::Unreachable(unreachable), fix: fix.to_owned() });
+                }
+                
+                unreachable = 0;
+            }
+            
+            continue;
+        };
+        
+        let mut found = |lint: Lint, fix: String| findings.push(Finding { ir, lint, fix });
+        
+        for loc in reads(inst) {
+            if !state.contains(loc) {
+                let fix = if loc == 0 { "set ACC first, e.g. with `READ` or `LOAD #0`".to_owned() } else { format!("write R{loc} first, e.g. with `LOAD #0` then `STORE {loc}`") };
+                found(Lint::UninitRead(loc), fix);
+            }
+        }
+        
+        if let Some(adr) = inst.target().filter(|adr| *adr >= len) {
+            let fix = if adr == len { "add a `STOP` at the end of the program".to_owned() } else { format!("jump to one of the {len} instructions") };
+            found(Lint::InexistentJump(adr), fix);
+        }
+        
+        if ir.inner() + 1 == len && !matches!(inst, Instruction::Stop | Instruction::Jump(_)) {
+            found(Lint::MissingStop, "add a `STOP` after it".to_owned());
+        }
+        
+        if let Instruction::Div(Value::Constant(v)) | Instruction::Mod(Value::Constant(v)) = inst {
+            if v.is_zero() {
+                found(Lint::DivByZero, "use a non-zero divisor".to_owned());
+            }
+        }
+        
+        if inst == Instruction::Read {
+            let overwritten = match code.get(ir + 1) {
+                Some(Instruction::Read | Instruction::Load(Value::Constant(_))) => true,
+                Some(Instruction::Load(Value::Register(Register::Direct(loc)))) => loc.raw() != 0,
+                _ => false,
+            };
+            
+            if overwritten {
+                found(Lint::OverwrittenRead, "store the value first, e.g. with `STORE 1`".to_owned());
+            }
+        }
+    }
+    
+    findings
+}
+
+#[cfg(test)]
+mod test {
+    use crate::analysis::{lint, Lint};
+    use crate::model::{Ir, RoCode};
+    use crate::inst;
+    use std::str::FromStr;
+    
+    #[test]
+    fn findings() {
+        let lints = |code: &RoCode<i32>| lint(code).into_iter().map(|finding| (finding.ir.inner(), finding.lint)).collect::<Vec<_>>();
+        
+        let code = RoCode::from_str("
+            READ
+            JUMZ end
+            STORE 1
+            LOAD 2      ; never written
+            ADD 1       ; written on some path
+        end:
+            DIV #0
+            JUMP done
+            WRITE
+            WRITE
+        done:
+            STOP
+            STOP
+        ").unwrap();
+        
+        assert_eq!(lints(&code), [
+            (3, Lint::UninitRead(2)),
+            (5, Lint::DivByZero),
+            (7, Lint::Unreachable(2)),
+        ]);
+        
+        let code = RoCode::from([
+            inst!(LOAD 0),
+            inst!(READ),
+            inst!(READ),
+            inst!(LOAD #1),
+            inst!(JUMG 7),
+            inst!(WRITE),
+        ]);
+        
+        assert_eq!(lints(&code), [
+            (0, Lint::UninitRead(0)),
+            (1, Lint::OverwrittenRead),
+            (2, Lint::OverwrittenRead),
+            (4, Lint::InexistentJump(Ir::new(7))),
+            (5, Lint::MissingStop),
+        ]);
+        
+        let finding = &lint(&code)[4];
+        assert_eq!(finding.diagnostic(&code).to_string(), "error: the program may run past its end\n --> anon:6\n  |\n4 | LOAD #1\n5 | JUMG 7\n6 | WRITE\n  | ^^^^^\n  |\n  = help: add a `STOP` after it");
+    }
+}
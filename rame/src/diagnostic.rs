@@ -0,0 +1,386 @@
+//! Renders errors with the code around them.
+//!
+//! ```text
+//! error: integer overflow
+//!  --> a.ram:4
+//!   |
+//! 3 | LOAD #1
+//! 4 | DIV #0
+//!   | ^^^^^^
+//!   |
+//!   = note: ACC = 1
+//! ```
+
+use std::env;
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, IsTerminal as _};
+use std::ops::Range;
+
+#[cfg(feature = "clap")]
+use clap::builder::PossibleValue;
+
+/// How [`Diagnostic::emit`] prints diagnostics.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum Format {
+    /// Rendered for humans, see [`Diagnostic::render`].
+    #[default]
+    Human,
+    
+    /// One JSON object per line, see [`Diagnostic::to_json`].
+    Json,
+}
+
+#[cfg(feature = "clap")]
+impl clap::ValueEnum for Format {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[Format::Human, Format::Json]
+    }
+    
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        Some(match self {
+            Format::Human => PossibleValue::new("human").help("Rendered with the code around them"),
+            Format::Json => PossibleValue::new("json").help("One JSON object per line, with the error kind, file, line, IR, instruction, ACC and register values"),
+        })
+    }
+}
+
+/// The severity of a [`Diagnostic`], or the kind of one of its entries.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Level {
+    Error,
+    Warning,
+    /// Suggests a fix.
+    Help,
+    /// Gives some context.
+    Note,
+}
+
+impl Level {
+    /// The ANSI escape sequence this level is colored with.
+    const fn color(self) -> &'static str {
+        match self {
+            Level::Error => "\x1b[1;31m",
+            Level::Warning => "\x1b[1;33m",
+            Level::Help => "\x1b[1;36m",
+            Level::Note => "\x1b[1;32m",
+        }
+    }
+}
+
+impl Display for Level {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Level::Error => "error",
+            Level::Warning => "warning",
+            Level::Help => "help",
+            Level::Note => "note",
+        })
+    }
+}
+
+/// A line of code shown by a [`Diagnostic`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Line {
+    /// The line number.
+    nb: usize,
+    
+    text: String,
+    
+    /// The underlined characters, if any.
+    mark: Option<Range<usize>>,
+}
+
+/// An error, the code around it, and some `help:` and `note:` entries.
+///
+/// Some structured data can also be attached for [`Format::Json`], e.g. [`Diagnostic::acc`].
+///
+/// # Example
+///
+/// ```
+/// # use rame::diagnostic::Diagnostic;
+/// let diag = Diagnostic::error("integer overflow")
+///     .at("a.ram:4")
+///     .line(3, "LOAD #1")
+///     .marked_line(4, "DIV #0", 0..6)
+///     .note("ACC = 1");
+///
+/// assert_eq!(diag.to_string(), "error: integer overflow\n --> a.ram:4\n  |\n3 | LOAD #1\n4 | DIV #0\n  | ^^^^^^\n  |\n  = note: ACC = 1");
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[must_use]
+pub struct Diagnostic {
+    level: Level,
+    message: String,
+    
+    /// Where the error is, e.g. `a.ram:4`.
+    origin: Option<String>,
+    
+    lines: Vec<Line>,
+    entries: Vec<(Level, String)>,
+    
+    /// The name of the error's variant, e.g. `IntegerOverfow`.
+    kind: Option<&'static str>,
+    
+    /// The file and line of the error.
+    file: Option<String>,
+    line: Option<usize>,
+    
+    /// The index and the text of the failing instruction.
+    ir: Option<usize>,
+    inst: Option<String>,
+    
+    /// The value of ACC, `None` if unknown or uninitialized.
+    acc: Option<String>,
+    
+    /// The values of the registers used by the failing instruction, `None` if uninitialized.
+    registers: Vec<(usize, Option<String>)>,
+}
+
+impl Diagnostic {
+    pub fn new(level: Level, message: impl Display) -> Diagnostic {
+        Diagnostic {
+            level,
+            message: message.to_string(),
+            origin: None,
+            lines: Vec::new(),
+            entries: Vec::new(),
+            kind: None,
+            file: None,
+            line: None,
+            ir: None,
+            inst: None,
+            acc: None,
+            registers: Vec::new(),
+        }
+    }
+    
+    pub fn error(message: impl Display) -> Diagnostic {
+        Diagnostic::new(Level::Error, message)
+    }
+    
+    pub fn warning(message: impl Display) -> Diagnostic {
+        Diagnostic::new(Level::Warning, message)
+    }
+    
+    /// Sets where the error is, e.g. `a.ram:4` or `fibo.algo:5 in fibo()`.
+    pub fn at(mut self, origin: impl Display) -> Diagnostic {
+        self.origin = Some(origin.to_string());
+        self
+    }
+    
+    /// Shows a line of code.
+    pub fn line(mut self, nb: usize, text: impl Into<String>) -> Diagnostic {
+        self.lines.push(Line { nb, text: text.into(), mark: None });
+        self
+    }
+    
+    /// Shows a line of code, with some characters underlined.
+    pub fn marked_line(mut self, nb: usize, text: impl Into<String>, mark: Range<usize>) -> Diagnostic {
+        self.lines.push(Line { nb, text: text.into(), mark: Some(mark) });
+        self
+    }
+    
+    /// Adds a `help:` entry, suggesting a fix.
+    pub fn help(mut self, msg: impl Display) -> Diagnostic {
+        self.entries.push((Level::Help, msg.to_string()));
+        self
+    }
+    
+    /// Adds a `note:` entry, giving some context.
+    pub fn note(mut self, msg: impl Display) -> Diagnostic {
+        self.entries.push((Level::Note, msg.to_string()));
+        self
+    }
+    
+    /// Sets the name of the error's variant, e.g. `IntegerOverfow`.
+    pub const fn kind(mut self, kind: &'static str) -> Diagnostic {
+        self.kind = Some(kind);
+        self
+    }
+    
+    /// Sets the file and the line of the error, the file being `None` if unknown.
+    pub fn position(mut self, file: Option<impl Display>, line: usize) -> Diagnostic {
+        self.file = file.map(|file| file.to_string());
+        self.line = Some(line);
+        self
+    }
+    
+    /// Sets the index of the failing instruction, `None` if it couldn't be parsed, and its text.
+    pub fn inst(mut self, ir: Option<usize>, inst: impl Display) -> Diagnostic {
+        self.ir = ir;
+        self.inst = Some(inst.to_string());
+        self
+    }
+    
+    /// Sets the value of ACC, `None` if uninitialized, and adds a `note:` entry showing it.
+    pub fn acc(self, acc: Option<impl Display>) -> Diagnostic {
+        let diag = self.acc_value(acc);
+        let note = format!("ACC = {}", diag.acc.as_deref().unwrap_or("<uninitialized>"));
+        diag.note(note)
+    }
+    
+    /// Sets the value of ACC, `None` if uninitialized, without showing it.
+    pub fn acc_value(mut self, acc: Option<impl Display>) -> Diagnostic {
+        self.acc = acc.map(|acc| acc.to_string());
+        self
+    }
+    
+    /// Sets the value of a register, `None` if uninitialized, and adds a `note:` entry showing it.
+    pub fn register(self, n: usize, value: Option<impl Display>) -> Diagnostic {
+        let value = value.map(|value| value.to_string());
+        let note = format!("R{n} = {}", value.as_deref().unwrap_or("<uninitialized>"));
+        self.register_value(n, value).note(note)
+    }
+    
+    /// Sets the value of a register, `None` if uninitialized, without showing it.
+    pub fn register_value(mut self, n: usize, value: Option<impl Display>) -> Diagnostic {
+        self.registers.push((n, value.map(|value| value.to_string())));
+        self
+    }
+    
+    /// Returns the message, e.g. `integer overflow`.
+    #[must_use]
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+    
+    /// Renders `self`, with ANSI colors if `color` is `true`.
+    #[must_use]
+    pub fn render(&self, color: bool) -> String {
+        let mut out = String::new();
+        self.write(&mut out, color).expect("writing into a `String` doesn't fail");
+        out
+    }
+    
+    fn write<W: fmt::Write>(&self, out: &mut W, color: bool) -> fmt::Result {
+        let paint = |s: &str, style: &str| if color { format!("{style}{s}\x1b[0m") } else { s.to_owned() };
+        let gutter = |s: &str| paint(s, "\x1b[1;34m");
+        
+        let width = self.lines.iter().map(|l| l.nb.to_string().len()).max().unwrap_or(1);
+        let pad = " ".repeat(width);
+        
+        write!(out, "{}{}", paint(&self.level.to_string(), self.level.color()), paint(&format!(": {}", self.message), "\x1b[1m"))?;
+        
+        if let Some(origin) = &self.origin {
+            write!(out, "\n{pad}{} {origin}", gutter("-->"))?;
+        }
+        
+        if !self.lines.is_empty() {
+            write!(out, "\n{pad} {}", gutter("|"))?;
+            
+            for Line { nb, text, mark } in &self.lines {
+                write!(out, "\n{} {text}", gutter(&format!("{nb:>width$} |")))?;
+                
+                if let Some(mark) = mark {
+                    let carets = "^".repeat(mark.len().max(1));
+                    write!(out, "\n{pad} {} {}{}", gutter("|"), " ".repeat(mark.start), paint(&carets, self.level.color()))?;
+                }
+            }
+            
+            write!(out, "\n{pad} {}", gutter("|"))?;
+        }
+        
+        for (level, msg) in &self.entries {
+            write!(out, "\n{pad} {} {}: {msg}", gutter("="), paint(&level.to_string(), "\x1b[1m"))?;
+        }
+        
+        Ok(())
+    }
+    
+    /// Returns `self` as a single-line JSON object.
+    ///
+    /// Its keys are always present, unknown values being `null`:
+    /// `level`, `kind`, `message`, `file`, `line`, `ir`, `instruction`, `acc`,
+    /// `registers` (objects with a `register` and a `value`), `help` and `notes` (strings).
+    /// ACC and register values are numbers.
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        fn string(s: &str) -> String {
+            let mut json = String::with_capacity(s.len() + 2);
+            json.push('"');
+            for c in s.chars() {
+                match c {
+                    '"' => json.push_str("\\\""),
+                    '\\' => json.push_str("\\\\"),
+                    '\n' => json.push_str("\\n"),
+                    c if c.is_control() => {
+                        json.push_str("\\u");
+                        json.extend([12, 8, 4, 0].map(|shift| char::from_digit((u32::from(c) >> shift) & 0xf, 16).unwrap_or('0')));
+                    },
+                    c => json.push(c),
+                }
+            }
+            json.push('"');
+            json
+        }
+        
+        fn or_null<V>(v: Option<V>, f: impl FnOnce(V) -> String) -> String {
+            v.map_or_else(|| "null".to_owned(), f)
+        }
+        
+        let entries = |level: Level| {
+            let entries = self.entries.iter().filter(|(l, _)| *l == level).map(|(_, msg)| string(msg)).collect::<Vec<_>>();
+            format!("[{}]", entries.join(","))
+        };
+        
+        let registers = self.registers.iter()
+            .map(|(n, value)| format!("{{\"register\":{n},\"value\":{}}}", or_null(value.as_ref(), Clone::clone)))
+            .collect::<Vec<_>>();
+        
+        format!(
+            "{{\"level\":{},\"kind\":{},\"message\":{},\"file\":{},\"line\":{},\"ir\":{},\"instruction\":{},\"acc\":{},\"registers\":[{}],\"help\":{},\"notes\":{}}}",
+            string(&self.level.to_string()),
+            or_null(self.kind, string),
+            string(&self.message),
+            or_null(self.file.as_deref(), string),
+            or_null(self.line, |line| line.to_string()),
+            or_null(self.ir, |ir| ir.to_string()),
+            or_null(self.inst.as_deref(), string),
+            or_null(self.acc.as_ref(), Clone::clone),
+            registers.join(","),
+            entries(Level::Help),
+            entries(Level::Note),
+        )
+    }
+    
+    /// Prints `self` on stderr in `format`.
+    ///
+    /// Human-readable diagnostics are colored if stderr is a terminal and `NO_COLOR` isn't set.
+    pub fn emit(&self, format: Format) {
+        match format {
+            Format::Human => {
+                let color = io::stderr().is_terminal() && !env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+                eprintln!("{}", self.render(color));
+            },
+            Format::Json => eprintln!("{}", self.to_json()),
+        }
+    }
+}
+
+impl Display for Diagnostic {
+    /// Renders `self` without colors.
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.write(f, false)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::diagnostic::Diagnostic;
+    
+    #[test]
+    fn json() {
+        let diag = Diagnostic::error("integer overflow\n")
+            .kind("IntegerOverfow")
+            .position(None::<&str>, 3)
+            .inst(Some(2), "ADD 1")
+            .acc(Some(127))
+            .register(1, None::<i8>)
+            .help("using `--bits=8`");
+        
+        assert_eq!(
+            diag.to_json(),
+            r#"{"level":"error","kind":"IntegerOverfow","message":"integer overflow\n","file":null,"line":3,"ir":2,"instruction":"ADD 1","acc":127,"registers":[{"register":1,"value":null}],"help":["using `--bits=8`"],"notes":["ACC = 127","R1 = <uninitialized>"]}"#,
+        );
+    }
+}
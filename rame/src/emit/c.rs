@@ -0,0 +1,595 @@
+use crate::emit::Options;
+use crate::model::{Instruction, Integer, Ir, Loc, Register, RoCode, Value};
+use sealed::sealed;
+use std::collections::BTreeSet;
+use std::fmt::{self, Display, Formatter};
+
+#[cfg(feature = "indirect_jumps")]
+use crate::model::Address;
+
+/// Displays some code as a standalone C program, see [`C::new`].
+///
+/// The program reads its input from its arguments, e.g. `./a.out 1,2 3`, then prompts for the rest on stdin;
+/// and prints `Output = [...]` once stopped, like `rame-run`. Errors are printed with the failing instruction,
+/// and the program then exits with `1`.
+///
+/// The integers are the [C type](CInteger::C_TYPE) of `T`, e.g. `int16_t` for `i16`, or `__int128`,
+/// with the overflow checks of GCC and Clang's `__builtin_*_overflow`.
+///
+/// # Example
+///
+/// ```
+/// # use rame::emit::C;
+/// # use rame::inst;
+/// # use rame::model::RoCode;
+/// let code = RoCode::<i32>::from([
+///     inst!(READ),
+///     inst!(ADD #1),
+///     inst!(WRITE),
+///     inst!(STOP),
+/// ]);
+///
+/// let c = C::new(&code).to_string();
+/// assert!(c.contains("typedef int32_t ram_t;"));
+/// assert!(c.contains("if (__builtin_add_overflow(acc, v, &acc)) ram_fail(1, \"integer overflow\");"));
+/// ```
+#[derive(Copy, Clone, Debug)]
+#[must_use]
+pub struct C<'c, T: CInteger> {
+    code: &'c RoCode<T>,
+    options: Options,
+}
+
+impl<'c, T: CInteger> C<'c, T> {
+    pub const fn new(code: &'c RoCode<T>) -> C<'c, T> {
+        C {
+            code,
+            options: Options::new(),
+        }
+    }
+    
+    /// Sets which checks of [`Ram`](crate::runner::Ram) are kept.
+    pub const fn options(mut self, options: Options) -> C<'c, T> {
+        self.options = options;
+        self
+    }
+}
+
+/// The definitions shared by every program, after `ram_t`, `RAM_*` and `ram_code`.
+const PRELUDE: &str = r#"static ram_t *ram_memory;
+#if RAM_UNINIT_CHECKS
+static unsigned char *ram_init;
+#endif
+
+static ram_t *ram_args, *ram_output;
+static size_t ram_args_len, ram_args_cap, ram_output_len, ram_output_cap;
+
+/* The count of values read. */
+static size_t ram_read_count;
+
+/* Formats `v` in decimal, at the end of `buf`. */
+static const char *ram_format(char buf[48], ram_t v) {
+    char *p = buf + 47;
+    int neg = v < 0;
+    *p = '\0';
+    do {
+        int d = (int)(v % 10);
+        *--p = (char)('0' + (d < 0 ? -d : d));
+        v /= 10;
+    } while (v != 0);
+    if (neg) *--p = '-';
+    return p;
+}
+
+/* Parses a decimal integer, returning 0 if invalid or out of range. */
+static int ram_parse(const char *s, ram_t *out) {
+    ram_t v = 0;
+    int neg;
+    while (isspace((unsigned char)*s)) s++;
+    neg = *s == '-';
+    if (*s == '-' || *s == '+') s++;
+    if (!isdigit((unsigned char)*s)) return 0;
+    for (; isdigit((unsigned char)*s); s++) {
+        int d = *s - '0';
+        if (__builtin_mul_overflow(v, 10, &v)) return 0;
+        if (neg ? __builtin_sub_overflow(v, d, &v) : __builtin_add_overflow(v, d, &v)) return 0;
+    }
+    while (isspace((unsigned char)*s)) s++;
+    *out = v;
+    return *s == '\0';
+}
+
+static void ram_push(ram_t **vec, size_t *len, size_t *cap, ram_t v) {
+    if (*len == *cap) {
+        *cap = *cap ? 2 * *cap : 16;
+        *vec = realloc(*vec, *cap * sizeof **vec);
+        if (!*vec) {
+            fputs("error: out of memory\n", stderr);
+            exit(1);
+        }
+    }
+    (*vec)[(*len)++] = v;
+}
+
+static _Noreturn void ram_fail(size_t ir, const char *msg) {
+    fflush(stdout);
+    fprintf(stderr, "error: %s\n", msg);
+    if (ram_code[ir]) fprintf(stderr, " --> %zu | %s\n", ir, ram_code[ir]);
+    exit(1);
+}
+
+static inline _Noreturn void ram_fail_address(size_t ir, ram_t adr, int read) {
+    char buf[48], msg[128];
+    if (adr < 0) snprintf(msg, sizeof msg, "invalid address R%s: out of range integral type conversion attempted", ram_format(buf, adr));
+    else if (read && RAM_UNINIT_CHECKS) snprintf(msg, sizeof msg, "reading uninitialized memory R%s", ram_format(buf, adr));
+    else snprintf(msg, sizeof msg, "accessing R%s exceeds the memory limit of %lld registers", ram_format(buf, adr), RAM_MEMORY_LIMIT);
+    ram_fail(ir, msg);
+}
+
+static inline ram_t ram_load(size_t ir, size_t adr) {
+#if RAM_UNINIT_CHECKS
+    if (!ram_init[adr]) {
+        char msg[64];
+        snprintf(msg, sizeof msg, "reading uninitialized memory R%zu", adr);
+        ram_fail(ir, msg);
+    }
+#else
+    (void)ir;
+#endif
+    return ram_memory[adr];
+}
+
+static inline void ram_store(size_t adr, ram_t v) {
+    ram_memory[adr] = v;
+#if RAM_UNINIT_CHECKS
+    ram_init[adr] = 1;
+#endif
+}
+
+/* Returns the address held by the register `ptr`, to be read if `read`. */
+static inline size_t ram_address(size_t ir, size_t ptr, int read) {
+    ram_t adr = ram_load(ir, ptr);
+    if (adr < 0 || adr >= RAM_MEMORY_LIMIT) ram_fail_address(ir, adr, read);
+    return (size_t)adr;
+}
+
+/* Reads the arguments, then prompts on stdin; returns 0 if there's nothing left to read. */
+static inline int ram_read(ram_t *v) {
+    char line[256];
+    if (ram_read_count < ram_args_len) {
+        *v = ram_args[ram_read_count++];
+        return 1;
+    }
+    for (;;) {
+        const char *s = line;
+        printf("E%zu = ", ram_read_count);
+        fflush(stdout);
+        if (!fgets(line, sizeof line, stdin)) {
+            puts("<eof>");
+            return 0;
+        }
+        if (ram_parse(line, v)) {
+            ram_read_count++;
+            return 1;
+        }
+        while (isspace((unsigned char)*s)) s++;
+        if (*s == '\0') return 0;
+        fprintf(stderr, "error: invalid integer `%.*s`\n", (int)strcspn(s, "\r\n"), s);
+    }
+}
+
+static inline void ram_write(ram_t v) {
+    ram_push(&ram_output, &ram_output_len, &ram_output_cap, v);
+}
+
+static inline int ram_stop(void) {
+    char buf[48];
+    size_t i;
+    printf("Output = [");
+    for (i = 0; i < ram_output_len; i++) printf("%s%s", i ? ", " : "", ram_format(buf, ram_output[i]));
+    puts("]");
+    return 0;
+}
+
+static void ram_start(int argc, char **argv) {
+    int i;
+    ram_memory = calloc(RAM_MEMORY_LIMIT, sizeof *ram_memory);
+#if RAM_UNINIT_CHECKS
+    ram_init = calloc(RAM_MEMORY_LIMIT, 1);
+    if (!ram_init) ram_memory = NULL;
+#endif
+    if (!ram_memory) {
+        fputs("error: out of memory\n", stderr);
+        exit(1);
+    }
+    for (i = 1; i < argc; i++) {
+        char *arg;
+        for (arg = strtok(argv[i], ","); arg; arg = strtok(NULL, ",")) {
+            ram_t v;
+            if (!ram_parse(arg, &v)) {
+                fprintf(stderr, "error: invalid integer %s\n", arg);
+                exit(1);
+            }
+            ram_push(&ram_args, &ram_args_len, &ram_args_cap, v);
+        }
+    }
+}
+"#;
+
+/// An integer type that has a C equivalent, see [`C`].
+#[sealed]
+pub trait CInteger: Integer {
+    /// The C type, e.g. `int16_t` for `i16`.
+    const C_TYPE: &'static str;
+}
+
+macro_rules! c_integers {
+    ($($t:ty => $c:literal,)*) => {$(
+        #[sealed]
+        impl CInteger for $t {
+            const C_TYPE: &'static str = $c;
+        }
+    )*};
+}
+
+c_integers! {
+    i8 => "int8_t",
+    i16 => "int16_t",
+    i32 => "int32_t",
+    i64 => "int64_t",
+    i128 => "__int128",
+    u8 => "uint8_t",
+    u16 => "uint16_t",
+    u32 => "uint32_t",
+    u64 => "uint64_t",
+    u128 => "unsigned __int128",
+}
+
+/// Returns a C expression of `v`, whose type fits in `ram_t`.
+fn constant<T: Integer>(v: T) -> String {
+    match (v.to_i32(), v.to_i64(), v.to_u64()) {
+        (Some(v), _, _) => v.to_string(),
+        (_, Some(i64::MIN), _) => "INT64_MIN".to_owned(),
+        (_, Some(v), _) => format!("INT64_C({v})"),
+        (_, _, Some(v)) => format!("UINT64_C({v})"),
+        _ => {
+            // No 128-bit literals
+            #[expect(clippy::cast_sign_loss, reason = "two's complement")]
+            let bits = v.to_i128().map_or_else(|| v.to_u128().expect("at most 128 bits"), |v| v as u128);
+            format!("(ram_t)((unsigned __int128)UINT64_C({}) << 64 | UINT64_C({}))", bits >> 64, bits & u128::from(u64::MAX))
+        },
+    }
+}
+
+impl<T: CInteger> C<'_, T> {
+    /// Returns the C expression of a register's address,
+    /// or the statement failing if it's past the memory limit.
+    fn address<L: Loc>(&self, ir: Ir, reg: Register<L>) -> Result<String, String> {
+        let (adr, read, expr) = match reg {
+            Register::Direct(adr) => (adr.raw(), L::READ, adr.raw().to_string()),
+            Register::Indirect(ptr) => (ptr.raw(), true, format!("ram_address({ir}, {}, {})", ptr.raw(), u8::from(L::READ))),
+        };
+        
+        if adr < self.options.memory_limit {
+            Ok(expr)
+        }
+        else {
+            Err(self.past_limit(ir, adr, read))
+        }
+    }
+    
+    /// Returns the statement failing on reading (if `read`) or writing a register past the memory limit.
+    fn past_limit(&self, ir: Ir, adr: usize, read: bool) -> String {
+        if read && self.options.uninit_checks {
+            format!("ram_fail({ir}, \"reading uninitialized memory R{adr}\");")
+        }
+        else {
+            format!("ram_fail({ir}, \"accessing R{adr} exceeds the memory limit of {} registers\");", self.options.memory_limit)
+        }
+    }
+    
+    /// Returns the C expression of a value, see [`C::address`].
+    fn value(&self, ir: Ir, v: Value<T>) -> Result<String, String> {
+        match v {
+            Value::Constant(v) => Ok(constant(v)),
+            Value::Register(reg) => self.address(ir, reg).map(|adr| format!("ram_load({ir}, {adr})")),
+        }
+    }
+    
+    /// Returns the statement computing `lhs op rhs` into `lhs`.
+    fn arith(&self, ir: Ir, op: &str, lhs: &str, rhs: &str) -> String {
+        if self.options.overflow_checks {
+            format!("if (__builtin_{op}_overflow({lhs}, {rhs}, &{lhs})) ram_fail({ir}, \"integer overflow\");")
+        }
+        else {
+            format!("(void)__builtin_{op}_overflow({lhs}, {rhs}, &{lhs});")
+        }
+    }
+    
+    /// Returns the statement jumping to an address.
+    fn jump(&self, ir: Ir, inst: Instruction<T>) -> String {
+        #[cfg(feature = "indirect_jumps")]
+        if let Some(Address::Register(ptr)) = inst.jump() {
+            if ptr.raw() >= self.options.memory_limit {
+                return self.past_limit(ir, ptr.raw(), true);
+            }
+            
+            return format!("{{ ram_target = ram_load({ir}, {}); ram_jump_ir = {ir}; goto ram_dispatch; }}", ptr.raw());
+        }
+        
+        inst.target()
+            .filter(|adr| adr.inner() < self.code.len())
+            .map_or_else(|| format!("ram_fail({ir}, \"jumping to an inexistent location\");"), |adr| format!("goto L{adr};"))
+    }
+    
+    /// Returns the statements running an instruction.
+    fn inst(&self, ir: Ir, inst: Instruction<T>) -> Vec<String> {
+        let mut stmts = Vec::new();
+        
+        macro_rules! tri {
+            ($e:expr) => {
+                match $e {
+                    Ok(e) => e,
+                    Err(fail) => {
+                        stmts.push(fail);
+                        return stmts;
+                    },
+                }
+            };
+        }
+        
+        match inst {
+            Instruction::Read => {
+                stmts.push("ram_t v;".to_owned());
+                stmts.push(format!("if (!ram_read(&v)) ram_fail({ir}, \"nothing left to read\");"));
+                stmts.push("ram_store(0, v);".to_owned());
+            },
+            Instruction::Write => stmts.push(format!("ram_write(ram_load({ir}, 0));")),
+            Instruction::Load(v) => {
+                let v = tri!(self.value(ir, v));
+                stmts.push(format!("ram_store(0, {v});"));
+            },
+            Instruction::Store(reg) => match self.address(ir, reg) {
+                Ok(adr) => {
+                    stmts.push(format!("ram_t acc = ram_load({ir}, 0);"));
+                    stmts.push(format!("ram_store({adr}, acc);"));
+                },
+                Err(fail) => {
+                    stmts.push(format!("(void)ram_load({ir}, 0);"));
+                    stmts.push(fail);
+                },
+            },
+            Instruction::Increment(reg) | Instruction::Decrement(reg) => {
+                let adr = tri!(self.address(ir, reg));
+                stmts.push(format!("size_t adr = {adr};"));
+                stmts.push(format!("ram_t v = ram_load({ir}, adr);"));
+                stmts.push(self.arith(ir, if matches!(inst, Instruction::Increment(_)) { "add" } else { "sub" }, "v", "1"));
+                stmts.push("ram_store(adr, v);".to_owned());
+            },
+            Instruction::Add(v) | Instruction::Sub(v) | Instruction::Mul(v) | Instruction::Div(v) | Instruction::Mod(v) => {
+                stmts.push(format!("ram_t acc = ram_load({ir}, 0);"));
+                let v = tri!(self.value(ir, v));
+                stmts.push(format!("ram_t v = {v};"));
+                
+                let op = match inst {
+                    Instruction::Add(_) => "add",
+                    Instruction::Sub(_) => "sub",
+                    Instruction::Mul(_) => "mul",
+                    _ => {
+                        // Undefined behavior in C
+                        let overflow = if T::min_value() < T::zero() { " || (acc == RAM_MIN && v == -1)" } else { "" };
+                        stmts.push(format!("if (v == 0{overflow}) ram_fail({ir}, \"integer overflow\");"));
+                        stmts.push(format!("ram_store(0, acc {} v);", if matches!(inst, Instruction::Div(_)) { '/' } else { '%' }));
+                        return stmts;
+                    },
+                };
+                
+                stmts.push(self.arith(ir, op, "acc", "v"));
+                stmts.push("ram_store(0, acc);".to_owned());
+            },
+            Instruction::Jump(_) => stmts.push(self.jump(ir, inst)),
+            Instruction::JumpZero(_) | Instruction::JumpLtz(_) | Instruction::JumpGtz(_) => {
+                let cond = match inst {
+                    Instruction::JumpZero(_) => "==",
+                    Instruction::JumpLtz(_) => "<",
+                    _ => ">",
+                };
+                
+                stmts.push(format!("if (ram_load({ir}, 0) {cond} 0) {}", self.jump(ir, inst)));
+            },
+            Instruction::Stop => stmts.push("return ram_stop();".to_owned()),
+            Instruction::Nop => {},
+        }
+        
+        stmts
+    }
+}
+
+impl<T: CInteger> Display for C<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let len = self.code.len();
+        let limit = i64::try_from(self.options.memory_limit).unwrap_or(i64::MAX);
+        
+        #[cfg(feature = "indirect_jumps")]
+        let dispatch = self.code.iter().any(|inst| matches!(inst.jump(), Some(Address::Register(_))));
+        #[cfg(not(feature = "indirect_jumps"))]
+        let dispatch = false;
+        
+        // Unused labels are warned about
+        let labels = self.code.iter().filter_map(Instruction::target).filter(|adr| adr.inner() < len).collect::<BTreeSet<_>>();
+        
+        writeln!(f, "/* Generated by rame. */")?;
+        writeln!(f, "#include <ctype.h>")?;
+        writeln!(f, "#include <stdint.h>")?;
+        writeln!(f, "#include <stdio.h>")?;
+        writeln!(f, "#include <stdlib.h>")?;
+        writeln!(f, "#include <string.h>")?;
+        writeln!(f)?;
+        writeln!(f, "typedef {} ram_t;", T::C_TYPE)?;
+        writeln!(f, "#define RAM_MIN {}", constant(T::min_value()))?;
+        writeln!(f, "#define RAM_MAX {}", constant(T::max_value()))?;
+        writeln!(f, "#define RAM_MEMORY_LIMIT {limit}LL")?;
+        writeln!(f, "#define RAM_UNINIT_CHECKS {}", u8::from(self.options.uninit_checks))?;
+        writeln!(f)?;
+        
+        writeln!(f, "/* The instructions, for errors. */")?;
+        writeln!(f, "static const char *const ram_code[] = {{")?;
+        for inst in self.code.iter() {
+            writeln!(f, "    \"{inst}\",")?;
+        }
+        writeln!(f, "    NULL,")?;
+        writeln!(f, "}};")?;
+        writeln!(f)?;
+        
+        f.write_str(PRELUDE)?;
+        writeln!(f)?;
+        
+        writeln!(f, "int main(int argc, char **argv) {{")?;
+        if dispatch {
+            writeln!(f, "    ram_t ram_target;")?;
+            writeln!(f, "    size_t ram_jump_ir;")?;
+        }
+        writeln!(f, "    ram_start(argc, argv);")?;
+        
+        for (ir, inst) in self.code.enumerate() {
+            writeln!(f)?;
+            if dispatch || labels.contains(&ir) {
+                write!(f, "L{ir}:")?;
+            }
+            
+            writeln!(f, "    /* {inst} */")?;
+            let stmts = self.inst(ir, inst);
+            match stmts.as_slice() {
+                [] => writeln!(f, "    ;")?,
+                [stmt] => writeln!(f, "    {stmt}")?,
+                stmts => {
+                    writeln!(f, "    {{")?;
+                    for stmt in stmts {
+                        writeln!(f, "        {stmt}")?;
+                    }
+                    writeln!(f, "    }}")?;
+                },
+            }
+        }
+        
+        writeln!(f)?;
+        writeln!(f, "    ram_fail({}, \"unexpected end of file\");", len.saturating_sub(1))?;
+        
+        if dispatch {
+            writeln!(f)?;
+            writeln!(f, "ram_dispatch:")?;
+            writeln!(f, "    switch (ram_target) {{")?;
+            for ir in 0..len {
+                writeln!(f, "    case {ir}: goto L{ir};")?;
+            }
+            writeln!(f, "    default:")?;
+            writeln!(f, "        if (ram_target < 0) ram_fail(ram_jump_ir, \"jumping to an invalid location: out of range integral type conversion attempted\");")?;
+            writeln!(f, "        ram_fail(ram_jump_ir, \"jumping to an inexistent location\");")?;
+            writeln!(f, "    }}")?;
+        }
+        
+        writeln!(f, "}}")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::emit::test::{self, Backend};
+    use crate::emit::{CInteger, Options, C};
+    use crate::inst;
+    use crate::model::RoCode;
+    use crate::runner::Ram;
+    use std::fmt::Display;
+    use std::fs;
+    use std::process::{Command, Stdio};
+    
+    /// Compiles `code` with the system C compiler, and returns what the program prints for each input;
+    /// `None` if there's no C compiler, skipping the test.
+    fn run<T: CInteger>(c: &C<'_, T>, name: &str, inputs: &[&[T]]) -> Option<Vec<String>> {
+        let dir = std::env::temp_dir().join(format!("rame-emit-c-{}-{name}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let src = dir.join("a.c");
+        let exe = dir.join("a.out");
+        fs::write(&src, c.to_string()).unwrap();
+        
+        let Ok(status) = Command::new("cc").args(["-std=c11", "-O1", "-Wall", "-Werror", "-Wno-type-limits", "-o"]).arg(&exe).arg(&src).status() else {
+            eprintln!("skipping `{name}`: no C compiler");
+            fs::remove_dir_all(&dir).unwrap();
+            return None;
+        };
+        
+        assert!(status.success(), "{}", c);
+        
+        let outputs = inputs.iter().map(|input| {
+            let args = input.iter().map(ToString::to_string).collect::<Vec<_>>().join(",");
+            let out = Command::new(&exe).arg(args).stdin(Stdio::null()).output().unwrap();
+            let mut printed = String::from_utf8(out.stdout).unwrap();
+            printed.push_str(&String::from_utf8(out.stderr).unwrap());
+            printed
+        }).collect();
+        
+        fs::remove_dir_all(&dir).unwrap();
+        Some(outputs)
+    }
+    
+    /// Compiles with the system C compiler.
+    struct Cc;
+    
+    impl<T: CInteger> Backend<T> for Cc {
+        fn run(code: &RoCode<T>, options: Options, name: &str, inputs: &[&[T]]) -> Option<Vec<String>> {
+            run(&C::new(code).options(options), name, inputs)
+        }
+        
+        /// Only the start of errors is checked, as the failing instruction follows.
+        fn check<I: Iterator<Item = T>>(output: &str, mut ram: Ram<T, I>, context: impl Display) {
+            match ram.run_for(1_000_000) {
+                Ok(stopped) => {
+                    assert!(stopped, "infinite loop");
+                    assert_eq!(output, format!("Output = {:?}\n", ram.output()), "{context}");
+                },
+                Err(e) => assert!(output.contains(&format!("error: {e}\n")), "{context}: {output} instead of {e}"),
+            }
+        }
+    }
+    
+    #[test]
+    fn fibo() {
+        test::fibo::<Cc>();
+    }
+    
+    #[test]
+    fn errors() {
+        test::errors::<Cc>();
+        
+        let code = RoCode::<i8>::from([inst!(READ), inst!(ADD #100), inst!(WRITE), inst!(LOAD @1), inst!(STOP)]);
+        let Some(outputs) = run(&C::new(&code), "errors-i8", &[&[], &[50], &[-128]]) else {
+            return;
+        };
+        
+        assert_eq!(outputs, [
+            "E0 = <eof>\nerror: nothing left to read\n --> 0 | READ\n",
+            "error: integer overflow\n --> 1 | ADD #100\n",
+            "error: reading uninitialized memory R1\n --> 3 | LOAD @1\n",
+        ]);
+        
+        let wrapping = run(&C::new(&code).options(Options::new().overflow_checks(false).uninit_checks(false)), "wrapping", &[&[50]]);
+        assert_eq!(wrapping.unwrap(), ["Output = [-106]\n"]);
+    }
+    
+    #[test]
+    fn fuzz() {
+        test::fuzz::<i16, Cc>(20);
+    }
+    
+    #[test]
+    fn constants() {
+        let code = RoCode::<i128>::from([inst!(LOAD #0)]).map(|_| i128::MIN + 1);
+        let c = C::new(&code).to_string();
+        assert!(c.contains("typedef __int128 ram_t;"), "{c}");
+        assert!(c.contains("ram_store(0, (ram_t)((unsigned __int128)UINT64_C(9223372036854775808) << 64 | UINT64_C(1)));"), "{c}");
+        
+        let code = RoCode::<u8>::from([inst!(LOAD #200), inst!(DIV #3)]);
+        let c = C::new(&code).to_string();
+        assert!(c.contains("typedef uint8_t ram_t;") && c.contains("if (v == 0) ram_fail(1, \"integer overflow\");"), "{c}");
+    }
+}
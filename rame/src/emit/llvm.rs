@@ -0,0 +1,590 @@
+use crate::emit::{status, Options};
+use crate::model::{Instruction, Integer, Ir, Loc, Register, RoCode, Value};
+use std::fmt::{self, Display, Formatter, Write as _};
+
+#[cfg(feature = "indirect_jumps")]
+use crate::model::Address;
+
+/// Displays some code as an LLVM IR module, see [`Llvm::new`].
+///
+/// The module defines `i32 @rame_run()`, which runs the code and returns `0` once stopped,
+/// or the [status](crate::emit::error_kind) of the error, storing the failing instruction into `@rame_ir`,
+/// the register into `@rame_adr` and the invalid address into `@rame_value`.
+/// `READ` calls `i1 @rame_read(ptr)`, returning `false` if there's nothing left to read,
+/// and `WRITE` calls `void @rame_write(iN)`; both are defined by the host, e.g. in C:
+/// ```c
+/// bool rame_read(int16_t *v);
+/// void rame_write(int16_t v);
+/// ```
+///
+/// The registers are a global array, and the overflow checks use the `*.with.overflow` intrinsics.
+/// Pointers are opaque, as required since LLVM 17; LLVM 14 needs `-opaque-pointers`.
+///
+/// # Example
+///
+/// ```
+/// # use rame::emit::Llvm;
+/// # use rame::inst;
+/// # use rame::model::RoCode;
+/// let code = RoCode::<i32>::from([
+///     inst!(READ),
+///     inst!(ADD #1),
+///     inst!(WRITE),
+///     inst!(STOP),
+/// ]);
+///
+/// let ll = Llvm::new(&code).to_string();
+/// assert!(ll.contains("define i32 @rame_run()"));
+/// assert!(ll.contains("@llvm.sadd.with.overflow.i32(i32 %t"));
+/// ```
+#[derive(Copy, Clone, Debug)]
+#[must_use]
+pub struct Llvm<'c, T: Integer> {
+    code: &'c RoCode<T>,
+    options: Options,
+}
+
+impl<'c, T: Integer> Llvm<'c, T> {
+    pub const fn new(code: &'c RoCode<T>) -> Llvm<'c, T> {
+        Llvm {
+            code,
+            options: Options::new(),
+        }
+    }
+    
+    /// Sets which checks of [`Ram`](crate::runner::Ram) are kept.
+    pub const fn options(mut self, options: Options) -> Llvm<'c, T> {
+        self.options = options;
+        self
+    }
+}
+
+/// The body of `@rame_run` being written.
+struct Func<'l, 'c, T: Integer> {
+    llvm: &'l Llvm<'c, T>,
+    out: String,
+    
+    /// The type of `T`, e.g. `i16`.
+    ty: String,
+    signed: bool,
+    
+    /// The count of temporaries.
+    tmps: usize,
+    /// The count of blocks of the current instruction.
+    blocks: usize,
+    
+    /// The blocks returning an error, written after the current instruction.
+    fails: Vec<String>,
+}
+
+/// An index into the registers, either a constant or a temporary.
+type Index = String;
+
+impl<T: Integer> Func<'_, '_, T> {
+    fn tmp(&mut self) -> String {
+        self.tmps += 1;
+        format!("%t{}", self.tmps)
+    }
+    
+    /// Starts a new block of the instruction `ir`, and returns its label.
+    fn block(&mut self, ir: Ir) -> String {
+        self.blocks += 1;
+        let label = format!("L{ir}.{}", self.blocks);
+        writeln!(self.out, "{label}:").unwrap();
+        label
+    }
+    
+    /// Returns the label of a block returning `status`, storing `adr` into `@rame_adr` and `value` into `@rame_value`.
+    fn fail(&mut self, ir: Ir, status: u32, adr: Option<&str>, value: Option<&str>) -> String {
+        self.blocks += 1;
+        let label = format!("L{ir}.{}", self.blocks);
+        
+        let mut block = format!("{label}:\n  store i64 {ir}, ptr @rame_ir\n");
+        if let Some(adr) = adr {
+            writeln!(block, "  store i64 {adr}, ptr @rame_adr").unwrap();
+        }
+        if let Some(value) = value {
+            writeln!(block, "  store {} {value}, ptr @rame_value", self.ty).unwrap();
+        }
+        writeln!(block, "  ret i32 {status}").unwrap();
+        
+        self.fails.push(block);
+        format!("%{label}")
+    }
+    
+    /// Branches to `fail` if `cond`, otherwise to a new block.
+    fn check(&mut self, ir: Ir, cond: &str, fail: &str) {
+        let next = format!("%L{ir}.{}", self.blocks + 1);
+        writeln!(self.out, "  br i1 {cond}, label {fail}, label {next}").unwrap();
+        self.block(ir);
+    }
+    
+    /// Returns a temporary pointing to a register, or its initialized flag.
+    fn gep(&mut self, init: bool, idx: &str) -> String {
+        let p = self.tmp();
+        let (array, ty) = if init { ("@rame_init", "i8") } else { ("@rame_memory", self.ty.as_str()) };
+        writeln!(self.out, "  {p} = getelementptr inbounds [{} x {ty}], ptr {array}, i64 0, i64 {idx}", self.llvm.options.memory_limit).unwrap();
+        p
+    }
+    
+    /// Reads a register.
+    fn load(&mut self, ir: Ir, idx: &str) -> String {
+        if self.llvm.options.uninit_checks {
+            let p = self.gep(true, idx);
+            let flag = self.tmp();
+            let uninit = self.tmp();
+            writeln!(self.out, "  {flag} = load i8, ptr {p}").unwrap();
+            writeln!(self.out, "  {uninit} = icmp eq i8 {flag}, 0").unwrap();
+            let fail = self.fail(ir, status::READ_UNINIT, Some(idx), None);
+            self.check(ir, &uninit, &fail);
+        }
+        
+        let p = self.gep(false, idx);
+        let v = self.tmp();
+        writeln!(self.out, "  {v} = load {}, ptr {p}", self.ty).unwrap();
+        v
+    }
+    
+    /// Writes a register.
+    fn store(&mut self, idx: &str, v: &str) {
+        let p = self.gep(false, idx);
+        writeln!(self.out, "  store {} {v}, ptr {p}", self.ty).unwrap();
+        
+        if self.llvm.options.uninit_checks {
+            let p = self.gep(true, idx);
+            writeln!(self.out, "  store i8 1, ptr {p}").unwrap();
+        }
+    }
+    
+    /// Converts a register value into an index, failing with `invalid` if negative or past `usize`.
+    fn index(&mut self, ir: Ir, v: &str, invalid: u32) -> Index {
+        let bits = size_of::<T>() * 8;
+        
+        if self.signed {
+            let neg = self.tmp();
+            writeln!(self.out, "  {neg} = icmp slt {} {v}, 0", self.ty).unwrap();
+            let fail = self.fail(ir, invalid, None, Some(v));
+            self.check(ir, &neg, &fail);
+        }
+        
+        if bits > 64 {
+            let big = self.tmp();
+            writeln!(self.out, "  {big} = icmp ugt {} {v}, {}", self.ty, u64::MAX).unwrap();
+            let fail = self.fail(ir, invalid, None, Some(v));
+            self.check(ir, &big, &fail);
+        }
+        
+        let idx = self.tmp();
+        match bits {
+            64 => return v.to_owned(),
+            ..64 => writeln!(self.out, "  {idx} = zext {} {v} to i64", self.ty).unwrap(),
+            _ => writeln!(self.out, "  {idx} = trunc {} {v} to i64", self.ty).unwrap(),
+        }
+        idx
+    }
+    
+    /// Returns the index of a register, `None` if accessing it fails.
+    fn address<L: Loc>(&mut self, ir: Ir, reg: Register<L>) -> Option<Index> {
+        let limit = self.llvm.options.memory_limit;
+        
+        let direct = |this: &mut Self, adr: usize, read: bool| if adr < limit {
+            Some(adr.to_string())
+        }
+        else {
+            let fail = this.fail(ir, this.llvm.options.past_limit(read), Some(&adr.to_string()), None);
+            writeln!(this.out, "  br label {fail}").unwrap();
+            None
+        };
+        
+        match reg {
+            Register::Direct(adr) => direct(self, adr.raw(), L::READ),
+            Register::Indirect(ptr) => {
+                let ptr = direct(self, ptr.raw(), true)?;
+                let v = self.load(ir, &ptr);
+                let idx = self.index(ir, &v, status::INVALID_ADDRESS);
+                
+                let over = self.tmp();
+                writeln!(self.out, "  {over} = icmp uge i64 {idx}, {limit}").unwrap();
+                let fail = self.fail(ir, self.llvm.options.past_limit(L::READ), Some(&idx), None);
+                self.check(ir, &over, &fail);
+                Some(idx)
+            },
+        }
+    }
+    
+    /// Returns a value, `None` if reading it fails.
+    fn value(&mut self, ir: Ir, v: Value<T>) -> Option<String> {
+        match v {
+            Value::Constant(v) => Some(v.to_string()),
+            Value::Register(reg) => {
+                let idx = self.address(ir, reg)?;
+                Some(self.load(ir, &idx))
+            },
+        }
+    }
+    
+    /// Returns `lhs op rhs`, for `add`, `sub` and `mul`.
+    fn arith(&mut self, ir: Ir, op: &str, lhs: &str, rhs: &str) -> String {
+        let ty = self.ty.clone();
+        let r = self.tmp();
+        
+        if self.llvm.options.overflow_checks {
+            let sign = if self.signed { 's' } else { 'u' };
+            let pair = self.tmp();
+            let overflow = self.tmp();
+            writeln!(self.out, "  {pair} = call {{ {ty}, i1 }} @llvm.{sign}{op}.with.overflow.{ty}({ty} {lhs}, {ty} {rhs})").unwrap();
+            writeln!(self.out, "  {r} = extractvalue {{ {ty}, i1 }} {pair}, 0").unwrap();
+            writeln!(self.out, "  {overflow} = extractvalue {{ {ty}, i1 }} {pair}, 1").unwrap();
+            let fail = self.fail(ir, status::INTEGER_OVERFLOW, None, None);
+            self.check(ir, &overflow, &fail);
+        }
+        else {
+            writeln!(self.out, "  {r} = {op} {ty} {lhs}, {rhs}").unwrap();
+        }
+        
+        r
+    }
+    
+    /// Branches to a constant address if `cond`, or unconditionally.
+    fn jump(&mut self, ir: Ir, cond: Option<&str>, inst: Instruction<T>) {
+        #[cfg(feature = "indirect_jumps")]
+        if let Some(Address::Register(ptr)) = inst.jump() {
+            return self.jump_indirect(ir, cond, ptr.raw());
+        }
+        
+        let target = inst.target().filter(|adr| adr.inner() < self.llvm.code.len())
+            .map_or_else(|| self.fail(ir, status::INEXISTENT_JUMP, None, None), |adr| format!("%L{adr}"));
+        
+        match cond {
+            Some(cond) => self.check(ir, cond, &target),
+            None => writeln!(self.out, "  br label {target}").unwrap(),
+        }
+    }
+    
+    /// Branches to the address in a register if `cond`, or unconditionally.
+    #[cfg(feature = "indirect_jumps")]
+    fn jump_indirect(&mut self, ir: Ir, cond: Option<&str>, ptr: usize) {
+        if let Some(cond) = cond {
+            let skip = format!("%L{ir}.skip");
+            let next = format!("%L{ir}.{}", self.blocks + 1);
+            writeln!(self.out, "  br i1 {cond}, label {next}, label {skip}").unwrap();
+            self.block(ir);
+        }
+        
+        if ptr >= self.llvm.options.memory_limit {
+            let fail = self.fail(ir, self.llvm.options.past_limit(true), Some(&ptr.to_string()), None);
+            writeln!(self.out, "  br label {fail}").unwrap();
+        }
+        else {
+            let v = self.load(ir, &ptr.to_string());
+            let idx = self.index(ir, &v, status::INVALID_JUMP);
+            let inexistent = self.fail(ir, status::INEXISTENT_JUMP, None, None);
+            
+            write!(self.out, "  switch i64 {idx}, label {inexistent} [").unwrap();
+            for target in 0..self.llvm.code.len() {
+                write!(self.out, " i64 {target}, label %L{target}").unwrap();
+            }
+            writeln!(self.out, " ]").unwrap();
+        }
+        
+        if cond.is_some() {
+            writeln!(self.out, "L{ir}.skip:").unwrap();
+        }
+    }
+    
+    /// Writes an instruction, and returns whether it may fall through.
+    fn inst(&mut self, ir: Ir, inst: Instruction<T>) -> bool {
+        let ty = self.ty.clone();
+        
+        macro_rules! tri {
+            ($e:expr) => {
+                match $e {
+                    Some(e) => e,
+                    None => return false,
+                }
+            };
+        }
+        
+        match inst {
+            Instruction::Read => {
+                let ok = self.tmp();
+                writeln!(self.out, "  {ok} = call zeroext i1 @rame_read(ptr %read)").unwrap();
+                let eof = self.tmp();
+                writeln!(self.out, "  {eof} = xor i1 {ok}, true").unwrap();
+                let fail = self.fail(ir, status::READ_EOF, None, None);
+                self.check(ir, &eof, &fail);
+                
+                let v = self.tmp();
+                writeln!(self.out, "  {v} = load {ty}, ptr %read").unwrap();
+                self.store("0", &v);
+            },
+            Instruction::Write => {
+                let acc = self.load(ir, "0");
+                writeln!(self.out, "  call void @rame_write({ty} {acc})").unwrap();
+            },
+            Instruction::Load(v) => {
+                let v = tri!(self.value(ir, v));
+                self.store("0", &v);
+            },
+            Instruction::Store(reg) => {
+                let acc = self.load(ir, "0");
+                let idx = tri!(self.address(ir, reg));
+                self.store(&idx, &acc);
+            },
+            Instruction::Increment(reg) | Instruction::Decrement(reg) => {
+                let idx = tri!(self.address(ir, reg));
+                let v = self.load(ir, &idx);
+                let r = self.arith(ir, if matches!(inst, Instruction::Increment(_)) { "add" } else { "sub" }, &v, "1");
+                self.store(&idx, &r);
+            },
+            Instruction::Add(v) | Instruction::Sub(v) | Instruction::Mul(v) => {
+                let acc = self.load(ir, "0");
+                let v = tri!(self.value(ir, v));
+                let op = match inst {
+                    Instruction::Add(_) => "add",
+                    Instruction::Sub(_) => "sub",
+                    _ => "mul",
+                };
+                
+                let r = self.arith(ir, op, &acc, &v);
+                self.store("0", &r);
+            },
+            Instruction::Div(v) | Instruction::Mod(v) => {
+                let acc = self.load(ir, "0");
+                let v = tri!(self.value(ir, v));
+                
+                // Undefined behavior in LLVM
+                let overflow = self.fail(ir, status::INTEGER_OVERFLOW, None, None);
+                let zero = self.tmp();
+                writeln!(self.out, "  {zero} = icmp eq {ty} {v}, 0").unwrap();
+                self.check(ir, &zero, &overflow);
+                
+                if self.signed {
+                    let (min, minus_one, both) = (self.tmp(), self.tmp(), self.tmp());
+                    writeln!(self.out, "  {min} = icmp eq {ty} {acc}, {}", T::min_value()).unwrap();
+                    writeln!(self.out, "  {minus_one} = icmp eq {ty} {v}, -1").unwrap();
+                    writeln!(self.out, "  {both} = and i1 {min}, {minus_one}").unwrap();
+                    self.check(ir, &both, &overflow);
+                }
+                
+                let op = match (matches!(inst, Instruction::Div(_)), self.signed) {
+                    (true, true) => "sdiv",
+                    (true, false) => "udiv",
+                    (false, true) => "srem",
+                    (false, false) => "urem",
+                };
+                
+                let r = self.tmp();
+                writeln!(self.out, "  {r} = {op} {ty} {acc}, {v}").unwrap();
+                self.store("0", &r);
+            },
+            Instruction::Jump(_) => {
+                self.jump(ir, None, inst);
+                return false;
+            },
+            Instruction::JumpZero(_) | Instruction::JumpLtz(_) | Instruction::JumpGtz(_) => {
+                let acc = self.load(ir, "0");
+                let pred = match (inst, self.signed) {
+                    (Instruction::JumpZero(_), _) => "eq",
+                    (Instruction::JumpLtz(_), true) => "slt",
+                    (Instruction::JumpLtz(_), false) => "ult",
+                    (_, true) => "sgt",
+                    (_, false) => "ugt",
+                };
+                
+                let cond = self.tmp();
+                writeln!(self.out, "  {cond} = icmp {pred} {ty} {acc}, 0").unwrap();
+                self.jump(ir, Some(&cond), inst);
+            },
+            Instruction::Stop => {
+                writeln!(self.out, "  ret i32 {}", status::STOPPED).unwrap();
+                return false;
+            },
+            Instruction::Nop => {},
+        }
+        
+        true
+    }
+}
+
+impl<T: Integer> Display for Llvm<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let ty = format!("i{}", size_of::<T>() * 8);
+        let limit = self.options.memory_limit;
+        
+        writeln!(f, "; Generated by rame.")?;
+        writeln!(f)?;
+        writeln!(f, "@rame_memory = internal global [{limit} x {ty}] zeroinitializer")?;
+        if self.options.uninit_checks {
+            writeln!(f, "@rame_init = internal global [{limit} x i8] zeroinitializer")?;
+        }
+        writeln!(f)?;
+        writeln!(f, "@rame_ir = global i64 0")?;
+        writeln!(f, "@rame_adr = global i64 0")?;
+        writeln!(f, "@rame_value = global {ty} 0")?;
+        writeln!(f)?;
+        writeln!(f, "declare zeroext i1 @rame_read(ptr)")?;
+        writeln!(f, "declare void @rame_write({ty})")?;
+        for op in ["add", "sub", "mul"] {
+            for sign in ['s', 'u'] {
+                writeln!(f, "declare {{ {ty}, i1 }} @llvm.{sign}{op}.with.overflow.{ty}({ty}, {ty})")?;
+            }
+        }
+        writeln!(f)?;
+        
+        let mut func = Func {
+            llvm: self,
+            out: String::new(),
+            ty: ty.clone(),
+            signed: T::min_value() < T::zero(),
+            tmps: 0,
+            blocks: 0,
+            fails: Vec::new(),
+        };
+        
+        writeln!(f, "define i32 @rame_run() {{")?;
+        writeln!(f, "entry:")?;
+        writeln!(f, "  %read = alloca {ty}")?;
+        if self.code.is_empty() {
+            writeln!(f, "  store i64 0, ptr @rame_ir")?;
+            writeln!(f, "  ret i32 {}", status::EOF)?;
+        }
+        else {
+            writeln!(f, "  br label %L0")?;
+        }
+        
+        for (ir, inst) in self.code.enumerate() {
+            func.blocks = 0;
+            writeln!(func.out)?;
+            writeln!(func.out, "L{ir}: ; {inst}")?;
+            
+            if func.inst(ir, inst) {
+                if ir.inner() + 1 < self.code.len() {
+                    writeln!(func.out, "  br label %L{}", ir + 1)?;
+                }
+                else {
+                    let eof = func.fail(ir, status::EOF, None, None);
+                    writeln!(func.out, "  br label {eof}")?;
+                }
+            }
+            
+            for block in func.fails.drain(..) {
+                func.out.push_str(&block);
+            }
+        }
+        
+        f.write_str(&func.out)?;
+        writeln!(f, "}}")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::emit::test::{self, Backend};
+    use crate::emit::{Llvm, Options};
+    use crate::inst;
+    use crate::model::{Integer, RoCode};
+    use std::fs;
+    use std::process::Command;
+    
+    /// Defines `rame_read` and `rame_write` over the arguments and stdout, for `-DT=<type>`.
+    const HOST: &str = r#"
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+
+extern int rame_run(void);
+extern int64_t rame_adr;
+extern T rame_value;
+
+static char **args;
+
+bool rame_read(T *v) {
+    if (!*args) return false;
+    *v = (T)strtoll(*args++, NULL, 10);
+    return true;
+}
+
+void rame_write(T v) {
+    printf("%lld ", (long long)v);
+}
+
+int main(int argc, char **argv) {
+    (void)argc;
+    args = argv + 1;
+    int status = rame_run();
+    printf("| %d", status);
+    if (status == 2 || status == 4) printf(" R%lld", (long long)rame_adr);
+    if (status == 3) printf(" %lld", (long long)rame_value);
+    printf("\n");
+}
+"#;
+
+    /// Compiles with `llc` and the system C compiler, then prints like [`test::expected`] through [`HOST`].
+    struct Llc;
+    
+    impl<T: Integer> Backend<T> for Llc {
+        fn run(code: &RoCode<T>, options: Options, name: &str, inputs: &[&[T]]) -> Option<Vec<String>> {
+            run(&Llvm::new(code).options(options), name, inputs)
+        }
+    }
+    
+    /// Compiles `ll` with `llc` and the system C compiler, and returns what [`HOST`] prints for each input;
+    /// `None` if there's no `llc` or C compiler.
+    fn run<T: Integer>(ll: &Llvm<'_, T>, name: &str, inputs: &[&[T]]) -> Option<Vec<String>> {
+        let dir = std::env::temp_dir().join(format!("rame-emit-llvm-{}-{name}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.ll"), ll.to_string()).unwrap();
+        fs::write(dir.join("host.c"), HOST).unwrap();
+        
+        // Opaque pointers are the default since LLVM 15
+        let version = Command::new("llc").arg("--version").output().ok()?;
+        let mut llc = Command::new("llc");
+        if String::from_utf8_lossy(&version.stdout).contains("LLVM version 14.") {
+            llc.arg("-opaque-pointers");
+        }
+        
+        let status = llc.args(["-O1", "-filetype=obj", "-relocation-model=pic", "-o"]).arg(dir.join("a.o")).arg(dir.join("a.ll")).status().unwrap();
+        assert!(status.success(), "{ll}");
+        
+        let status = Command::new("cc")
+            .arg(format!("-DT=int{}_t", size_of::<T>() * 8))
+            .arg("-o").arg(dir.join("a.out"))
+            .arg(dir.join("host.c")).arg(dir.join("a.o"))
+            .status().ok()?;
+        assert!(status.success());
+        
+        let outputs = inputs.iter().map(|input| {
+            let out = Command::new(dir.join("a.out")).args(input.iter().map(ToString::to_string)).output().unwrap();
+            String::from_utf8(out.stdout).unwrap()
+        }).collect();
+        
+        fs::remove_dir_all(&dir).unwrap();
+        Some(outputs)
+    }
+    
+    #[test]
+    fn fibo() {
+        test::fibo::<Llc>();
+    }
+    
+    #[test]
+    fn errors() {
+        test::errors::<Llc>();
+    }
+    
+    #[test]
+    fn fuzz() {
+        test::fuzz::<i16, Llc>(20);
+    }
+    
+    #[test]
+    fn unsigned() {
+        let code = RoCode::<u8>::from([inst!(LOAD #200), inst!(DIV #3), inst!(JUML 0), inst!(STOP)]);
+        let ll = Llvm::new(&code).options(Options::new().overflow_checks(false)).to_string();
+        assert!(ll.contains("udiv i8 %t"), "{ll}");
+        assert!(ll.contains("icmp ult i8 %t"), "{ll}");
+        assert!(!ll.contains("call { i8, i1 }"), "{ll}");
+    }
+}
@@ -0,0 +1,197 @@
+//! Translates code into other languages, see [`C`], [`Llvm`] and [`Wat`].
+
+use crate::runner::DEFAULT_MEMORY_LIMIT;
+
+mod c;
+mod llvm;
+mod wat;
+
+pub use c::{CInteger, C};
+pub use llvm::Llvm;
+pub use wat::{MemoryLimitError, WasmInteger, Wat};
+
+/// What the `run` functions of [`Llvm`] and [`Wat`] return.
+mod status {
+    pub(super) const STOPPED: u32 = 0;
+    pub(super) const READ_EOF: u32 = 1;
+    pub(super) const READ_UNINIT: u32 = 2;
+    pub(super) const INVALID_ADDRESS: u32 = 3;
+    pub(super) const MEMORY_LIMIT: u32 = 4;
+    pub(super) const INTEGER_OVERFLOW: u32 = 5;
+    pub(super) const INEXISTENT_JUMP: u32 = 6;
+    pub(super) const INVALID_JUMP: u32 = 7;
+    pub(super) const EOF: u32 = 8;
+}
+
+/// Returns the [kind](crate::runner::RunError::kind) of the error returned by the `run` functions
+/// of [`Llvm`] and [`Wat`], `None` if stopped or unknown.
+#[must_use]
+pub const fn error_kind(status: u32) -> Option<&'static str> {
+    match status {
+        status::READ_EOF => Some("ReadEof"),
+        status::READ_UNINIT => Some("ReadUninit"),
+        status::INVALID_ADDRESS => Some("InvalidAddress"),
+        status::MEMORY_LIMIT => Some("MemoryLimit"),
+        status::INTEGER_OVERFLOW => Some("IntegerOverfow"),
+        status::INEXISTENT_JUMP => Some("InexistentJump"),
+        status::INVALID_JUMP => Some("InvalidJump"),
+        status::EOF => Some("Eof"),
+        _ => None,
+    }
+}
+
+/// Which checks of [`Ram`](crate::runner::Ram) are kept by [`C`], [`Llvm`] and [`Wat`], all of them by default.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+#[must_use]
+pub struct Options {
+    /// Fail on overflows instead of wrapping around.
+    overflow_checks: bool,
+    
+    /// Fail on reading uninitialized registers instead of reading `0`.
+    uninit_checks: bool,
+    
+    /// The count of registers.
+    memory_limit: usize,
+}
+
+impl Options {
+    pub const fn new() -> Options {
+        Options {
+            overflow_checks: true,
+            uninit_checks: true,
+            memory_limit: DEFAULT_MEMORY_LIMIT,
+        }
+    }
+    
+    /// Fails with `integer overflow` like [`Ram`](crate::runner::Ram), or wraps around if disabled.
+    ///
+    /// Dividing by zero, or the smallest integer by `-1`, always fails.
+    pub const fn overflow_checks(mut self, overflow_checks: bool) -> Options {
+        self.overflow_checks = overflow_checks;
+        self
+    }
+    
+    /// Fails on reading uninitialized registers like [`Ram`](crate::runner::Ram), or reads `0` if disabled.
+    ///
+    /// Reading a register past the memory limit fails like reading an uninitialized one,
+    /// or with a memory limit error if disabled.
+    pub const fn uninit_checks(mut self, uninit_checks: bool) -> Options {
+        self.uninit_checks = uninit_checks;
+        self
+    }
+    
+    /// Sets the count of registers, see [`Ram::with_memory_limit`](crate::runner::Ram::with_memory_limit).
+    pub const fn memory_limit(mut self, memory_limit: usize) -> Options {
+        self.memory_limit = memory_limit;
+        self
+    }
+    
+    /// Returns the status of reading (if `read`) or writing a register past the memory limit, see [`Options::uninit_checks`].
+    const fn past_limit(self, read: bool) -> u32 {
+        if read && self.uninit_checks { status::READ_UNINIT } else { status::MEMORY_LIMIT }
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::emit::{self, Options};
+    use crate::fuzz::{Generator, Rng};
+    use crate::inst;
+    use crate::model::{Integer, RoCode};
+    use crate::runner::{Ram, RunError};
+    use std::fmt::Display;
+    use std::str::FromStr;
+    
+    /// Runs the code translated by an emitter, for the tests shared by [`C`](emit::C), [`Llvm`](emit::Llvm) and [`Wat`](emit::Wat).
+    pub(super) trait Backend<T: Integer> {
+        /// Translates `code` with `options`, then returns what it prints for each input;
+        /// `None` if a tool is missing, skipping the test.
+        fn run(code: &RoCode<T>, options: Options, name: &str, inputs: &[&[T]]) -> Option<Vec<String>>;
+        
+        /// Asserts that `output` is what `ram` prints once run, see [`expected`].
+        fn check<I: Iterator<Item = T>>(output: &str, ram: Ram<T, I>, context: impl Display) {
+            assert_eq!(output, expected(ram), "{context}");
+        }
+    }
+    
+    /// Returns what's written by `ram` once run, then its status, with the address of a failing register
+    /// or the value of an invalid one, e.g. `3 | 2 R1\n`.
+    pub(super) fn expected<T: Integer, I: Iterator<Item = T>>(mut ram: Ram<T, I>) -> String {
+        let result = ram.run_for(1_000_000);
+        let output = ram.output().iter().map(|v| format!("{v} ")).collect::<String>();
+        
+        match result {
+            Ok(stopped) => {
+                assert!(stopped, "infinite loop");
+                format!("{output}| 0\n")
+            },
+            Err(e) => {
+                let status = (1..=8).find(|&status| emit::error_kind(status) == Some(e.kind())).unwrap();
+                match e {
+                    RunError::ReadUninit { adr } => format!("{output}| {status} R{adr}\n"),
+                    RunError::MemoryLimit { adr, .. } => format!("{output}| {status} R{adr}\n"),
+                    RunError::InvalidAddress { adr, .. } => format!("{output}| {status} {adr}\n"),
+                    _ => format!("{output}| {status}\n"),
+                }
+            },
+        }
+    }
+    
+    pub(super) fn fibo<B: Backend<i32>>() {
+        let code = RoCode::<i32>::from_str(include_str!("../../benches/fibo.ram")).unwrap();
+        let inputs = (0..15).map(|n| [n]).collect::<Vec<_>>();
+        let Some(outputs) = B::run(&code, Options::new(), "fibo", &inputs.iter().map(|input| &input[..]).collect::<Vec<_>>()) else {
+            return;
+        };
+        
+        for (input, output) in inputs.iter().zip(outputs) {
+            B::check(&output, Ram::new(code.clone(), *input), format_args!("fibo({input:?})"));
+        }
+    }
+    
+    pub(super) fn errors<B: Backend<i16>>() {
+        let codes = [
+            RoCode::<i16>::from([inst!(READ), inst!(READ)]),
+            RoCode::from([inst!(LOAD #1), inst!(STORE 2), inst!(LOAD @1)]),
+            RoCode::from([inst!(LOAD #-3), inst!(STORE 1), inst!(LOAD @1)]),
+            RoCode::from([inst!(LOAD #5000), inst!(STORE 1), inst!(STORE @1)]),
+            RoCode::from([inst!(LOAD #7), inst!(STORE 1000)]),
+            RoCode::from([inst!(LOAD 1000)]),
+            RoCode::from([inst!(LOAD #5000), inst!(STORE 1), inst!(LOAD @1)]),
+            RoCode::from([inst!(LOAD #7), inst!(STORE 1), inst!(INC 1000)]),
+            RoCode::from([inst!(LOAD #32767), inst!(STORE 1), inst!(INC 1)]),
+            RoCode::from([inst!(LOAD #-32768), inst!(DIV #-1)]),
+            RoCode::from([inst!(LOAD #7), inst!(MOD #0)]),
+            RoCode::from([inst!(LOAD #1), inst!(JUMG 9)]),
+            RoCode::from([inst!(LOAD #-7), inst!(DIV #2), inst!(WRITE), inst!(LOAD #-7), inst!(MOD #2), inst!(WRITE)]),
+        ];
+        
+        for code in codes {
+            let Some(outputs) = B::run(&code, Options::new().memory_limit(1000), "errors", &[&[4]]) else {
+                return;
+            };
+            
+            B::check(&outputs[0], Ram::new(code.clone(), [4]).with_memory_limit(1000), &code);
+        }
+    }
+    
+    /// Checks the code generated from `seeds` against [`Ram`].
+    pub(super) fn fuzz<T: Integer, B: Backend<T>>(seeds: u64) {
+        for seed in 0..seeds {
+            let mut rng = Rng::new(seed);
+            let code = Generator::default().generate::<T>(&mut rng);
+            let input = (0..4).map(|_| rng.small(100)).collect::<Vec<T>>();
+            let Some(outputs) = B::run(&code, Options::new().memory_limit(1000), &format!("fuzz-{seed}"), &[&input]) else {
+                return;
+            };
+            
+            B::check(&outputs[0], Ram::new(code.clone(), input).with_memory_limit(1000), format_args!("seed {seed}:\n{code}"));
+        }
+    }
+}
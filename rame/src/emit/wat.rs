@@ -0,0 +1,863 @@
+use crate::emit::{status, Options};
+use crate::model::{Instruction, Integer, Ir, Loc, Register, RoCode, Value};
+use sealed::sealed;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter, Write as _};
+
+#[cfg(feature = "indirect_jumps")]
+use crate::model::Address;
+
+/// Displays some code as a WebAssembly text module, see [`Wat::new`].
+///
+/// The module exports `run`, which runs the code and returns `0` once stopped,
+/// or the [status](crate::emit::error_kind) of the error, storing the failing instruction into the `ir` global,
+/// the register into `adr` and the invalid address into `value`.
+/// `READ` calls the `rame.read` import with the address of `ACC` in the exported `memory`,
+/// where it writes the read integer in little-endian and returns `1`, or returns `0` if there's nothing left to read;
+/// `WRITE` calls `rame.write`, with an `i32`, or an `i64` for 64-bit integers. E.g. in JavaScript, with `-b 16`:
+/// ```js
+/// const input = [10], output = [];
+/// const { instance } = await WebAssembly.instantiate(wasm, { rame: {
+///     read(ptr) {
+///         if (!input.length) return 0;
+///         new DataView(instance.exports.memory.buffer).setInt16(ptr, input.shift(), true);
+///         return 1;
+///     },
+///     write(v) { output.push(v); },
+/// }});
+/// const status = instance.exports.run();
+/// ```
+///
+/// The registers are stored in `memory`, followed by whether they were initialized,
+/// and the arithmetic is done on `i64`s. The jumps go back through a `br_table` within a `loop`,
+/// except the forward ones, which break out of the blocks of the instructions in-between.
+///
+/// # Example
+///
+/// ```
+/// # use rame::emit::Wat;
+/// # use rame::inst;
+/// # use rame::model::RoCode;
+/// let code = RoCode::<i16>::from([
+///     inst!(READ),
+///     inst!(JUMZ 0),
+///     inst!(WRITE),
+///     inst!(STOP),
+/// ]);
+///
+/// let wat = Wat::new(&code).to_string();
+/// assert!(wat.contains("br_table $L0 $L1 $L2 $L3 $end"));
+/// assert!(wat.contains("(import \"rame\" \"write\" (func $write (param i32)))"));
+/// ```
+#[derive(Copy, Clone, Debug)]
+#[must_use]
+pub struct Wat<'c, T: WasmInteger> {
+    code: &'c RoCode<T>,
+    options: Options,
+}
+
+impl<'c, T: WasmInteger> Wat<'c, T> {
+    pub const fn new(code: &'c RoCode<T>) -> Wat<'c, T> {
+        Wat {
+            code,
+            options: Options::new(),
+        }
+    }
+    
+    /// Sets which checks of [`Ram`](crate::runner::Ram) are kept.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`MemoryLimitError`] if the registers don't fit in the 4 GiB of a memory.
+    pub const fn options(mut self, options: Options) -> Result<Wat<'c, T>, MemoryLimitError> {
+        let max = u32::MAX as usize / (size_of::<T>() + 1);
+        if options.memory_limit > max {
+            return Err(MemoryLimitError { max });
+        }
+        
+        self.options = options;
+        Ok(self)
+    }
+}
+
+/// An integer type that fits in a WebAssembly `i64`, see [`Wat`].
+#[sealed]
+pub trait WasmInteger: Integer {}
+
+macro_rules! wasm_integers {
+    ($($t:ty,)*) => {$(
+        #[sealed]
+        impl WasmInteger for $t {}
+    )*};
+}
+
+wasm_integers! {
+    i8,
+    i16,
+    i32,
+    i64,
+    u8,
+    u16,
+    u32,
+    u64,
+}
+
+/// The error type returned by [`Wat::options`].
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct MemoryLimitError {
+    max: usize,
+}
+
+impl MemoryLimitError {
+    /// Returns the highest memory limit whose registers fit in a memory.
+    #[must_use]
+    pub const fn max(&self) -> usize {
+        self.max
+    }
+}
+
+impl Display for MemoryLimitError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "the registers don't fit in the 4 GiB of a WebAssembly memory, the limit is at most {}", self.max)
+    }
+}
+
+impl Error for MemoryLimitError {}
+
+/// A register whose address is known, either directly or from `$p`.
+#[derive(Copy, Clone, Debug)]
+enum Reg {
+    Direct(usize),
+    Indirect,
+}
+
+/// The body of `run` being written.
+struct Func<'w, 'c, T: WasmInteger> {
+    wat: &'w Wat<'c, T>,
+    out: String,
+    
+    /// The nesting of the current `if`s.
+    depth: usize,
+    
+    signed: bool,
+    
+    /// The size of `T`, in bytes.
+    size: usize,
+}
+
+impl<T: WasmInteger> Func<'_, '_, T> {
+    fn line(&mut self, line: impl Display) {
+        writeln!(self.out, "{:1$}{line}", "", 4 + 2 * self.depth).unwrap();
+    }
+    
+    fn open(&mut self, line: impl Display) {
+        self.line(line);
+        self.depth += 1;
+    }
+    
+    fn close(&mut self) {
+        self.depth -= 1;
+        self.line("end");
+    }
+    
+    /// Returns `status` from `run`, storing the `adr` and `value` pushed by the given instructions.
+    fn exit(&mut self, ir: Ir, status: u32, adr: Option<&str>, value: Option<&str>) {
+        self.line(format_args!("i32.const {ir}"));
+        self.line("global.set $ir");
+        
+        if let Some(adr) = adr {
+            self.line(adr);
+            self.line("global.set $adr");
+        }
+        
+        if let Some(value) = value {
+            self.line(value);
+            self.line("global.set $value");
+        }
+        
+        self.line(format_args!("i32.const {status}"));
+        self.line("return");
+    }
+    
+    /// Pops a condition, and returns `status` from `run` if it holds.
+    fn fail(&mut self, ir: Ir, status: u32, adr: Option<&str>, value: Option<&str>) {
+        self.open("if");
+        self.exit(ir, status, adr, value);
+        self.close();
+    }
+    
+    /// Pushes the address of a register.
+    fn address_of(&mut self, reg: Reg) {
+        match reg {
+            Reg::Direct(adr) => self.line(format_args!("i32.const {}", adr * self.size)),
+            Reg::Indirect => {
+                self.line("local.get $p");
+                if self.size > 1 {
+                    self.line(format_args!("i32.const {}", self.size.trailing_zeros()));
+                    self.line("i32.shl");
+                }
+            },
+        }
+    }
+    
+    /// Pushes the address of whether a register was initialized, without the offset.
+    fn init_of(&mut self, reg: Reg) {
+        match reg {
+            Reg::Direct(adr) => self.line(format_args!("i32.const {adr}")),
+            Reg::Indirect => self.line("local.get $p"),
+        }
+    }
+    
+    /// Pushes the value of a register.
+    fn load(&mut self, ir: Ir, reg: Reg) {
+        if self.wat.options.uninit_checks {
+            self.init_of(reg);
+            self.line(format_args!("i32.load8_u offset={}", self.wat.options.memory_limit * self.size));
+            self.line("i32.eqz");
+            self.fail(ir, status::READ_UNINIT, Some(&IndexOf(reg).to_string()), None);
+        }
+        
+        let sign = if self.signed { 's' } else { 'u' };
+        self.address_of(reg);
+        match self.size {
+            8 => self.line("i64.load"),
+            size => self.line(format_args!("i64.load{}_{sign}", size * 8)),
+        }
+    }
+    
+    /// Writes a local into a register.
+    fn store(&mut self, reg: Reg, local: &str) {
+        self.address_of(reg);
+        self.line(format_args!("local.get {local}"));
+        match self.size {
+            8 => self.line("i64.store"),
+            size => self.line(format_args!("i64.store{}", size * 8)),
+        }
+        
+        if self.wat.options.uninit_checks {
+            self.init_of(reg);
+            self.line("i32.const 1");
+            self.line(format_args!("i32.store8 offset={}", self.wat.options.memory_limit * self.size));
+        }
+    }
+    
+    /// Returns a register, `None` if accessing it fails.
+    fn address<L: Loc>(&mut self, ir: Ir, reg: Register<L>) -> Option<Reg> {
+        let limit = self.wat.options.memory_limit;
+        
+        let direct = |this: &mut Self, adr: usize, read: bool| if adr < limit {
+            Some(Reg::Direct(adr))
+        }
+        else {
+            this.exit(ir, this.wat.options.past_limit(read), Some(&format!("i64.const {adr}")), None);
+            None
+        };
+        
+        match reg {
+            Register::Direct(adr) => direct(self, adr.raw(), L::READ),
+            Register::Indirect(ptr) => {
+                let ptr = direct(self, ptr.raw(), true)?;
+                self.load(ir, ptr);
+                self.line("local.set $v");
+                
+                if self.signed {
+                    self.line("local.get $v");
+                    self.line("i64.const 0");
+                    self.line("i64.lt_s");
+                    self.fail(ir, status::INVALID_ADDRESS, None, Some("local.get $v"));
+                }
+                
+                self.line("local.get $v");
+                self.line(format_args!("i64.const {limit}"));
+                self.line("i64.ge_u");
+                self.fail(ir, self.wat.options.past_limit(L::READ), Some("local.get $v"), None);
+                
+                self.line("local.get $v");
+                self.line("i32.wrap_i64");
+                self.line("local.set $p");
+                Some(Reg::Indirect)
+            },
+        }
+    }
+    
+    /// Pushes a value, `None` if reading it fails.
+    fn value(&mut self, ir: Ir, v: Value<T>) -> Option<()> {
+        match v {
+            Value::Constant(v) => self.line(format_args!("i64.const {v}")),
+            Value::Register(reg) => {
+                let reg = self.address(ir, reg)?;
+                self.load(ir, reg);
+            },
+        }
+        
+        Some(())
+    }
+    
+    /// Sets `$r` to `$a op $b`, for `add`, `sub` and `mul`.
+    fn arith(&mut self, ir: Ir, op: &str) {
+        self.line("local.get $a");
+        self.line("local.get $b");
+        self.line(format_args!("i64.{op}"));
+        self.line("local.set $r");
+        
+        if !self.wat.options.overflow_checks {
+            return;
+        }
+        
+        // The operands of smaller integers can't overflow an `i64`
+        if self.size < 8 {
+            self.line("local.get $r");
+            self.line(format_args!("i64.const {}", T::max_value()));
+            if self.signed {
+                self.line("i64.gt_s");
+                self.line("local.get $r");
+                self.line(format_args!("i64.const {}", T::min_value()));
+                self.line("i64.lt_s");
+                self.line("i32.or");
+            }
+            else {
+                self.line("i64.gt_u");
+            }
+        }
+        else {
+            match (op, self.signed) {
+                ("add", true) => {
+                    for line in ["local.get $a", "local.get $r", "i64.xor", "local.get $b", "local.get $r", "i64.xor", "i64.and", "i64.const 0", "i64.lt_s"] {
+                        self.line(line);
+                    }
+                },
+                ("sub", true) => {
+                    for line in ["local.get $a", "local.get $b", "i64.xor", "local.get $a", "local.get $r", "i64.xor", "i64.and", "i64.const 0", "i64.lt_s"] {
+                        self.line(line);
+                    }
+                },
+                ("add", false) => {
+                    for line in ["local.get $r", "local.get $a", "i64.lt_u"] {
+                        self.line(line);
+                    }
+                },
+                ("sub", false) => {
+                    for line in ["local.get $a", "local.get $b", "i64.lt_u"] {
+                        self.line(line);
+                    }
+                },
+                (_, signed) => {
+                    // `$r / $a != $b`, without trapping
+                    if signed {
+                        self.line("local.get $a");
+                        self.line("i64.const -1");
+                        self.line("i64.eq");
+                        self.open("if (result i32)");
+                        self.line("local.get $b");
+                        self.line(format_args!("i64.const {}", T::min_value()));
+                        self.line("i64.eq");
+                        self.depth -= 1;
+                        self.line("else");
+                        self.depth += 1;
+                    }
+                    
+                    self.line("local.get $a");
+                    self.line("i64.eqz");
+                    self.open("if (result i32)");
+                    self.line("i32.const 0");
+                    self.depth -= 1;
+                    self.line("else");
+                    self.depth += 1;
+                    self.line("local.get $r");
+                    self.line("local.get $a");
+                    self.line(if signed { "i64.div_s" } else { "i64.div_u" });
+                    self.line("local.get $b");
+                    self.line("i64.ne");
+                    self.close();
+                    
+                    if signed {
+                        self.close();
+                    }
+                },
+            }
+        }
+        
+        self.fail(ir, status::INTEGER_OVERFLOW, None, None);
+    }
+    
+    /// Jumps to a constant instruction, after the condition if `cond`.
+    fn jump(&mut self, ir: Ir, cond: bool, inst: Instruction<T>) {
+        if cond {
+            self.open("if");
+        }
+        
+        #[cfg(feature = "indirect_jumps")]
+        if let Some(Address::Register(ptr)) = inst.jump() {
+            self.jump_indirect(ir, ptr.raw());
+            
+            if cond {
+                self.close();
+            }
+            return;
+        }
+        
+        match inst.target().filter(|adr| adr.inner() < self.wat.code.len()) {
+            Some(target) if target > ir => self.line(format_args!("br $L{target}")),
+            Some(target) => {
+                self.line(format_args!("i32.const {target}"));
+                self.line("local.set $jump");
+                self.line("br $dispatch");
+            },
+            None => self.exit(ir, status::INEXISTENT_JUMP, None, None),
+        }
+        
+        if cond {
+            self.close();
+        }
+    }
+    
+    /// Jumps to the instruction in a register.
+    #[cfg(feature = "indirect_jumps")]
+    fn jump_indirect(&mut self, ir: Ir, ptr: usize) {
+        if ptr >= self.wat.options.memory_limit {
+            return self.exit(ir, self.wat.options.past_limit(true), Some(&format!("i64.const {ptr}")), None);
+        }
+        
+        self.load(ir, Reg::Direct(ptr));
+        self.line("local.set $v");
+        
+        if self.signed {
+            self.line("local.get $v");
+            self.line("i64.const 0");
+            self.line("i64.lt_s");
+            self.fail(ir, status::INVALID_JUMP, None, Some("local.get $v"));
+        }
+        
+        self.line("local.get $v");
+        self.line(format_args!("i64.const {}", self.wat.code.len()));
+        self.line("i64.ge_u");
+        self.fail(ir, status::INEXISTENT_JUMP, None, None);
+        
+        self.line("local.get $v");
+        self.line("i32.wrap_i64");
+        self.line("local.set $jump");
+        self.line("br $dispatch");
+    }
+    
+    /// Writes an instruction.
+    fn inst(&mut self, ir: Ir, inst: Instruction<T>) {
+        const ACC: Reg = Reg::Direct(0);
+        
+        match inst {
+            Instruction::Read => {
+                self.address_of(ACC);
+                self.line("call $read");
+                self.line("i32.eqz");
+                self.fail(ir, status::READ_EOF, None, None);
+                
+                if self.wat.options.uninit_checks {
+                    self.init_of(ACC);
+                    self.line("i32.const 1");
+                    self.line(format_args!("i32.store8 offset={}", self.wat.options.memory_limit * self.size));
+                }
+            },
+            Instruction::Write => {
+                self.load(ir, ACC);
+                if self.size < 8 {
+                    self.line("i32.wrap_i64");
+                }
+                self.line("call $write");
+            },
+            Instruction::Load(v) => {
+                let Some(()) = self.value(ir, v) else { return };
+                self.line("local.set $r");
+                self.store(ACC, "$r");
+            },
+            Instruction::Store(reg) => {
+                self.load(ir, ACC);
+                self.line("local.set $r");
+                let Some(reg) = self.address(ir, reg) else { return };
+                self.store(reg, "$r");
+            },
+            Instruction::Increment(reg) | Instruction::Decrement(reg) => {
+                let Some(reg) = self.address(ir, reg) else { return };
+                self.load(ir, reg);
+                self.line("local.set $a");
+                self.line("i64.const 1");
+                self.line("local.set $b");
+                self.arith(ir, if matches!(inst, Instruction::Increment(_)) { "add" } else { "sub" });
+                self.store(reg, "$r");
+            },
+            Instruction::Add(v) | Instruction::Sub(v) | Instruction::Mul(v) => {
+                self.load(ir, ACC);
+                self.line("local.set $a");
+                let Some(()) = self.value(ir, v) else { return };
+                self.line("local.set $b");
+                
+                self.arith(ir, match inst {
+                    Instruction::Add(_) => "add",
+                    Instruction::Sub(_) => "sub",
+                    _ => "mul",
+                });
+                self.store(ACC, "$r");
+            },
+            Instruction::Div(v) | Instruction::Mod(v) => {
+                self.load(ir, ACC);
+                self.line("local.set $a");
+                let Some(()) = self.value(ir, v) else { return };
+                self.line("local.set $b");
+                
+                self.line("local.get $b");
+                self.line("i64.eqz");
+                self.fail(ir, status::INTEGER_OVERFLOW, None, None);
+                
+                if self.signed {
+                    self.line("local.get $a");
+                    self.line(format_args!("i64.const {}", T::min_value()));
+                    self.line("i64.eq");
+                    self.line("local.get $b");
+                    self.line("i64.const -1");
+                    self.line("i64.eq");
+                    self.line("i32.and");
+                    self.fail(ir, status::INTEGER_OVERFLOW, None, None);
+                }
+                
+                let op = if matches!(inst, Instruction::Div(_)) { "div" } else { "rem" };
+                let sign = if self.signed { 's' } else { 'u' };
+                self.line("local.get $a");
+                self.line("local.get $b");
+                self.line(format_args!("i64.{op}_{sign}"));
+                self.line("local.set $r");
+                self.store(ACC, "$r");
+            },
+            Instruction::Jump(_) => self.jump(ir, false, inst),
+            Instruction::JumpZero(_) | Instruction::JumpLtz(_) | Instruction::JumpGtz(_) => {
+                self.load(ir, ACC);
+                
+                let sign = if self.signed { 's' } else { 'u' };
+                match inst {
+                    Instruction::JumpZero(_) => self.line("i64.eqz"),
+                    Instruction::JumpLtz(_) => {
+                        self.line("i64.const 0");
+                        self.line(format_args!("i64.lt_{sign}"));
+                    },
+                    _ => {
+                        self.line("i64.const 0");
+                        self.line(format_args!("i64.gt_{sign}"));
+                    },
+                }
+                
+                self.jump(ir, true, inst);
+            },
+            Instruction::Stop => {
+                self.line(format_args!("i32.const {}", status::STOPPED));
+                self.line("return");
+            },
+            Instruction::Nop => {},
+        }
+    }
+}
+
+/// Displays the instruction pushing the index of a register, as an `i64`.
+struct IndexOf(Reg);
+
+impl Display for IndexOf {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Reg::Direct(adr) => write!(f, "i64.const {adr}"),
+            Reg::Indirect => f.write_str("local.get $v"),
+        }
+    }
+}
+
+impl<T: WasmInteger> Display for Wat<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let size = size_of::<T>();
+        let pages = (self.options.memory_limit * (size + 1)).div_ceil(1 << 16).max(1);
+        let len = self.code.len();
+        
+        writeln!(f, ";; Generated by rame.")?;
+        writeln!(f, "(module")?;
+        writeln!(f, "  (import \"rame\" \"read\" (func $read (param i32) (result i32)))")?;
+        writeln!(f, "  (import \"rame\" \"write\" (func $write (param {})))", if size < 8 { "i32" } else { "i64" })?;
+        writeln!(f)?;
+        writeln!(f, "  (memory (export \"memory\") {pages})")?;
+        writeln!(f, "  (global $ir (export \"ir\") (mut i32) (i32.const 0))")?;
+        writeln!(f, "  (global $adr (export \"adr\") (mut i64) (i64.const 0))")?;
+        writeln!(f, "  (global $value (export \"value\") (mut i64) (i64.const 0))")?;
+        writeln!(f)?;
+        writeln!(f, "  (func (export \"run\") (result i32)")?;
+        writeln!(f, "    (local $jump i32) (local $p i32) (local $a i64) (local $b i64) (local $r i64) (local $v i64)")?;
+        writeln!(f, "    loop $dispatch")?;
+        writeln!(f, "    block $end")?;
+        for ir in (0..len).rev() {
+            writeln!(f, "    block $L{ir}")?;
+        }
+        writeln!(f, "    local.get $jump")?;
+        write!(f, "    br_table")?;
+        for ir in 0..len {
+            write!(f, " $L{ir}")?;
+        }
+        writeln!(f, " $end")?;
+        
+        let mut func = Func {
+            wat: self,
+            out: String::new(),
+            depth: 0,
+            signed: T::min_value() < T::zero(),
+            size,
+        };
+        
+        for (ir, inst) in self.code.enumerate() {
+            func.line(format_args!("end $L{ir} ;; {inst}"));
+            func.inst(ir, inst);
+        }
+        
+        func.line("end $end");
+        func.exit(Ir::new(len.saturating_sub(1)), status::EOF, None, None);
+        func.line("end $dispatch");
+        func.line("unreachable");
+        
+        f.write_str(&func.out)?;
+        writeln!(f, "  )")?;
+        writeln!(f, ")")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::emit::test::{self, Backend};
+    use crate::emit::{Options, WasmInteger, Wat};
+    use crate::inst;
+    use crate::model::{Integer, RoCode};
+    use std::collections::HashMap;
+    use std::fmt::Write as _;
+    
+    /// Runs the `run` function of a module written by [`Wat`], with only the instructions it uses,
+    /// and returns what was written then the status, like [`test::expected`].
+    fn interpret<T: Integer>(wat: &str, input: &[T]) -> String {
+        let size = size_of::<T>();
+        let signed = T::min_value() < T::zero();
+        let unsigned = |v: i64| if size == 8 { v as u64 } else { v as u64 & ((1 << (size * 8)) - 1) };
+        
+        let pages = wat.lines().find_map(|l| l.trim().strip_prefix("(memory (export \"memory\") ")).unwrap();
+        let mut memory = vec![0_u8; pages.trim_end_matches(')').parse::<usize>().unwrap() << 16];
+        
+        let body = wat.lines()
+            .skip_while(|l| !l.trim().starts_with("(local "))
+            .skip(1)
+            .take_while(|l| l.trim() != ")")
+            .map(|l| l.split(";;").next().unwrap().split_whitespace().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        
+        // The `end` of each `block`, `loop`, `if` and `else`, and the `else` of each `if`
+        let mut ends = HashMap::new();
+        let mut elses = HashMap::new();
+        let mut open = Vec::new();
+        for (pc, line) in body.iter().enumerate() {
+            match line[0] {
+                "block" | "loop" | "if" => open.push(pc),
+                "else" => {
+                    elses.insert(*open.last().unwrap(), pc);
+                    ends.insert(pc, 0);
+                },
+                "end" => {
+                    let start = open.pop().expect("unbalanced `end`");
+                    ends.insert(start, pc);
+                    if let Some(&e) = elses.get(&start) {
+                        ends.insert(e, pc);
+                    }
+                },
+                _ => {},
+            }
+        }
+        assert!(open.is_empty(), "unclosed blocks");
+        
+        let mut input = input.iter();
+        let mut output = String::new();
+        let mut stack = Vec::<i64>::new();
+        let mut ctrl = Vec::<usize>::new();
+        let mut locals = HashMap::<&str, i64>::new();
+        let mut globals = HashMap::<&str, i64>::new();
+        let mut pc = 0;
+        
+        let offset = |line: &[&str]| line.get(1).map_or(0, |o| o.strip_prefix("offset=").unwrap().parse::<usize>().unwrap());
+        let status = loop {
+            let line = &body[pc];
+            let mut pop = || stack.pop().unwrap();
+            pc += 1;
+            
+            let branch = |label: &str, ctrl: &mut Vec<usize>| loop {
+                let start = *ctrl.last().unwrap();
+                if body[start].get(1) == Some(&label) {
+                    if body[start][0] == "loop" {
+                        break start + 1;
+                    }
+                    
+                    ctrl.pop();
+                    break ends[&start] + 1;
+                }
+                ctrl.pop();
+            };
+            
+            let v = match *line.as_slice() {
+                ["block" | "loop", ..] => {
+                    ctrl.push(pc - 1);
+                    continue;
+                },
+                ["if", ..] => {
+                    if pop() != 0 {
+                        ctrl.push(pc - 1);
+                    }
+                    else if let Some(&e) = elses.get(&(pc - 1)) {
+                        ctrl.push(pc - 1);
+                        pc = e + 1;
+                    }
+                    else {
+                        pc = ends[&(pc - 1)] + 1;
+                    }
+                    continue;
+                },
+                ["else"] => {
+                    pc = ends[&(pc - 1)];
+                    continue;
+                },
+                ["end", ..] => {
+                    ctrl.pop();
+                    continue;
+                },
+                ["br", label] => {
+                    pc = branch(label, &mut ctrl);
+                    continue;
+                },
+                ["br_table", ref labels @ ..] => {
+                    let i = usize::try_from(pop()).unwrap().min(labels.len() - 1);
+                    pc = branch(labels[i], &mut ctrl);
+                    continue;
+                },
+                ["return"] => break pop(),
+                ["i32.const" | "i64.const", v] => v.parse::<i64>().unwrap_or_else(|_| v.parse::<u64>().unwrap() as i64),
+                ["local.get", local] => locals.get(local).copied().unwrap_or(0),
+                ["local.set", local] => {
+                    locals.insert(local, pop());
+                    continue;
+                },
+                ["global.set", global] => {
+                    globals.insert(global, pop());
+                    continue;
+                },
+                ["call", "$read"] => {
+                    let ptr = usize::try_from(pop()).unwrap();
+                    input.next().map_or(0, |v| {
+                        memory[ptr..ptr + size].copy_from_slice(&v.to_i128().unwrap().to_le_bytes()[..size]);
+                        1
+                    })
+                },
+                ["call", "$write"] => {
+                    let v = pop();
+                    if signed {
+                        write!(output, "{v} ").unwrap();
+                    }
+                    else {
+                        write!(output, "{} ", unsigned(v)).unwrap();
+                    }
+                    continue;
+                },
+                [op, ..] if op.starts_with("i64.load") || op == "i32.load8_u" => {
+                    let bytes = match op.trim_start_matches("i64.load").trim_start_matches("i32.load") {
+                        "" => 8,
+                        bits => bits[..bits.len() - 2].parse::<usize>().unwrap() / 8,
+                    };
+                    
+                    let adr = usize::try_from(pop()).unwrap() + offset(line);
+                    let mut le = [0; 8];
+                    le[..bytes].copy_from_slice(&memory[adr..adr + bytes]);
+                    
+                    let shift = 64 - 8 * bytes;
+                    if op.ends_with("_s") {
+                        i64::from_le_bytes(le) << shift >> shift
+                    }
+                    else {
+                        i64::from_le_bytes(le)
+                    }
+                },
+                [op, ..] if op.starts_with("i64.store") || op == "i32.store8" => {
+                    let bytes = match op.trim_start_matches("i64.store").trim_start_matches("i32.store") {
+                        "" => 8,
+                        bits => bits.parse::<usize>().unwrap() / 8,
+                    };
+                    
+                    let v = pop();
+                    let adr = usize::try_from(pop()).unwrap() + offset(line);
+                    memory[adr..adr + bytes].copy_from_slice(&v.to_le_bytes()[..bytes]);
+                    continue;
+                },
+                ["i64.eqz" | "i32.eqz"] => (pop() == 0).into(),
+                ["i32.wrap_i64"] => pop() << 32 >> 32,
+                [op] => {
+                    let (rhs, lhs) = (pop(), pop());
+                    match op {
+                        "i64.add" => lhs.wrapping_add(rhs),
+                        "i64.sub" => lhs.wrapping_sub(rhs),
+                        "i64.mul" => lhs.wrapping_mul(rhs),
+                        "i64.div_s" => lhs.checked_div(rhs).expect("trap"),
+                        "i64.rem_s" => lhs.checked_rem(rhs).expect("trap"),
+                        "i64.div_u" => (lhs as u64).checked_div(rhs as u64).expect("trap") as i64,
+                        "i64.rem_u" => (lhs as u64).checked_rem(rhs as u64).expect("trap") as i64,
+                        "i64.and" | "i32.and" => lhs & rhs,
+                        "i32.or" => lhs | rhs,
+                        "i64.xor" => lhs ^ rhs,
+                        "i32.shl" => lhs << rhs,
+                        "i64.eq" => (lhs == rhs).into(),
+                        "i64.ne" => (lhs != rhs).into(),
+                        "i64.lt_s" => (lhs < rhs).into(),
+                        "i64.gt_s" => (lhs > rhs).into(),
+                        "i64.lt_u" => ((lhs as u64) < rhs as u64).into(),
+                        "i64.gt_u" => (lhs as u64 > rhs as u64).into(),
+                        "i64.ge_u" => (lhs as u64 >= rhs as u64).into(),
+                        _ => panic!("unknown instruction `{op}`"),
+                    }
+                },
+                _ => panic!("unknown instruction `{}`", line.join(" ")),
+            };
+            
+            stack.push(v);
+        };
+        
+        let global = |name| globals.get(name).copied().unwrap_or(0);
+        match status {
+            2 | 4 => format!("{output}| {status} R{}\n", global("$adr") as u64),
+            3 => format!("{output}| {status} {}\n", global("$value")),
+            _ => format!("{output}| {status}\n"),
+        }
+    }
+    
+    /// Interprets with [`interpret`].
+    struct Interpreter;
+    
+    impl<T: WasmInteger> Backend<T> for Interpreter {
+        fn run(code: &RoCode<T>, options: Options, _: &str, inputs: &[&[T]]) -> Option<Vec<String>> {
+            let wat = Wat::new(code).options(options).unwrap().to_string();
+            Some(inputs.iter().map(|input| interpret(&wat, input)).collect())
+        }
+    }
+    
+    #[test]
+    fn fibo() {
+        test::fibo::<Interpreter>();
+    }
+    
+    #[test]
+    fn errors() {
+        test::errors::<Interpreter>();
+        
+        let code = RoCode::<i8>::from([inst!(READ), inst!(ADD #100), inst!(WRITE), inst!(LOAD @1), inst!(STOP)]);
+        let wat = Wat::new(&code).options(Options::new().memory_limit(1000).overflow_checks(false).uninit_checks(false)).unwrap().to_string();
+        assert_eq!(interpret::<i8>(&wat, &[50]), "-106 | 0\n");
+        
+        let error = Wat::new(&code).options(Options::new().memory_limit(usize::MAX)).unwrap_err();
+        assert_eq!(error.max(), u32::MAX as usize / 2);
+    }
+    
+    #[test]
+    fn fuzz() {
+        test::fuzz::<i16, Interpreter>(100);
+        test::fuzz::<i64, Interpreter>(100);
+        test::fuzz::<u8, Interpreter>(100);
+    }
+}
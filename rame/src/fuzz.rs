@@ -0,0 +1,388 @@
+//! Random programs, to test the runner and the optimizer.
+
+use crate::model::{Address, Instruction, Integer, Ir, Loc, Register, RoCode, RoLoc, RwLoc, Value, WoLoc};
+
+/// A small pseudo-random number generator (`SplitMix64`).
+#[derive(Clone, Debug)]
+pub struct Rng(u64);
+
+impl Rng {
+    /// Creates a new generator from its seed.
+    #[must_use]
+    pub const fn new(seed: u64) -> Rng {
+        Rng(seed)
+    }
+    
+    /// Returns the next random number.
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+    
+    /// Returns a number in `0..n`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is zero.
+    #[expect(clippy::cast_possible_truncation)]
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+    
+    /// Returns `true` with a probability of `1/n`.
+    pub fn one_in(&mut self, n: usize) -> bool {
+        self.below(n) == 0
+    }
+    
+    /// Returns a value in `-n..=n`, or zero if it doesn't fit in `T`.
+    #[expect(clippy::cast_possible_wrap)]
+    pub fn small<T: Integer>(&mut self, n: u32) -> T {
+        let v = (self.next_u64() % (2 * u64::from(n) + 1)) as i64 - i64::from(n);
+        
+        // e.g. negative values for unsigned types
+        T::from(v).unwrap_or_else(T::zero)
+    }
+    
+    /// Returns an interesting value: either a small one, or one near the bounds of `T`.
+    pub fn value<T: Integer>(&mut self) -> T {
+        match self.below(4) {
+            0 | 1 => self.small(10),
+            2 => self.small(1000),
+            _ => match self.below(4) {
+                0 => T::min_value(),
+                1 => T::max_value(),
+                2 => T::min_value() + T::one(),
+                _ => T::max_value() - T::one(),
+            },
+        }
+    }
+}
+
+/// Generates random but well-formed programs:
+/// all jump targets are valid, and all loops are bounded.
+///
+/// Forward jumps may land anywhere, and backward jumps only close a loop,
+/// whose counter is reserved to it.
+///
+/// # Example
+///
+/// ```
+/// # use rame::fuzz::{Generator, Rng};
+/// # use rame::model::RoCode;
+/// # use rame::runner::Ram;
+/// let code: RoCode<i32> = Generator::default().generate(&mut Rng::new(42));
+///
+/// // Either stops or fails, but doesn't loop forever
+/// let mut ram = Ram::new(code, [1, 2, 3]);
+/// assert!(!matches!(ram.run_for(1_000_000), Ok(false)));
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct Generator {
+    /// Approximate count of instructions to generate.
+    pub len: usize,
+    
+    /// Count of general-purpose registers, starting from `R1`.
+    pub registers: usize,
+    
+    /// Maximum nesting of loops.
+    pub depth: usize,
+    
+    /// Maximum iteration count of a loop.
+    pub iterations: u32,
+}
+
+impl Default for Generator {
+    fn default() -> Self {
+        Generator {
+            len: 40,
+            registers: 4,
+            depth: 2,
+            iterations: 4,
+        }
+    }
+}
+
+/// A label identifier.
+type Label = usize;
+
+/// An instruction, before the labels are resolved.
+#[derive(Copy, Clone, Debug)]
+enum Item<T: Integer> {
+    Inst(Instruction<T>),
+    Jump(fn(Address) -> Instruction<T>, Label),
+    /// Loads the address of a label into ACC.
+    #[cfg_attr(not(feature = "indirect_jumps"), expect(dead_code))]
+    LoadLabel(Label),
+    Label(Label),
+}
+
+impl Generator {
+    /// Generates a random program.
+    ///
+    /// The registers `R1` to `R{registers}` hold data, the next register points to one of them,
+    /// and the next `depth` registers are loop counters.
+    pub fn generate<T: Integer>(&self, rng: &mut Rng) -> RoCode<T> {
+        let mut b = Builder {
+            gen: self,
+            rng,
+            items: Vec::new(),
+            labels: 0,
+            pending: Vec::new(),
+            len: 0,
+        };
+        
+        // Initialize some registers
+        for r in 1..=self.registers {
+            if !b.rng.one_in(4) {
+                let v = b.rng.value();
+                b.inst(Instruction::Load(Value::Constant(v)));
+                b.inst(Instruction::Store(Register::Direct(WoLoc::from(r))));
+            }
+        }
+        
+        let data: RoLoc = b.data();
+        b.inst(Instruction::Load(Value::Constant(T::from(data.raw()).unwrap_or_else(T::one))));
+        b.inst(Instruction::Store(Register::Direct(WoLoc::from(self.pointer()))));
+        
+        b.block(0);
+        
+        for label in std::mem::take(&mut b.pending) {
+            b.items.push(Item::Label(label));
+        }
+        
+        b.inst(Instruction::Stop);
+        b.resolve()
+    }
+    
+    /// The register pointing to a data register.
+    const fn pointer(&self) -> usize {
+        self.registers + 1
+    }
+    
+    /// The counter of loops at the specified depth.
+    const fn counter(&self, depth: usize) -> usize {
+        self.registers + 2 + depth
+    }
+    
+    /// A register that indirect jumps read their address from.
+    #[cfg(feature = "indirect_jumps")]
+    const fn trampoline(&self) -> usize {
+        self.registers + 2 + self.depth
+    }
+}
+
+struct Builder<'g, 'r, T: Integer> {
+    gen: &'g Generator,
+    rng: &'r mut Rng,
+    items: Vec<Item<T>>,
+    
+    /// Count of created labels.
+    labels: usize,
+    
+    /// Labels that were jumped to, but not yet placed.
+    pending: Vec<Label>,
+    
+    /// Count of generated instructions.
+    len: usize,
+}
+
+impl<T: Integer> Builder<'_, '_, T> {
+    fn inst(&mut self, inst: Instruction<T>) {
+        self.items.push(Item::Inst(inst));
+        self.len += 1;
+    }
+    
+    fn label(&mut self) -> Label {
+        self.labels += 1;
+        self.labels - 1
+    }
+    
+    /// Generates instructions until the length is reached, or randomly for nested blocks.
+    fn block(&mut self, depth: usize) {
+        while self.len < self.gen.len {
+            if depth > 0 && self.rng.one_in(8) {
+                return;
+            }
+            
+            match self.rng.below(20) {
+                0..=11 => {
+                    let inst = self.simple();
+                    self.inst(inst);
+                },
+                12..=14 => {
+                    // forward jump
+                    let label = self.label();
+                    let jump = [Instruction::Jump, Instruction::JumpZero, Instruction::JumpLtz, Instruction::JumpGtz][self.rng.below(4)];
+                    self.items.push(Item::Jump(jump, label));
+                    self.len += 1;
+                    self.pending.push(label);
+                },
+                15 | 16 if !self.pending.is_empty() => {
+                    let i = self.rng.below(self.pending.len());
+                    let label = self.pending.swap_remove(i);
+                    self.items.push(Item::Label(label));
+                },
+                17 | 18 if depth < self.gen.depth => {
+                    // LOAD #n; STORE c; start: ...; DEC c; LOAD c; JUMG start
+                    let counter = self.gen.counter(depth);
+                    let n = T::from(1 + self.rng.below(self.gen.iterations as usize)).unwrap_or_else(T::one);
+                    self.inst(Instruction::Load(Value::Constant(n)));
+                    self.inst(Instruction::Store(Register::Direct(WoLoc::from(counter))));
+                    
+                    let start = self.label();
+                    self.items.push(Item::Label(start));
+                    self.block(depth + 1);
+                    
+                    self.inst(Instruction::Decrement(Register::Direct(RwLoc::from(counter))));
+                    self.inst(Instruction::Load(Value::Register(Register::Direct(RoLoc::from(counter)))));
+                    self.items.push(Item::Jump(Instruction::JumpGtz, start));
+                    self.len += 1;
+                },
+                #[cfg(feature = "indirect_jumps")]
+                19 => {
+                    // LOAD #label; STORE t; JUMx @t
+                    let label = self.label();
+                    let trampoline = self.gen.trampoline();
+                    self.items.push(Item::LoadLabel(label));
+                    self.len += 1;
+                    self.inst(Instruction::Store(Register::Direct(WoLoc::from(trampoline))));
+                    
+                    let jump = [Instruction::Jump, Instruction::JumpZero, Instruction::JumpLtz, Instruction::JumpGtz][self.rng.below(4)];
+                    self.inst(jump(Address::Register(RoLoc::from(trampoline))));
+                    self.pending.push(label);
+                },
+                _ => {},
+            }
+        }
+    }
+    
+    /// A random data register.
+    fn data<L: Loc>(&mut self) -> L {
+        L::from(1 + self.rng.below(self.gen.registers))
+    }
+    
+    /// A random register to write to.
+    fn register<L: Loc>(&mut self) -> Register<L> {
+        if self.rng.one_in(4) {
+            Register::Indirect(RoLoc::from(self.gen.pointer()))
+        }
+        else {
+            Register::Direct(self.data())
+        }
+    }
+    
+    /// A random operand; any register can be read.
+    fn value(&mut self) -> Value<T> {
+        match self.rng.below(8) {
+            0..=2 => Value::Constant(self.rng.small(4)),
+            3 => Value::Constant(self.rng.value()),
+            4 => Value::Register(Register::Direct(RoLoc::from(self.rng.below(self.gen.counter(self.gen.depth))))),
+            _ => Value::Register(self.register()),
+        }
+    }
+    
+    /// A random non-jumping instruction.
+    fn simple(&mut self) -> Instruction<T> {
+        match self.rng.below(16) {
+            0 => Instruction::Read,
+            1 | 2 => Instruction::Write,
+            3 | 4 => Instruction::Load(self.value()),
+            5 | 6 => Instruction::Store(self.register()),
+            7 => Instruction::Increment(self.register()),
+            8 => Instruction::Decrement(self.register()),
+            9 | 10 => Instruction::Add(self.value()),
+            11 => Instruction::Sub(self.value()),
+            12 => Instruction::Mul(self.value()),
+            13 => Instruction::Div(self.value()),
+            14 => Instruction::Mod(self.value()),
+            _ => Instruction::Nop,
+        }
+    }
+    
+    /// Replaces the labels by their address.
+    fn resolve(self) -> RoCode<T> {
+        let mut adrs = vec![0; self.labels];
+        let mut ir = 0;
+        for item in &self.items {
+            match item {
+                Item::Label(label) => adrs[*label] = ir,
+                _ => ir += 1,
+            }
+        }
+        
+        self.items.into_iter().filter_map(|item| match item {
+            Item::Inst(inst) => Some(inst),
+            Item::Jump(jump, label) => Some(jump(Address::from(Ir::new(adrs[label])))),
+            Item::LoadLabel(label) => Some(Instruction::Load(Value::Constant(T::from(adrs[label]).expect("the code should be addressable")))),
+            Item::Label(_) => None,
+        }).collect::<Vec<_>>().as_slice().into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::runner::Ram;
+    
+    /// Count of programs to generate for each property; can be overridden by `RAME_FUZZ_PROGRAMS`.
+    fn programs() -> u64 {
+        std::env::var("RAME_FUZZ_PROGRAMS").ok().and_then(|n| n.parse().ok()).unwrap_or(300)
+    }
+    
+    #[test]
+    fn generate_well_formed_programs() {
+        for seed in 0..programs() {
+            let mut rng = Rng::new(seed);
+            let code = Generator::default().generate::<i16>(&mut rng);
+            
+            for inst in code.iter() {
+                if let Some(adr) = inst.target() {
+                    assert!(adr < code.len(), "seed {seed}: jumping out of the code:\n{code}");
+                }
+            }
+            
+            let input = (0..4).map(|_| rng.value()).collect::<Vec<i16>>();
+            let mut ram = Ram::new(code, input);
+            assert!(!matches!(ram.run_for(1_000_000), Ok(false)), "seed {seed}: infinite loop:\n{}", ram.code());
+        }
+    }
+    
+    /// Runs every pass on random programs, and compares their behavior with the original programs.
+    #[cfg(feature = "optimizer")]
+    fn fuzz_passes<T: Integer>() {
+        use crate::optimizer::{run_pass, PassManager, Validator};
+        use std::panic::{self, AssertUnwindSafe};
+        
+        for seed in 0..programs() {
+            let code = Generator::default().generate::<T>(&mut Rng::new(seed));
+            let mut pm = PassManager::new();
+            
+            let passes = pm.registered().to_vec();
+            let optimized = passes.iter()
+                .map(|p| (p.name, panic::catch_unwind(AssertUnwindSafe(|| run_pass(&code, p.pass)))))
+                .chain([("all passes", panic::catch_unwind(AssertUnwindSafe(|| pm.run(&code))))]);
+            
+            for (name, optimized) in optimized {
+                let Ok(optimized) = optimized else {
+                    panic!("seed {seed}: `{name}` panicked on:\n{code}");
+                };
+                
+                if let Err(e) = Validator::new(&code, &optimized).check_random(16, seed) {
+                    panic!("seed {seed}: `{name}` miscompiled:\n{code}\ninto:\n{optimized}\n{e}");
+                }
+            }
+        }
+    }
+    
+    #[test]
+    #[cfg(feature = "optimizer")]
+    fn passes_preserve_behavior() {
+        fuzz_passes::<i8>();
+        fuzz_passes::<i32>();
+        fuzz_passes::<u8>();
+    }
+}
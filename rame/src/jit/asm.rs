@@ -0,0 +1,330 @@
+//! A minimal x86-64 encoder, for the instructions the JIT emits.
+
+/// A general-purpose register.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[repr(u8)]
+pub(super) enum Reg {
+    Rax = 0,
+    Rcx = 1,
+    Rdx = 2,
+    Rbx = 3,
+    Rsi = 6,
+    Rdi = 7,
+    R8 = 8,
+    R9 = 9,
+    R12 = 12,
+    R13 = 13,
+}
+
+impl Reg {
+    /// The 3 low bits, for ModRM and SIB.
+    const fn low(self) -> u8 {
+        self as u8 & 7
+    }
+    
+    /// The high bit, for REX.
+    const fn high(self) -> u8 {
+        self as u8 >> 3
+    }
+}
+
+/// A condition code, for `Jcc`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[repr(u8)]
+pub(super) enum Cond {
+    Overflow = 0x0,
+    #[cfg(feature = "indirect_jumps")]
+    NoOverflow = 0x1,
+    #[cfg(feature = "indirect_jumps")]
+    Below = 0x2,
+    AboveEq = 0x3,
+    Eq = 0x4,
+    NotEq = 0x5,
+    Sign = 0x8,
+    #[cfg(feature = "indirect_jumps")]
+    NotSign = 0x9,
+    Less = 0xC,
+    #[cfg(feature = "indirect_jumps")]
+    GreaterEq = 0xD,
+    #[cfg(feature = "indirect_jumps")]
+    LessEq = 0xE,
+    Greater = 0xF,
+}
+
+#[cfg(feature = "indirect_jumps")]
+impl Cond {
+    /// Returns the opposite condition.
+    pub(super) const fn not(self) -> Cond {
+        match self {
+            Cond::Overflow => Cond::NoOverflow,
+            Cond::NoOverflow => Cond::Overflow,
+            Cond::Below => Cond::AboveEq,
+            Cond::AboveEq => Cond::Below,
+            Cond::Eq => Cond::NotEq,
+            Cond::NotEq => Cond::Eq,
+            Cond::Sign => Cond::NotSign,
+            Cond::NotSign => Cond::Sign,
+            Cond::Less => Cond::GreaterEq,
+            Cond::GreaterEq => Cond::Less,
+            Cond::LessEq => Cond::Greater,
+            Cond::Greater => Cond::LessEq,
+        }
+    }
+}
+
+/// A position in the code, maybe not known yet.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub(super) struct Label(usize);
+
+/// Some machine code being written.
+#[derive(Debug, Default)]
+pub(super) struct Asm {
+    code: Vec<u8>,
+    
+    /// The offset of each label, if bound.
+    labels: Vec<Option<usize>>,
+    /// The offsets of the 32-bit displacements to the labels.
+    fixups: Vec<(usize, Label)>,
+}
+
+const REX_W: u8 = 0x48;
+
+impl Asm {
+    /// Returns the current offset.
+    pub(super) fn offset(&self) -> usize {
+        self.code.len()
+    }
+    
+    pub(super) fn label(&mut self) -> Label {
+        self.labels.push(None);
+        Label(self.labels.len() - 1)
+    }
+    
+    /// Places `label` at the current offset.
+    pub(super) fn bind(&mut self, label: Label) {
+        debug_assert!(self.labels[label.0].is_none(), "label bound twice");
+        self.labels[label.0] = Some(self.offset());
+    }
+    
+    #[cfg(feature = "indirect_jumps")]
+    /// Returns the offset of `label`, which must be bound.
+    pub(super) fn offset_of(&self, label: Label) -> usize {
+        self.labels[label.0].expect("unbound label")
+    }
+    
+    fn emit(&mut self, bytes: &[u8]) {
+        self.code.extend_from_slice(bytes);
+    }
+    
+    /// Emits a 32-bit displacement to `label`, relative to the end of the displacement.
+    fn rel32(&mut self, label: Label) {
+        self.fixups.push((self.offset(), label));
+        self.emit(&[0; 4]);
+    }
+    
+    #[cfg(feature = "indirect_jumps")]
+    /// Emits the 32-bit offset of `label` relative to `table`, for jump tables.
+    pub(super) fn table_entry(&mut self, label: Label, table: Label) {
+        let entry = i32::try_from(self.offset_of(label)).expect("code too large") - i32::try_from(self.offset_of(table)).expect("code too large");
+        self.emit(&entry.to_le_bytes());
+    }
+    
+    /// Pads with `int3` until the offset is a multiple of `align`.
+    pub(super) fn align(&mut self, align: usize) {
+        while self.offset() % align != 0 {
+            self.emit(&[0xCC]);
+        }
+    }
+    
+    /// Resolves the displacements to the labels, and returns the code.
+    pub(super) fn finish(mut self) -> Vec<u8> {
+        for (at, label) in self.fixups {
+            let target = self.labels[label.0].expect("unbound label");
+            let rel = i32::try_from(target as isize - (at + 4) as isize).expect("code too large");
+            self.code[at..at + 4].copy_from_slice(&rel.to_le_bytes());
+        }
+        
+        self.code
+    }
+    
+    /// `push reg`
+    pub(super) fn push(&mut self, reg: Reg) {
+        if reg.high() != 0 {
+            self.emit(&[0x41]);
+        }
+        self.emit(&[0x50 | reg.low()]);
+    }
+    
+    /// `pop reg`
+    pub(super) fn pop(&mut self, reg: Reg) {
+        if reg.high() != 0 {
+            self.emit(&[0x41]);
+        }
+        self.emit(&[0x58 | reg.low()]);
+    }
+    
+    /// `ret`
+    pub(super) fn ret(&mut self) {
+        self.emit(&[0xC3]);
+    }
+    
+    /// `op dst, src`, for the `op r/m64, r64` instructions.
+    fn rr(&mut self, op: u8, dst: Reg, src: Reg) {
+        self.emit(&[REX_W | src.high() << 2 | dst.high(), op, 0xC0 | src.low() << 3 | dst.low()]);
+    }
+    
+    /// `mov dst, src`
+    pub(super) fn mov(&mut self, dst: Reg, src: Reg) {
+        self.rr(0x89, dst, src);
+    }
+    
+    /// `add dst, src`
+    pub(super) fn add(&mut self, dst: Reg, src: Reg) {
+        self.rr(0x01, dst, src);
+    }
+    
+    /// `sub dst, src`
+    pub(super) fn sub(&mut self, dst: Reg, src: Reg) {
+        self.rr(0x29, dst, src);
+    }
+    
+    /// `test a, b`
+    pub(super) fn test(&mut self, a: Reg, b: Reg) {
+        self.rr(0x85, a, b);
+    }
+    
+    /// `cmp a, b`
+    pub(super) fn cmp(&mut self, a: Reg, b: Reg) {
+        self.rr(0x39, a, b);
+    }
+    
+    /// `imul dst, src`
+    pub(super) fn imul(&mut self, dst: Reg, src: Reg) {
+        self.emit(&[REX_W | dst.high() << 2 | src.high(), 0x0F, 0xAF, 0xC0 | dst.low() << 3 | src.low()]);
+    }
+    
+    /// `cqo`, sign-extending `rax` into `rdx`.
+    pub(super) fn cqo(&mut self) {
+        self.emit(&[REX_W, 0x99]);
+    }
+    
+    /// `idiv reg`
+    pub(super) fn idiv(&mut self, reg: Reg) {
+        self.emit(&[REX_W | reg.high(), 0xF7, 0xF8 | reg.low()]);
+    }
+    
+    /// `add reg, imm8`, or `sub reg, -imm8`.
+    pub(super) fn add_imm8(&mut self, reg: Reg, imm: i8) {
+        let (ext, imm) = if imm < 0 { (5, imm.unsigned_abs()) } else { (0, imm.unsigned_abs()) };
+        self.emit(&[REX_W | reg.high(), 0x83, 0xC0 | ext << 3 | reg.low(), imm]);
+    }
+    
+    /// `cmp reg, imm32`, the immediate being sign-extended.
+    pub(super) fn cmp_imm(&mut self, reg: Reg, imm: i32) {
+        self.emit(&[REX_W | reg.high(), 0x81, 0xF8 | reg.low()]);
+        self.emit(&imm.to_le_bytes());
+    }
+    
+    /// `mov reg, imm`, with the shortest encoding.
+    pub(super) fn mov_imm(&mut self, reg: Reg, imm: i64) {
+        if let Ok(imm) = u32::try_from(imm) {
+            // Zero-extended
+            if reg.high() != 0 {
+                self.emit(&[0x41]);
+            }
+            self.emit(&[0xB8 | reg.low()]);
+            self.emit(&imm.to_le_bytes());
+        }
+        else if let Ok(imm) = i32::try_from(imm) {
+            // Sign-extended
+            self.emit(&[REX_W | reg.high(), 0xC7, 0xC0 | reg.low()]);
+            self.emit(&imm.to_le_bytes());
+        }
+        else {
+            self.emit(&[REX_W | reg.high(), 0xB8 | reg.low()]);
+            self.emit(&imm.to_le_bytes());
+        }
+    }
+    
+    /// `op reg, [base + index * scale]`, or `op [base + index * scale], reg`.
+    fn sib(&mut self, rex: u8, op: &[u8], reg: u8, base: Reg, index: Reg, scale: u8) {
+        debug_assert!(base != Reg::R13 && base.low() != 5, "`base` needs a displacement");
+        self.emit(&[rex | (reg >> 3) << 2 | index.high() << 1 | base.high()]);
+        self.emit(op);
+        self.emit(&[(reg & 7) << 3 | 0b100, scale << 6 | index.low() << 3 | base.low()]);
+    }
+    
+    /// `mov dst, [base + index * 8]`
+    pub(super) fn load(&mut self, dst: Reg, base: Reg, index: Reg) {
+        self.sib(REX_W, &[0x8B], dst as u8, base, index, 3);
+    }
+    
+    /// `mov [base + index * 8], src`
+    pub(super) fn store(&mut self, base: Reg, index: Reg, src: Reg) {
+        self.sib(REX_W, &[0x89], src as u8, base, index, 3);
+    }
+    
+    /// `cmp byte [base + index], 0`
+    pub(super) fn cmp_byte_zero(&mut self, base: Reg, index: Reg) {
+        self.sib(0x40, &[0x80], 7, base, index, 0);
+        self.emit(&[0]);
+    }
+    
+    /// `mov byte [base + index], imm`
+    pub(super) fn store_byte(&mut self, base: Reg, index: Reg, imm: u8) {
+        self.sib(0x40, &[0xC6], 0, base, index, 0);
+        self.emit(&[imm]);
+    }
+    
+    #[cfg(feature = "indirect_jumps")]
+    /// `movsxd dst, dword [base + index * 4]`
+    pub(super) fn load_i32(&mut self, dst: Reg, base: Reg, index: Reg) {
+        self.sib(REX_W, &[0x63], dst as u8, base, index, 2);
+    }
+    
+    /// `mov dst, [base + disp8]`
+    pub(super) fn load_disp8(&mut self, dst: Reg, base: Reg, disp: i8) {
+        self.emit(&[REX_W | dst.high() << 2 | base.high(), 0x8B, 0x40 | dst.low() << 3 | base.low(), disp.to_le_bytes()[0]]);
+    }
+    
+    /// `mov [base + disp8], src`
+    pub(super) fn store_disp8(&mut self, base: Reg, disp: i8, src: Reg) {
+        self.emit(&[REX_W | src.high() << 2 | base.high(), 0x89, 0x40 | src.low() << 3 | base.low(), disp.to_le_bytes()[0]]);
+    }
+    
+    #[cfg(feature = "indirect_jumps")]
+    /// `lea dst, [rip + label]`
+    pub(super) fn lea(&mut self, dst: Reg, label: Label) {
+        self.emit(&[REX_W | dst.high() << 2, 0x8D, dst.low() << 3 | 0b101]);
+        self.rel32(label);
+    }
+    
+    /// `jmp label`
+    pub(super) fn jmp(&mut self, label: Label) {
+        self.emit(&[0xE9]);
+        self.rel32(label);
+    }
+    
+    /// `jcc label`
+    pub(super) fn jcc(&mut self, cond: Cond, label: Label) {
+        self.emit(&[0x0F, 0x80 | cond as u8]);
+        self.rel32(label);
+    }
+    
+    #[cfg(feature = "indirect_jumps")]
+    /// `jmp reg`
+    pub(super) fn jmp_reg(&mut self, reg: Reg) {
+        if reg.high() != 0 {
+            self.emit(&[0x41]);
+        }
+        self.emit(&[0xFF, 0xE0 | reg.low()]);
+    }
+    
+    /// `call reg`
+    pub(super) fn call(&mut self, reg: Reg) {
+        if reg.high() != 0 {
+            self.emit(&[0x41]);
+        }
+        self.emit(&[0xFF, 0xD0 | reg.low()]);
+    }
+}
@@ -0,0 +1,81 @@
+//! Memory mapped for the compiled code.
+
+use std::ffi::{c_int, c_long, c_void};
+use std::io;
+use std::ptr::{self, NonNull};
+
+const PROT_READ: c_int = 1;
+const PROT_WRITE: c_int = 2;
+const PROT_EXEC: c_int = 4;
+const MAP_PRIVATE: c_int = 2;
+const MAP_ANONYMOUS: c_int = 0x20;
+const MAP_FAILED: *mut c_void = !0 as *mut c_void;
+
+extern "C" {
+    fn mmap(addr: *mut c_void, len: usize, prot: c_int, flags: c_int, fd: c_int, offset: c_long) -> *mut c_void;
+    fn mprotect(addr: *mut c_void, len: usize, prot: c_int) -> c_int;
+    fn munmap(addr: *mut c_void, len: usize) -> c_int;
+}
+
+/// Some anonymous memory: either zeroed, or read-only and executable, holding machine code.
+#[derive(Debug)]
+pub(super) struct Buffer {
+    ptr: NonNull<u8>,
+    len: usize,
+}
+
+impl Buffer {
+    /// Maps `len` bytes of zeroed memory, readable and writable.
+    ///
+    /// The pages are only allocated once accessed, unlike `calloc`, which may clear them all.
+    pub(super) fn zeroed(len: usize) -> io::Result<Buffer> {
+        let len = len.max(1);
+        
+        // SAFETY: a new anonymous mapping doesn't alias anything.
+        let ptr = unsafe { mmap(ptr::null_mut(), len, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS, -1, 0) };
+        if ptr == MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        
+        Ok(Buffer {
+            ptr: NonNull::new(ptr.cast()).expect("`mmap` doesn't return null"),
+            len,
+        })
+    }
+    
+    /// Maps some executable memory holding `code`.
+    pub(super) fn new(code: &[u8]) -> io::Result<Buffer> {
+        let buffer = Buffer::zeroed(code.len())?;
+        
+        // SAFETY: the mapping is writable and at least `code.len()` bytes long.
+        unsafe { ptr::copy_nonoverlapping(code.as_ptr(), buffer.ptr.as_ptr(), code.len()) };
+        
+        // W^X: the code isn't writable anymore once executable
+        // SAFETY: `ptr` and `len` describe our own mapping.
+        if unsafe { mprotect(buffer.ptr.as_ptr().cast(), buffer.len, PROT_READ | PROT_EXEC) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        
+        Ok(buffer)
+    }
+    
+    /// Returns the start of the mapping.
+    pub(super) const fn as_mut_ptr(&self) -> *mut u8 {
+        self.ptr.as_ptr()
+    }
+    
+    /// Returns the address of the code at `offset`.
+    pub(super) fn at(&self, offset: usize) -> *const u8 {
+        assert!(offset < self.len, "offset out of bounds");
+        
+        // SAFETY: in bounds of the mapping.
+        unsafe { self.ptr.as_ptr().add(offset) }
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        // SAFETY: `ptr` and `len` describe our own mapping, that nothing refers to anymore.
+        unsafe { munmap(self.ptr.as_ptr().cast(), self.len) };
+    }
+}
@@ -0,0 +1,619 @@
+//! Compiles RAM programs to native x86-64 code, see [`Jit`].
+
+use crate::jit::asm::{Asm, Cond, Label, Reg};
+use crate::jit::buffer::Buffer;
+use crate::model::{Instruction, Ir, Loc, Register, RoCode, RoLoc, Value};
+use crate::runner::{RunError, DEFAULT_MEMORY_LIMIT};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::io;
+use std::mem;
+
+#[cfg(feature = "indirect_jumps")]
+use crate::model::Address;
+
+mod asm;
+mod buffer;
+
+#[cfg(not(all(target_arch = "x86_64", target_os = "linux")))]
+compile_error!("the `jit` feature is only supported on x86-64 Linux");
+
+/// How the compiled code returns, in `rax`.
+mod status {
+    pub(super) const STOPPED: u32 = 0;
+    pub(super) const READ_EOF: u32 = 1;
+    pub(super) const READ_UNINIT: u32 = 2;
+    pub(super) const INVALID_ADDRESS: u32 = 3;
+    pub(super) const MEMORY_LIMIT: u32 = 4;
+    pub(super) const INTEGER_OVERFLOW: u32 = 5;
+    pub(super) const INEXISTENT_JUMP: u32 = 6;
+    #[cfg(feature = "indirect_jumps")]
+    pub(super) const INVALID_JUMP: u32 = 7;
+    pub(super) const EOF: u32 = 8;
+}
+
+/// What the compiled code gets in `rdi`, and stores its results into.
+#[repr(C)]
+struct Ctx<'a> {
+    /// The value read by `READ`, or the address or value that made the program fail.
+    value: i64,
+    /// The instruction that made the program fail.
+    ir: u64,
+    
+    read: &'a mut dyn FnMut() -> Option<i64>,
+    write: &'a mut dyn FnMut(i64),
+}
+
+/// The offset of [`Ctx::value`].
+const CTX_VALUE: i8 = 0;
+/// The offset of [`Ctx::ir`].
+const CTX_IR: i8 = 8;
+
+/// Called by `READ`, returning `0` if there's nothing left to read.
+extern "sysv64" fn read(ctx: &mut Ctx<'_>) -> u64 {
+    match (ctx.read)() {
+        Some(v) => {
+            ctx.value = v;
+            1
+        },
+        None => 0,
+    }
+}
+
+/// Called by `WRITE`.
+extern "sysv64" fn write(ctx: &mut Ctx<'_>, v: i64) {
+    (ctx.write)(v);
+}
+
+/// The signature of the compiled code: the context, the registers, and whether each register is initialized.
+type Entry = unsafe extern "sysv64" fn(ctx: *mut Ctx<'_>, memory: *mut i64, init: *mut u8) -> u64;
+
+/// How [`Jit::compile`] compiles the code.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Options {
+    /// Whether reading uninitialized registers fails, as with [`Ram`](crate::runner::Ram);
+    /// otherwise they read as `0`, which is faster.
+    pub uninit_checks: bool,
+    
+    /// The count of registers, accessing the others failing with [`RunError::MemoryLimit`], at most [`i32::MAX`].
+    /// Reading them fails with [`RunError::ReadUninit`] like with [`Ram`](crate::runner::Ram) instead, if [`Options::uninit_checks`].
+    ///
+    /// The memory is always dense, like [`MemoryLayout::Dense`](crate::runner::MemoryLayout::Dense).
+    pub memory_limit: usize,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            uninit_checks: true,
+            memory_limit: DEFAULT_MEMORY_LIMIT,
+        }
+    }
+}
+
+impl Options {
+    /// Returns the status of reading (if `read`) or writing a register past the memory limit.
+    const fn past_limit(self, read: bool) -> u32 {
+        if read && self.uninit_checks { status::READ_UNINIT } else { status::MEMORY_LIMIT }
+    }
+}
+
+/// A RAM program compiled to native x86-64 code.
+///
+/// The compiled code behaves like [`Ram`](crate::runner::Ram) with dense memory: arithmetic overflows,
+/// invalid addresses and jumps fail with the same [`RunError`]s, at the same instruction.
+///
+/// # Example
+///
+/// ```
+/// # use rame::inst;
+/// # use rame::jit::{Jit, Options};
+/// # use rame::model::RoCode;
+/// let code = RoCode::from([
+///     inst!(READ),
+///     inst!(MUL #3),
+///     inst!(WRITE),
+///     inst!(STOP),
+/// ]);
+///
+/// let jit = Jit::compile(&code, Options::default()).unwrap();
+/// let mut input = [14].into_iter();
+/// let mut output = Vec::new();
+/// jit.run(|| input.next(), |v| output.push(v)).unwrap();
+/// assert_eq!(output, [42]);
+/// ```
+#[derive(Debug)]
+pub struct Jit {
+    buffer: Buffer,
+    options: Options,
+}
+
+impl Jit {
+    /// Compiles some code.
+    ///
+    /// Fails if executable memory can't be allocated.
+    pub fn compile(code: &RoCode<i64>, mut options: Options) -> io::Result<Jit> {
+        options.memory_limit = options.memory_limit.clamp(1, i32::MAX as usize);
+        
+        let code = Compiler::new(code, options).compile();
+        Ok(Jit {
+            buffer: Buffer::new(&code)?,
+            options,
+        })
+    }
+    
+    /// Runs the compiled code, `READ` calling `read` and `WRITE` calling `write`.
+    ///
+    /// Like [`Ram::run`](crate::runner::Ram::run), never returns if the program never stops.
+    /// Panicking in `read` or `write` aborts.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the registers can't be mapped.
+    pub fn run(&self, mut read: impl FnMut() -> Option<i64>, mut write: impl FnMut(i64)) -> Result<(), JitError> {
+        let limit = self.options.memory_limit;
+        let memory = Buffer::zeroed(limit * size_of::<i64>()).expect("the registers should be allocated");
+        let init = Buffer::zeroed(if self.options.uninit_checks { limit } else { 0 }).expect("the registers should be allocated");
+        
+        let mut ctx = Ctx {
+            value: 0,
+            ir: 0,
+            read: &mut read,
+            write: &mut write,
+        };
+        
+        // SAFETY: the buffer holds code with this signature, at its start.
+        let entry = unsafe { mem::transmute::<*const u8, Entry>(self.buffer.at(0)) };
+        
+        // SAFETY: the compiled code only accesses the `limit` registers, and their `init` flags if enabled.
+        let status = unsafe { entry(&mut ctx, memory.as_mut_ptr().cast(), init.as_mut_ptr()) };
+        
+        let value = ctx.value;
+        let ir = Ir::new(usize::try_from(ctx.ir).expect("the instruction should exist"));
+        let adr = || usize::try_from(value).expect("the address should be valid");
+        
+        let err = match u32::try_from(status).expect("unknown status") {
+            status::STOPPED => return Ok(()),
+            status::READ_EOF => RunError::ReadEof,
+            status::READ_UNINIT => RunError::ReadUninit { adr: RoLoc::from(adr()) },
+            status::INVALID_ADDRESS => RunError::InvalidAddress { adr: value, err: usize::try_from(value).expect_err("the address should be negative") },
+            status::MEMORY_LIMIT => RunError::MemoryLimit { adr: adr(), limit },
+            status::INTEGER_OVERFLOW => RunError::IntegerOverfow,
+            status::INEXISTENT_JUMP => RunError::InexistentJump,
+            #[cfg(feature = "indirect_jumps")]
+            status::INVALID_JUMP => RunError::InvalidJump { err: usize::try_from(value).expect_err("the address should be negative") },
+            status::EOF => RunError::Eof,
+            status => unreachable!("unknown status {status}"),
+        };
+        
+        Err(JitError { ir, err })
+    }
+}
+
+/// The error type returned by [`Jit::run`].
+#[derive(Debug)]
+pub struct JitError {
+    /// The instruction that failed.
+    pub ir: Ir,
+    pub err: RunError<i64>,
+}
+
+impl Display for JitError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt(&self.err, f)
+    }
+}
+
+impl Error for JitError {}
+
+/// Some out-of-line code making the program fail.
+struct Stub {
+    label: Label,
+    ir: Ir,
+    status: u32,
+    /// The register holding [`Ctx::value`], if any.
+    value: Option<Reg>,
+}
+
+/// Compiles some code.
+///
+/// The registers of the RAM are at `rbx`, their `init` flags at `r12`, and the [`Ctx`] at `r13`.
+/// The RAM registers are addressed by `rcx`.
+struct Compiler<'a> {
+    asm: Asm,
+    code: &'a RoCode<i64>,
+    options: Options,
+    
+    /// The label of each instruction.
+    insts: Vec<Label>,
+    stubs: Vec<Stub>,
+    
+    /// Returns from the compiled code.
+    exit: Label,
+    /// The offsets of the instructions, for indirect jumps.
+    table: Label,
+}
+
+impl<'a> Compiler<'a> {
+    fn new(code: &'a RoCode<i64>, options: Options) -> Compiler<'a> {
+        let mut asm = Asm::default();
+        let insts = code.iter().map(|_| asm.label()).collect();
+        let exit = asm.label();
+        let table = asm.label();
+        
+        Compiler {
+            asm,
+            code,
+            options,
+            insts,
+            stubs: Vec::new(),
+            exit,
+            table,
+        }
+    }
+    
+    fn compile(mut self) -> Vec<u8> {
+        // Prologue, keeping the stack aligned on 16 bytes for the calls
+        self.asm.push(Reg::Rbx);
+        self.asm.push(Reg::R12);
+        self.asm.push(Reg::R13);
+        self.asm.mov(Reg::R13, Reg::Rdi);
+        self.asm.mov(Reg::Rbx, Reg::Rsi);
+        self.asm.mov(Reg::R12, Reg::Rdx);
+        
+        for (ir, inst) in self.code.enumerate() {
+            self.asm.bind(self.insts[ir.inner()]);
+            self.inst(ir, inst);
+        }
+        
+        // Running past the last instruction
+        let last = Ir::new(self.code.len().saturating_sub(1));
+        let eof = self.fail(last, status::EOF, None);
+        self.asm.jmp(eof);
+        
+        // Failing
+        let fail = self.asm.label();
+        for stub in mem::take(&mut self.stubs) {
+            self.asm.bind(stub.label);
+            if let Some(reg) = stub.value {
+                self.asm.mov(Reg::Rcx, reg);
+            }
+            self.asm.mov_imm(Reg::Rdx, i64::try_from(stub.ir.inner()).expect("code too large"));
+            self.asm.mov_imm(Reg::Rax, i64::from(stub.status));
+            self.asm.jmp(fail);
+        }
+        
+        self.asm.bind(fail);
+        self.asm.store_disp8(Reg::R13, CTX_VALUE, Reg::Rcx);
+        self.asm.store_disp8(Reg::R13, CTX_IR, Reg::Rdx);
+        
+        // Epilogue
+        self.asm.bind(self.exit);
+        self.asm.pop(Reg::R13);
+        self.asm.pop(Reg::R12);
+        self.asm.pop(Reg::Rbx);
+        self.asm.ret();
+        
+        self.asm.align(4);
+        self.asm.bind(self.table);
+        #[cfg(feature = "indirect_jumps")]
+        for &inst in &self.insts {
+            self.asm.table_entry(inst, self.table);
+        }
+        
+        self.asm.finish()
+    }
+    
+    /// Returns the label of some code making the program fail.
+    fn fail(&mut self, ir: Ir, status: u32, value: Option<Reg>) -> Label {
+        let label = self.asm.label();
+        self.stubs.push(Stub { label, ir, status, value });
+        label
+    }
+    
+    /// Puts a register's address in `rcx`, failing if past the memory limit, to be read if `read`.
+    fn direct(&mut self, ir: Ir, adr: usize, read: bool) {
+        #[expect(clippy::cast_possible_wrap, reason = "only displayed")]
+        self.asm.mov_imm(Reg::Rcx, adr as i64);
+        
+        if adr >= self.options.memory_limit {
+            let fail = self.fail(ir, self.options.past_limit(read), Some(Reg::Rcx));
+            self.asm.jmp(fail);
+        }
+    }
+    
+    /// Puts a register's address in `rcx`, clobbering `rdx`.
+    fn address<L: Loc>(&mut self, ir: Ir, reg: Register<L>) {
+        match reg {
+            Register::Direct(adr) => self.direct(ir, adr.raw(), L::READ),
+            Register::Indirect(ptr) => {
+                self.direct(ir, ptr.raw(), true);
+                self.check_init(ir);
+                self.asm.load(Reg::Rdx, Reg::Rbx, Reg::Rcx);
+                
+                self.asm.test(Reg::Rdx, Reg::Rdx);
+                let invalid = self.fail(ir, status::INVALID_ADDRESS, Some(Reg::Rdx));
+                self.asm.jcc(Cond::Sign, invalid);
+                
+                self.asm.cmp_imm(Reg::Rdx, i32::try_from(self.options.memory_limit).expect("the limit is clamped"));
+                let limit = self.fail(ir, self.options.past_limit(L::READ), Some(Reg::Rdx));
+                self.asm.jcc(Cond::AboveEq, limit);
+                
+                self.asm.mov(Reg::Rcx, Reg::Rdx);
+            },
+        }
+    }
+    
+    /// Fails if the register addressed by `rcx` is uninitialized, if enabled.
+    fn check_init(&mut self, ir: Ir) {
+        if self.options.uninit_checks {
+            self.asm.cmp_byte_zero(Reg::R12, Reg::Rcx);
+            let uninit = self.fail(ir, status::READ_UNINIT, Some(Reg::Rcx));
+            self.asm.jcc(Cond::Eq, uninit);
+        }
+    }
+    
+    /// Writes `src` into the register addressed by `rcx`.
+    fn store(&mut self, src: Reg) {
+        self.asm.store(Reg::Rbx, Reg::Rcx, src);
+        if self.options.uninit_checks {
+            self.asm.store_byte(Reg::R12, Reg::Rcx, 1);
+        }
+    }
+    
+    /// Reads ACC into `dst`.
+    fn load_acc(&mut self, ir: Ir, dst: Reg) {
+        self.asm.mov_imm(Reg::Rcx, 0);
+        self.check_init(ir);
+        self.asm.load(dst, Reg::Rbx, Reg::Rcx);
+    }
+    
+    /// Writes `src` into ACC.
+    fn store_acc(&mut self, src: Reg) {
+        self.asm.mov_imm(Reg::Rcx, 0);
+        self.store(src);
+    }
+    
+    /// Reads a value into `dst`, clobbering `rcx` and `rdx`.
+    fn value(&mut self, ir: Ir, v: Value<i64>, dst: Reg) {
+        match v {
+            Value::Constant(v) => self.asm.mov_imm(dst, v),
+            Value::Register(reg) => {
+                self.address(ir, reg);
+                self.check_init(ir);
+                self.asm.load(dst, Reg::Rbx, Reg::Rcx);
+            },
+        }
+    }
+    
+    /// Jumps to a constant address.
+    fn jump(&mut self, ir: Ir, cond: Option<Cond>, adr: Ir) {
+        let target = match self.insts.get(adr.inner()) {
+            Some(&target) => target,
+            None => self.fail(ir, status::INEXISTENT_JUMP, None),
+        };
+        
+        match cond {
+            Some(cond) => self.asm.jcc(cond, target),
+            None => self.asm.jmp(target),
+        }
+    }
+    
+    /// Jumps to the address in a register.
+    #[cfg(feature = "indirect_jumps")]
+    fn jump_indirect(&mut self, ir: Ir, cond: Option<Cond>, ptr: RoLoc) {
+        let skip = self.asm.label();
+        if let Some(cond) = cond {
+            self.asm.jcc(cond.not(), skip);
+        }
+        
+        self.direct(ir, ptr.raw(), true);
+        self.check_init(ir);
+        self.asm.load(Reg::Rax, Reg::Rbx, Reg::Rcx);
+        
+        self.asm.test(Reg::Rax, Reg::Rax);
+        let invalid = self.fail(ir, status::INVALID_JUMP, Some(Reg::Rax));
+        self.asm.jcc(Cond::Sign, invalid);
+        
+        self.asm.cmp_imm(Reg::Rax, i32::try_from(self.code.len()).expect("code too large"));
+        let inexistent = self.fail(ir, status::INEXISTENT_JUMP, None);
+        self.asm.jcc(Cond::AboveEq, inexistent);
+        
+        // The table holds the offsets of the instructions from itself
+        self.asm.lea(Reg::Rcx, self.table);
+        self.asm.load_i32(Reg::Rax, Reg::Rcx, Reg::Rax);
+        self.asm.add(Reg::Rax, Reg::Rcx);
+        self.asm.jmp_reg(Reg::Rax);
+        
+        self.asm.bind(skip);
+    }
+    
+    /// Jumps to an address, constant or not.
+    fn jump_to(&mut self, ir: Ir, cond: Option<Cond>, inst: Instruction<i64>) {
+        #[cfg(feature = "indirect_jumps")]
+        if let Some(Address::Register(ptr)) = inst.jump() {
+            return self.jump_indirect(ir, cond, ptr);
+        }
+        
+        let adr = inst.target().expect("a jump has an address");
+        self.jump(ir, cond, adr);
+    }
+    
+    fn inst(&mut self, ir: Ir, inst: Instruction<i64>) {
+        match inst {
+            Instruction::Read => {
+                self.asm.mov(Reg::Rdi, Reg::R13);
+                #[expect(clippy::fn_to_numeric_cast_any, reason = "called through `rax`")]
+                self.asm.mov_imm(Reg::Rax, read as *const () as i64);
+                self.asm.call(Reg::Rax);
+                
+                self.asm.test(Reg::Rax, Reg::Rax);
+                let eof = self.fail(ir, status::READ_EOF, None);
+                self.asm.jcc(Cond::Eq, eof);
+                
+                self.asm.load_disp8(Reg::Rax, Reg::R13, CTX_VALUE);
+                self.store_acc(Reg::Rax);
+            },
+            Instruction::Write => {
+                self.load_acc(ir, Reg::Rsi);
+                self.asm.mov(Reg::Rdi, Reg::R13);
+                #[expect(clippy::fn_to_numeric_cast_any, reason = "called through `rax`")]
+                self.asm.mov_imm(Reg::Rax, write as *const () as i64);
+                self.asm.call(Reg::Rax);
+            },
+            Instruction::Load(v) => {
+                self.value(ir, v, Reg::Rax);
+                self.store_acc(Reg::Rax);
+            },
+            Instruction::Store(reg) => {
+                self.load_acc(ir, Reg::Rax);
+                self.address(ir, reg);
+                self.store(Reg::Rax);
+            },
+            Instruction::Increment(reg) | Instruction::Decrement(reg) => {
+                self.address(ir, reg);
+                self.check_init(ir);
+                self.asm.load(Reg::Rax, Reg::Rbx, Reg::Rcx);
+                self.asm.add_imm8(Reg::Rax, if matches!(inst, Instruction::Increment(_)) { 1 } else { -1 });
+                
+                let overflow = self.fail(ir, status::INTEGER_OVERFLOW, None);
+                self.asm.jcc(Cond::Overflow, overflow);
+                self.store(Reg::Rax);
+            },
+            Instruction::Add(v) | Instruction::Sub(v) | Instruction::Mul(v) => {
+                self.load_acc(ir, Reg::Rax);
+                self.value(ir, v, Reg::R8);
+                
+                match inst {
+                    Instruction::Add(_) => self.asm.add(Reg::Rax, Reg::R8),
+                    Instruction::Sub(_) => self.asm.sub(Reg::Rax, Reg::R8),
+                    _ => self.asm.imul(Reg::Rax, Reg::R8),
+                }
+                
+                let overflow = self.fail(ir, status::INTEGER_OVERFLOW, None);
+                self.asm.jcc(Cond::Overflow, overflow);
+                self.store_acc(Reg::Rax);
+            },
+            Instruction::Div(v) | Instruction::Mod(v) => {
+                self.load_acc(ir, Reg::Rax);
+                self.value(ir, v, Reg::R8);
+                
+                // Both `x / 0` and `i64::MIN / -1` overflow
+                let overflow = self.fail(ir, status::INTEGER_OVERFLOW, None);
+                self.asm.test(Reg::R8, Reg::R8);
+                self.asm.jcc(Cond::Eq, overflow);
+                
+                let divide = self.asm.label();
+                self.asm.cmp_imm(Reg::R8, -1);
+                self.asm.jcc(Cond::NotEq, divide);
+                self.asm.mov_imm(Reg::R9, i64::MIN);
+                self.asm.cmp(Reg::Rax, Reg::R9);
+                self.asm.jcc(Cond::Eq, overflow);
+                
+                self.asm.bind(divide);
+                self.asm.cqo();
+                self.asm.idiv(Reg::R8);
+                if matches!(inst, Instruction::Mod(_)) {
+                    self.asm.mov(Reg::Rax, Reg::Rdx);
+                }
+                
+                self.store_acc(Reg::Rax);
+            },
+            Instruction::Jump(_) => self.jump_to(ir, None, inst),
+            Instruction::JumpZero(_) | Instruction::JumpLtz(_) | Instruction::JumpGtz(_) => {
+                self.load_acc(ir, Reg::Rax);
+                self.asm.test(Reg::Rax, Reg::Rax);
+                
+                let cond = match inst {
+                    Instruction::JumpZero(_) => Cond::Eq,
+                    Instruction::JumpLtz(_) => Cond::Less,
+                    _ => Cond::Greater,
+                };
+                self.jump_to(ir, Some(cond), inst);
+            },
+            Instruction::Stop => {
+                self.asm.mov_imm(Reg::Rax, i64::from(status::STOPPED));
+                self.asm.jmp(self.exit);
+            },
+            Instruction::Nop => {},
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fuzz::{Generator, Rng};
+    use crate::inst;
+    use crate::jit::{Jit, Options};
+    use crate::model::{Instruction, RoCode, Value};
+    use crate::runner::{Ram, RunError};
+    use std::str::FromStr;
+    
+    /// Returns the output and the error of `code` run by [`Ram`], then by [`Jit`].
+    fn outcomes(code: &RoCode<i64>, input: &[i64]) -> [(Vec<i64>, Option<RunError<i64>>); 2] {
+        let mut ram = Ram::new(code.clone(), input.iter().copied());
+        let err = ram.run_for(10_000_000).map(|stopped| assert!(stopped, "infinite loop")).err();
+        let ram = (Vec::from(ram), err);
+        
+        let mut input = input.iter().copied();
+        let mut output = Vec::new();
+        let err = Jit::compile(code, Options::default()).unwrap().run(|| input.next(), |v| output.push(v)).err();
+        let jit = (output, err.map(|e| e.err));
+        
+        [ram, jit]
+    }
+    
+    #[test]
+    fn fibo() {
+        let code = RoCode::from_str(include_str!("../../benches/fibo.ram")).unwrap();
+        for n in 0..20 {
+            let [ram, jit] = outcomes(&code, &[n]);
+            assert_eq!(ram, jit, "fibo({n})");
+        }
+    }
+    
+    #[test]
+    fn errors() {
+        let codes = [
+            RoCode::from([inst!(READ), inst!(READ)]),
+            RoCode::from([inst!(LOAD #1), inst!(STORE 2), inst!(LOAD @1)]),
+            RoCode::from([inst!(LOAD #-3), inst!(STORE 1), inst!(LOAD @1)]),
+            RoCode::from([inst!(LOAD #1), inst!(STORE 1), inst!(INC 1), inst!(LOAD #5_000_000), inst!(STORE @1)]),
+            RoCode::from([inst!(LOAD 5_000_000)]),
+            RoCode::from([inst!(LOAD #5_000_000), inst!(STORE 1), inst!(LOAD @1)]),
+            RoCode::from([Instruction::Load(Value::Constant(i64::MAX)), inst!(STORE 1), inst!(INC 1)]),
+            RoCode::from([Instruction::Load(Value::Constant(i64::MIN)), inst!(DIV #-1)]),
+            RoCode::from([Instruction::Load(Value::Constant(i64::MIN)), inst!(MOD #-1)]),
+            RoCode::from([inst!(LOAD #7), inst!(MOD #0)]),
+            RoCode::from([inst!(LOAD #1), inst!(JUMG 9)]),
+            RoCode::from([inst!(LOAD #-7), inst!(DIV #2), inst!(WRITE), inst!(LOAD #-7), inst!(MOD #2), inst!(WRITE)]),
+        ];
+        
+        for code in codes {
+            let [ram, jit] = outcomes(&code, &[4]);
+            assert_eq!(ram, jit, "{code}");
+        }
+    }
+    
+    #[test]
+    fn fuzz() {
+        for seed in 0..300 {
+            let mut rng = Rng::new(seed);
+            let code = Generator::default().generate::<i64>(&mut rng);
+            let input = (0..4).map(|_| rng.small(100)).collect::<Vec<i64>>();
+            
+            let [ram, jit] = outcomes(&code, &input);
+            assert_eq!(ram, jit, "seed {seed}:\n{code}");
+        }
+    }
+    
+    #[test]
+    fn uninit_unchecked() {
+        let code = RoCode::from([inst!(LOAD 5), inst!(ADD #1), inst!(WRITE), inst!(STOP)]);
+        let jit = Jit::compile(&code, Options { uninit_checks: false, ..Options::default() }).unwrap();
+        
+        let mut output = Vec::new();
+        jit.run(|| None, |v| output.push(v)).unwrap();
+        assert_eq!(output, [1]);
+    }
+}
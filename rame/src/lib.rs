@@ -1,9 +1,14 @@
-#[cfg(all(feature = "optimizer", feature = "indirect_jumps"))]
-compile_error!("the `optimizer` feature is not compatible with the `indirect_jumps` feature");
-
-pub(crate) mod error;
+pub mod analysis;
+pub mod diagnostic;
+pub mod emit;
+// Not part of the API, shared by the tests and `optimizer::Validator`
+#[doc(hidden)]
+pub mod fuzz;
 pub mod model;
 pub mod runner;
 
+#[cfg(feature = "jit")]
+pub mod jit;
+
 #[cfg(feature = "optimizer")]
 pub mod optimizer;
@@ -0,0 +1,256 @@
+//! Resolves the labels, register aliases and constants of RAM source.
+
+use crate::model::{Instruction, Integer, Ir, ParseCodeError, ParseInstructionError, ParseSymbolError};
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+/// A name defined by the source.
+#[derive(Copy, Clone, Debug)]
+enum Symbol<T: Integer> {
+    /// `name:`, the IR of the next instruction.
+    Label(Ir),
+    /// `.reg name = n`
+    Register(usize),
+    /// `.const name = v`
+    Constant(T),
+}
+
+impl<T: Integer> Symbol<T> {
+    const fn kind(&self) -> &'static str {
+        match self {
+            Symbol::Label(_) => "label",
+            Symbol::Register(_) => "register",
+            Symbol::Constant(_) => "constant",
+        }
+    }
+}
+
+/// Some instructions, the notes of some of them, and the line of each of them.
+type Assembled<T> = (Vec<Instruction<T>>, Vec<(Ir, String)>, Vec<(Ir, RangeInclusive<usize>)>);
+
+/// Returns `true` iff `s` is an identifier.
+fn is_name(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_') && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Assembles some lines of RAM source.
+///
+/// Names may be used before being defined:
+/// - `name` and `#name` are the IR of a label, e.g. `JUMP name` or `LOAD #name`;
+/// - `name` and `@name` are the register of a `.reg`, e.g. `STORE name`;
+/// - `#name` is the value of a `.const`, e.g. `ADD #name`.
+///
+/// Returns the instructions, the `; comments` ending their lines, and their lines starting from 1,
+/// or the errors of all the invalid lines, in order.
+/// The uses of a name whose definition is invalid aren't reported,
+/// and jumps past the end of the program are only reported if `check_jumps`.
+pub(super) fn assemble<T: Integer, I: IntoIterator<Item = io::Result<String>>>(lines: I, check_jumps: bool) -> Result<Assembled<T>, Vec<ParseCodeError<T>>> {
+    let mut symbols = HashMap::<String, (Symbol<T>, usize)>::new();
+    let mut define = |name: &str, symbol: Symbol<T>, i: usize| match symbols.insert(name.to_owned(), (symbol, i)) {
+        Some((_, j)) => Err(ParseSymbolError::Redefined(name.to_owned(), j + 1)),
+        None if !is_name(name) => Err(ParseSymbolError::InvalidName(name.to_owned())),
+        None => Ok(()),
+    };
+    
+    let mut errors = Vec::new();
+    
+    // Names whose definition is invalid
+    let mut broken = HashSet::<String>::new();
+    
+    // Line index, line, instruction, note
+    let mut insts = Vec::<(usize, String, String, String)>::new();
+    
+    for (i, l) in lines.into_iter().enumerate() {
+        let l = match l {
+            Ok(l) => l,
+            Err(e) => return Err(vec![e.into()]),
+        };
+        
+        // Remove `; comments` and spaces
+        let (mut stripped, note) = match l.split_once(';') {
+            Some((code, note)) => (code.trim_ascii(), note.trim_ascii()),
+            None => (l.trim_ascii(), ""),
+        };
+        
+        // `name:`, maybe followed by an instruction
+        while let Some((name, rest)) = stripped.split_once(':') {
+            if let Err(e) = define(name.trim_ascii(), Symbol::Label(Ir::new(insts.len())), i) {
+                errors.push(ParseCodeError::Symbol(i, l.clone(), e));
+            }
+            
+            stripped = rest.trim_ascii();
+        }
+        
+        if let Some(directive) = stripped.strip_prefix('.') {
+            let Some((directive, (name, value))) = directive.split_once(' ').and_then(|(directive, rest)| Some((directive, rest.split_once('=')?))) else {
+                errors.push(ParseCodeError::Symbol(i, l, ParseSymbolError::InvalidDirective));
+                continue;
+            };
+            
+            let (name, value) = (name.trim_ascii(), value.trim_ascii());
+            let symbol = match directive {
+                "reg" => usize::from_str(value).map(Symbol::Register).map_err(ParseInstructionError::InvalidRegister),
+                "const" => T::from_str(value).map(Symbol::Constant).map_err(ParseInstructionError::InvalidValue),
+                _ => {
+                    broken.insert(name.to_owned());
+                    let e = ParseSymbolError::UnknownDirective(directive.to_owned());
+                    errors.push(ParseCodeError::Symbol(i, l, e));
+                    continue;
+                },
+            };
+            
+            match symbol {
+                Ok(symbol) => if let Err(e) = define(name, symbol, i) {
+                    errors.push(ParseCodeError::Symbol(i, l, e));
+                },
+                Err(e) => {
+                    broken.insert(name.to_owned());
+                    errors.push(ParseCodeError::Inst(i, l, e));
+                },
+            }
+        }
+        else if !stripped.is_empty() {
+            let (inst, note) = (stripped.to_owned(), note.to_owned());
+            insts.push((i, l, inst, note));
+        }
+    }
+    
+    // Replaces the names by their values
+    let resolve = |param: &str| -> Result<String, ParseSymbolError> {
+        let (prefix, name) = if param.starts_with(['#', '@']) { param.split_at(1) } else { ("", param) };
+        if !is_name(name) {
+            return Ok(param.to_owned());
+        }
+        
+        match (prefix, symbols.get(name)) {
+            (_, None) => Err(ParseSymbolError::Undefined(name.to_owned())),
+            ("" | "#", Some((Symbol::Label(ir), _))) => Ok(format!("{prefix}{ir}")),
+            ("" | "@", Some((Symbol::Register(n), _))) => Ok(format!("{prefix}{n}")),
+            ("#", Some((Symbol::Constant(v), _))) => Ok(format!("#{v}")),
+            (_, Some((symbol, _))) => Err(ParseSymbolError::Misused(name.to_owned(), symbol.kind())),
+        }
+    };
+    
+    let len = insts.len();
+    let mut assembled = (Vec::with_capacity(len), Vec::with_capacity(len), Vec::with_capacity(len));
+    
+    for (ir, (i, l, inst, note)) in insts.into_iter().enumerate() {
+        let inst = match inst.split_once(' ') {
+            Some((op, param)) => match resolve(param.trim_ascii()) {
+                Ok(param) => format!("{op} {param}"),
+                Err(ParseSymbolError::Undefined(name)) if broken.contains(&name) => continue,
+                Err(e) => {
+                    errors.push(ParseCodeError::Symbol(i, l, e));
+                    continue;
+                },
+            },
+            None => inst,
+        };
+        
+        match Instruction::from_str(&inst) {
+            Ok(inst) if check_jumps && inst.target().is_some_and(|target| target.inner() >= len) => {
+                errors.push(ParseCodeError::Inst(i, l, ParseInstructionError::InexistentJump(len)));
+            },
+            Ok(inst) => {
+                assembled.0.push(inst);
+                assembled.1.push((Ir::new(ir), note));
+                assembled.2.push((Ir::new(ir), i + 1..=i + 1));
+            },
+            Err(e) => errors.push(ParseCodeError::Inst(i, l, e)),
+        }
+    }
+    
+    if errors.is_empty() {
+        Ok(assembled)
+    }
+    else {
+        errors.sort_by_key(ParseCodeError::line);
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::inst;
+    use crate::model::{Ir, ParseCodeError, ParseInstructionError, ParseSymbolError, RoCode};
+    use std::str::FromStr;
+    
+    #[test]
+    fn labels() {
+        let code = RoCode::<i32>::from_str("
+            .const N = 10
+            .reg i = 1
+            
+                LOAD #N
+                STORE i
+            loop: LOAD i    ; i > 0
+                JUMZ end
+                WRITE
+                DEC i
+                JUMP loop
+            end:
+            done: STOP
+        ").unwrap();
+        
+        assert_eq!(code, RoCode::from([
+            inst!(LOAD #10),
+            inst!(STORE 1),
+            inst!(LOAD 1),
+            inst!(JUMZ 7),
+            inst!(WRITE),
+            inst!(DEC 1),
+            inst!(JUMP 2),
+            inst!(STOP),
+        ]));
+        assert_eq!(code.note(Ir::new(2)), Some("i > 0"));
+        assert_eq!(code.note(Ir::new(3)), None);
+        
+        let code = RoCode::<i32>::from_str(".reg p = 2\nLOAD #end\nSTORE @p\nend: STOP").unwrap();
+        assert_eq!(code, RoCode::from([inst!(LOAD #2), inst!(STORE @2), inst!(STOP)]));
+    }
+    
+    #[test]
+    fn symbol_errors() {
+        let err = |src| match RoCode::<i32>::from_str(src) {
+            Err(ParseCodeError::Symbol(i, _, e)) => (i + 1, e),
+            r => panic!("{r:?}"),
+        };
+        
+        assert_eq!(err("READ\nJUMP end"), (2, ParseSymbolError::Undefined("end".to_owned())));
+        assert_eq!(err("a: READ\na: STOP"), (2, ParseSymbolError::Redefined("a".to_owned(), 1)));
+        assert_eq!(err(".const N = 1\nLOAD N"), (2, ParseSymbolError::Misused("N".to_owned(), "constant")));
+        assert_eq!(err("1a: STOP"), (1, ParseSymbolError::InvalidName("1a".to_owned())));
+        assert_eq!(err(".regs i = 1"), (1, ParseSymbolError::UnknownDirective("regs".to_owned())));
+        assert_eq!(err(".reg i"), (1, ParseSymbolError::InvalidDirective));
+        assert_eq!(RoCode::<i32>::from_str("STOP\n.const N = x").unwrap_err().to_string(), "2: \".const N = x\": invalid value (i32): invalid digit found in string");
+    }
+    
+    #[test]
+    fn all_errors() {
+        let errors = RoCode::<i32>::parse_all("
+            .const N = x
+            .regs i = 1
+                LOAD #N
+                STORE i
+            a:  JMPZ 1
+            a:  JUMP 7
+                ADD #1
+                STOP
+        ".as_bytes()).unwrap_err();
+        
+        let errors = errors.iter().map(|e| (e.line().unwrap(), e.to_string())).collect::<Vec<_>>();
+        assert_eq!(errors, [
+            (2, "2: \"            .const N = x\": invalid value (i32): invalid digit found in string".to_owned()),
+            (3, "3: \"            .regs i = 1\": unknown directive `.regs`".to_owned()),
+            (6, "6: \"            a:  JMPZ 1\": unknown instruction".to_owned()),
+            (7, "7: \"            a:  JUMP 7\": `a` is already defined at line 6".to_owned()),
+            (7, "7: \"            a:  JUMP 7\": jumping to an inexistent location: `<address>` must be less than 6".to_owned()),
+        ]);
+        
+        assert_eq!(RoCode::<i32>::from_str("JUMP 2\nSTOP").unwrap().len(), 2);
+        assert!(matches!(RoCode::<i32>::parse_all(&b"JUMP 2\nSTOP"[..]).unwrap_err()[..], [ParseCodeError::Inst(0, _, ParseInstructionError::InexistentJump(2))]));
+    }
+}
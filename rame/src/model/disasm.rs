@@ -0,0 +1,123 @@
+use crate::model::{Instruction, Integer, Ir, RoCode};
+use std::fmt::{self, Display, Formatter};
+
+/// Displays some code with labels, see [`RoCode::disassemble`].
+#[derive(Copy, Clone, Debug)]
+#[must_use]
+pub struct Disassembly<'c, T: Integer> {
+    code: &'c RoCode<T>,
+    
+    /// Annotate each instruction with its IR, after its note.
+    irs: bool,
+    
+    /// Annotate each instruction with the index of its basic block.
+    blocks: bool,
+}
+
+impl<'c, T: Integer> Disassembly<'c, T> {
+    pub(super) const fn new(code: &'c RoCode<T>) -> Disassembly<'c, T> {
+        Disassembly {
+            code,
+            irs: false,
+            blocks: false,
+        }
+    }
+    
+    /// Annotates each instruction with its [`Ir`].
+    pub const fn irs(&mut self, irs: bool) -> &mut Self {
+        self.irs = irs;
+        self
+    }
+    
+    /// Annotates each instruction with the index of its basic block.
+    pub const fn blocks(&mut self, blocks: bool) -> &mut Self {
+        self.blocks = blocks;
+        self
+    }
+}
+
+impl<T: Integer> Display for Disassembly<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let len = self.code.len();
+        
+        // The jumped-to IRs, `L0` being the first one
+        let mut labels = self.code.iter().filter_map(Instruction::target).filter(|adr| *adr <= len).collect::<Vec<_>>();
+        labels.sort_unstable();
+        labels.dedup();
+        let label = |adr: Ir| labels.binary_search(&adr).ok();
+        
+        let insts = self.code.iter().map(|inst| inst.target().and_then(label).map_or_else(|| inst.to_string(), |l| {
+            let inst = inst.to_string();
+            let (op, _) = inst.split_once(' ').expect("jumps have an address");
+            format!("{op} L{l}")
+        })).collect::<Vec<_>>();
+        
+        let width = insts.iter().map(String::len).max().unwrap_or_default();
+        let mut block = 0;
+        
+        for (ir, text) in insts.iter().enumerate().map(|(ir, text)| (Ir::new(ir), text)) {
+            let leader = ir == 0 || label(ir).is_some() || self.code.get(Ir::new(ir.inner() - 1)).is_some_and(|prev| prev.jump().is_some() || prev == Instruction::Stop);
+            if leader && ir != 0 {
+                block += 1;
+                writeln!(f)?;
+            }
+            
+            if let Some(l) = label(ir) {
+                writeln!(f, "L{l}:")?;
+            }
+            
+            let mut notes = self.code.note(ir).map(str::to_owned).into_iter().collect::<Vec<_>>();
+            if self.irs {
+                notes.push(format!("ir {ir}"));
+            }
+            if self.blocks {
+                notes.push(format!("block {block}"));
+            }
+            
+            if notes.is_empty() {
+                write!(f, "    {text}")?;
+            }
+            else {
+                write!(f, "    {text:width$}  ; {}", notes.join(", "))?;
+            }
+            
+            if ir + 1 < len {
+                writeln!(f)?;
+            }
+        }
+        
+        // Jumping right after the last instruction
+        if let Some(l) = label(Ir::new(len)) {
+            write!(f, "\n\nL{l}:")?;
+        }
+        
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::inst;
+    use crate::model::RoCode;
+    use std::str::FromStr;
+    
+    #[test]
+    fn disassemble() {
+        let code = RoCode::<i32>::from([
+            inst!(READ),
+            inst!(JUMZ 5),
+            inst!(SUB #1),
+            inst!(WRITE),
+            inst!(JUMP 1),
+            inst!(STOP),
+            inst!(JUMP 7),
+        ]);
+        
+        assert_eq!(code.disassemble().to_string(), "    READ\n\nL0:\n    JUMZ L1\n\n    SUB #1\n    WRITE\n    JUMP L0\n\nL1:\n    STOP\n\n    JUMP L2\n\nL2:");
+        assert_eq!(RoCode::from_str(&code.disassemble().to_string()).unwrap(), code);
+        
+        let annotated = code.disassemble().irs(true).blocks(true).to_string();
+        assert_eq!(annotated.lines().nth(3), Some("    JUMZ L1  ; ir 1, block 1"));
+        assert_eq!(RoCode::from_str(&annotated).unwrap(), code);
+    }
+}
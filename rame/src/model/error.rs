@@ -1,15 +1,21 @@
+use crate::diagnostic::Diagnostic;
+use crate::model::inst::closest_opcode;
 use crate::model::{Integer, Ir};
 use std::any::type_name;
 use std::{fmt, io};
 use std::error::Error;
 use std::fmt::{Display, Formatter};
+use std::path::Path;
 use std::str::FromStr;
 
 /// The error type returned by [`RoCode::parse`](crate::model::RoCode::parse).
+///
+/// The line indexes start from 0.
 #[derive(Debug)]
 pub enum ParseCodeError<T: Integer> {
     Io(io::Error),
     Inst(usize, String, ParseInstructionError<T>),
+    Symbol(usize, String, ParseSymbolError),
     NoInst,
 }
 
@@ -18,16 +24,101 @@ impl<T: Integer> Display for ParseCodeError<T> {
         match self {
             ParseCodeError::Io(e) => Display::fmt(e, f),
             ParseCodeError::Inst(i, l, e) => write!(f, "{}: {l:?}: {e}", i + 1),
+            ParseCodeError::Symbol(i, l, e) => write!(f, "{}: {l:?}: {e}", i + 1),
             ParseCodeError::NoInst => f.write_str("empty file"),
         }
     }
 }
 
+/// Returns the offset and the text of the instruction or directive of a line, without labels nor `; comments`.
+fn statement(l: &str) -> (usize, &str) {
+    let code = l.split(';').next().unwrap_or_default();
+    let start = code.rfind(':').map_or(0, |i| i + 1);
+    let start = start + (code.len() - start - code[start..].trim_start().len());
+    
+    (start, code[start..].trim_end())
+}
+
+impl<T: Integer> ParseCodeError<T> {
+    /// Returns the line of `self`, starting from 1, if any.
+    #[must_use]
+    pub const fn line(&self) -> Option<usize> {
+        match self {
+            ParseCodeError::Inst(i, _, _) | ParseCodeError::Symbol(i, _, _) => Some(*i + 1),
+            ParseCodeError::Io(_) | ParseCodeError::NoInst => None,
+        }
+    }
+    
+    /// Reports `self`, showing the faulty line with the faulty part underlined.
+    ///
+    /// `path` is the parsed file, if known.
+    pub fn diagnostic(&self, path: Option<&Path>) -> Diagnostic {
+        let path = path.map(|path| path.display().to_string());
+        let path = path.as_deref();
+        
+        // The line without indentation, the underlined part of its instruction or directive, and that instruction or directive
+        let line = |i: usize, l: &str, part: Option<&str>| {
+            let text = l.trim_start();
+            let (start, inst) = statement(text);
+            let mark = part.and_then(|part| inst.find(part).map(|at| start + at..start + at + part.len())).unwrap_or(start..start + inst.len());
+            
+            (format!("{}:{}", path.unwrap_or("anon"), i + 1), text.to_owned(), mark, inst.to_owned())
+        };
+        
+        match self {
+            ParseCodeError::Io(e) => Diagnostic::error(e).at(path.unwrap_or("anon")).kind("Io"),
+            ParseCodeError::Inst(i, l, e) => {
+                let (_, inst) = statement(l);
+                let (opcode, operand) = inst.split_once(' ').map_or((inst, None), |(opcode, operand)| (opcode, Some(operand.trim_start())));
+                
+                // The value of a directive
+                let operand = if opcode.starts_with('.') { operand.and_then(|operand| operand.split_once('=')).map(|(_, value)| value.trim()) } else { operand };
+                
+                let part = match e {
+                    ParseInstructionError::UnknownInstruction => Some(opcode),
+                    _ => operand,
+                };
+                
+                let (origin, text, mark, inst) = line(*i, l, part);
+                let diag = Diagnostic::error(e).at(origin).marked_line(i + 1, text, mark).kind(e.kind()).position(path, i + 1).inst(None, inst);
+                
+                match (e, closest_opcode(opcode)) {
+                    (ParseInstructionError::UnknownInstruction, Some((known, true))) if known == opcode => diag.help(format_args!("`{known}` takes an operand")),
+                    (ParseInstructionError::UnknownInstruction, Some((known, false))) if known == opcode => diag.help(format_args!("`{known}` takes no operand")),
+                    (ParseInstructionError::UnknownInstruction, Some((known, _))) => diag.help(format_args!("did you mean `{known}`?")),
+                    (ParseInstructionError::DisabledIndirect, _) => diag.help("enable the `indirect_jumps` feature"),
+                    _ => diag,
+                }
+            },
+            ParseCodeError::Symbol(i, l, e) => {
+                let part = match e {
+                    ParseSymbolError::InvalidName(name)
+                    | ParseSymbolError::Redefined(name, _)
+                    | ParseSymbolError::Undefined(name)
+                    | ParseSymbolError::Misused(name, _)
+                    | ParseSymbolError::UnknownDirective(name) => Some(name.as_str()),
+                    ParseSymbolError::InvalidDirective => None,
+                };
+                
+                let (origin, text, mark, inst) = line(*i, l, part);
+                let diag = Diagnostic::error(e).at(origin).marked_line(i + 1, text, mark).kind(e.kind()).position(path, i + 1).inst(None, inst);
+                
+                match e {
+                    ParseSymbolError::UnknownDirective(_) | ParseSymbolError::InvalidDirective => diag.help("the directives are `.reg <name> = <register>` and `.const <name> = <value>`"),
+                    _ => diag,
+                }
+            },
+            ParseCodeError::NoInst => Diagnostic::error("empty file").at(path.unwrap_or("anon")).kind("NoInst").help("a program needs at least one instruction, e.g. `STOP`"),
+        }
+    }
+}
+
 impl<T: Integer> Error for ParseCodeError<T> {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
         match self {
             ParseCodeError::Io(e) => Some(e),
             ParseCodeError::Inst(_, _, e) => Some(e),
+            ParseCodeError::Symbol(_, _, e) => Some(e),
             ParseCodeError::NoInst => None,
         }
     }
@@ -39,6 +130,58 @@ impl<T: Integer> From<io::Error> for ParseCodeError<T> {
     }
 }
 
+/// The error type for the labels, `.reg` and `.const` of [`RoCode::parse`](crate::model::RoCode::parse).
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum ParseSymbolError {
+    /// The name isn't an identifier.
+    InvalidName(String),
+    
+    /// The name was already defined, at the specified line.
+    Redefined(String, usize),
+    
+    /// The name isn't defined.
+    Undefined(String),
+    
+    /// The name was defined as another kind of symbol.
+    Misused(String, &'static str),
+    
+    /// The directive isn't `.reg` nor `.const`.
+    UnknownDirective(String),
+    
+    /// The directive isn't `.<directive> <name> = <value>`.
+    InvalidDirective,
+}
+
+impl ParseSymbolError {
+    /// Returns the name of `self`'s variant, e.g. `Undefined`.
+    #[must_use]
+    pub const fn kind(&self) -> &'static str {
+        match self {
+            ParseSymbolError::InvalidName(_) => "InvalidName",
+            ParseSymbolError::Redefined(..) => "Redefined",
+            ParseSymbolError::Undefined(_) => "Undefined",
+            ParseSymbolError::Misused(..) => "Misused",
+            ParseSymbolError::UnknownDirective(_) => "UnknownDirective",
+            ParseSymbolError::InvalidDirective => "InvalidDirective",
+        }
+    }
+}
+
+impl Display for ParseSymbolError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseSymbolError::InvalidName(name) => write!(f, "invalid name `{name}`"),
+            ParseSymbolError::Redefined(name, i) => write!(f, "`{name}` is already defined at line {i}"),
+            ParseSymbolError::Undefined(name) => write!(f, "undefined name `{name}`"),
+            ParseSymbolError::Misused(name, kind) => write!(f, "`{name}` is a {kind}, which can't be used here"),
+            ParseSymbolError::UnknownDirective(directive) => write!(f, "unknown directive `.{directive}`"),
+            ParseSymbolError::InvalidDirective => f.write_str("expected `.<directive> <name> = <value>`"),
+        }
+    }
+}
+
+impl Error for ParseSymbolError {}
+
 /// The error type returned by [`Instruction::from_str`](crate::model::Instruction::from_str).
 #[derive(Debug)]
 pub enum ParseInstructionError<T: Integer> {
@@ -53,9 +196,12 @@ pub enum ParseInstructionError<T: Integer> {
     
     /// Invalid `<address>`.
     InvalidAddress(<Ir as FromStr>::Err),
-
+    
     /// The `indirect_jumps` feature is opted out.
     DisabledIndirect,
+    
+    /// The jump target is past the end of the program, of the specified length.
+    InexistentJump(usize),
 }
 
 impl<T: Integer> Clone for ParseInstructionError<T> where <T as FromStr>::Err: Clone {
@@ -66,6 +212,7 @@ impl<T: Integer> Clone for ParseInstructionError<T> where <T as FromStr>::Err: C
             ParseInstructionError::InvalidRegister(err) => ParseInstructionError::InvalidRegister(err.clone()),
             ParseInstructionError::InvalidAddress(err) => ParseInstructionError::InvalidAddress(err.clone()),
             ParseInstructionError::DisabledIndirect => ParseInstructionError::DisabledIndirect,
+            ParseInstructionError::InexistentJump(len) => ParseInstructionError::InexistentJump(*len),
         }
     }
 }
@@ -80,6 +227,22 @@ impl<T: Integer> PartialEq for ParseInstructionError<T> where <T as FromStr>::Er
             ParseInstructionError::InvalidRegister(err) => matches!(other, ParseInstructionError::InvalidRegister(err1) if err == err1),
             ParseInstructionError::InvalidAddress(err) => matches!(other, ParseInstructionError::InvalidAddress(err1) if err == err1),
             ParseInstructionError::DisabledIndirect => matches!(other, ParseInstructionError::DisabledIndirect),
+            ParseInstructionError::InexistentJump(len) => matches!(other, ParseInstructionError::InexistentJump(len1) if len == len1),
+        }
+    }
+}
+
+impl<T: Integer> ParseInstructionError<T> {
+    /// Returns the name of `self`'s variant, e.g. `UnknownInstruction`.
+    #[must_use]
+    pub const fn kind(&self) -> &'static str {
+        match self {
+            ParseInstructionError::UnknownInstruction => "UnknownInstruction",
+            ParseInstructionError::InvalidValue(_) => "InvalidValue",
+            ParseInstructionError::InvalidRegister(_) => "InvalidRegister",
+            ParseInstructionError::InvalidAddress(_) => "InvalidAddress",
+            ParseInstructionError::DisabledIndirect => "DisabledIndirect",
+            ParseInstructionError::InexistentJump(_) => "InexistentJump",
         }
     }
 }
@@ -104,7 +267,10 @@ impl<T: Integer> Display for ParseInstructionError<T> {
             },
             ParseInstructionError::DisabledIndirect => {
                 f.write_str("the `indirect_jumps` feature is opted out")
-            }
+            },
+            ParseInstructionError::InexistentJump(len) => {
+                write!(f, "jumping to an inexistent location: `<address>` must be less than {len}")
+            },
         }
     }
 }
@@ -115,7 +281,34 @@ impl<T: Integer> Error for ParseInstructionError<T> {
             ParseInstructionError::UnknownInstruction => None,
             ParseInstructionError::InvalidValue(e) => Some(e),
             ParseInstructionError::InvalidRegister(e) | ParseInstructionError::InvalidAddress(e) => Some(e),
-            ParseInstructionError::DisabledIndirect => None,
+            ParseInstructionError::DisabledIndirect | ParseInstructionError::InexistentJump(_) => None,
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::model::RoCode;
+    use std::path::Path;
+    use std::str::FromStr;
+    
+    #[test]
+    fn parse_errors() {
+        let render = |src| RoCode::<i8>::from_str(src).unwrap_err().diagnostic(Some(Path::new("a.ram"))).to_string();
+        
+        assert_eq!(render("READ\n  loop: ADD #300 ; x + 300\nJUMP loop"), "error: invalid value (i8): number too large to fit in target type\n --> a.ram:2\n  |\n2 | loop: ADD #300 ; x + 300\n  |           ^^^^\n  |");
+        assert_eq!(render("READ\nWRTIE"), "error: unknown instruction\n --> a.ram:2\n  |\n2 | WRTIE\n  | ^^^^^\n  |\n  = help: did you mean `WRITE`?");
+        assert_eq!(render("STOP 1"), "error: unknown instruction\n --> a.ram:1\n  |\n1 | STOP 1\n  | ^^^^\n  |\n  = help: `STOP` takes no operand");
+        assert_eq!(render("JUMP end"), "error: undefined name `end`\n --> a.ram:1\n  |\n1 | JUMP end\n  |      ^^^\n  |");
+        assert_eq!(render(""), "error: empty file\n --> a.ram\n  = help: a program needs at least one instruction, e.g. `STOP`");
+    }
+    
+    #[test]
+    fn json() {
+        let err = RoCode::<i8>::from_str("READ\nJMPZ \"x\"").unwrap_err();
+        assert_eq!(
+            err.diagnostic(Some(Path::new("a.ram"))).to_json(),
+            r#"{"level":"error","kind":"UnknownInstruction","message":"unknown instruction","file":"a.ram","line":2,"ir":null,"instruction":"JMPZ \"x\"","acc":null,"registers":[],"help":["did you mean `JUMZ`?"],"notes":[]}"#,
+        );
+    }
+}
@@ -53,6 +53,9 @@ pub struct RwLoc(usize);
 /// A memory location.
 #[sealed]
 pub trait Loc: From<usize> + Copy + Display {
+    /// `true` if the register is read, e.g. by `INC`, `false` if it's only written, e.g. by `STORE`.
+    const READ: bool;
+    
     /// The raw address.
     fn raw(self) -> usize;
 }
@@ -172,6 +175,30 @@ impl<T: Integer> Instruction<T> {
             _ => None,
         }
     }
+    
+    /// Returns the constant address this instruction jumps to, if any.
+    #[must_use]
+    #[inline]
+    pub const fn target(self) -> Option<Ir> {
+        match self.jump() {
+            #[cfg(not(feature = "indirect_jumps"))]
+            Some(adr) => Some(adr),
+            #[cfg(feature = "indirect_jumps")]
+            Some(Address::Constant(adr)) => Some(adr),
+            _ => None,
+        }
+    }
+    
+    /// Returns the register whose value this instruction jumps to, if any.
+    #[cfg(feature = "indirect_jumps")]
+    #[must_use]
+    #[inline]
+    pub const fn indirect_jump(self) -> Option<RoLoc> {
+        match self.jump() {
+            Some(Address::Register(loc)) => Some(loc),
+            _ => None,
+        }
+    }
 }
 
 impl<T: Integer> FromStr for Instruction<T> {
@@ -223,6 +250,54 @@ impl<T: Integer> FromStr for Instruction<T> {
     }
 }
 
+/// The opcodes, and whether they take an operand.
+const OPCODES: [(&str, bool); 17] = [
+    ("READ", false), ("WRITE", false), ("STOP", false), ("NOP", false),
+    ("LOAD", true), ("STORE", true), ("INC", true), ("DEC", true),
+    ("ADD", true), ("SUB", true), ("MUL", true), ("DIV", true), ("MOD", true),
+    ("JUMP", true), ("JUMZ", true), ("JUML", true), ("JUMG", true),
+];
+
+/// Returns the opcode an unknown one most likely stands for, and whether it takes an operand,
+/// e.g. `JUMZ` for `JMPZ`.
+///
+/// Returns the opcode itself if it's known, i.e. if it's given or missing an operand.
+pub(crate) fn closest_opcode(opcode: &str) -> Option<(&'static str, bool)> {
+    // Levenshtein distance, ignoring case
+    let distance = |a: &str, b: &str| {
+        let mut row = (0..=b.len()).collect::<Vec<_>>();
+        for (i, ca) in a.chars().enumerate() {
+            let mut diag = row[0];
+            row[0] = i + 1;
+            for (j, cb) in b.chars().enumerate() {
+                let subst = diag + usize::from(!ca.eq_ignore_ascii_case(&cb));
+                diag = row[j + 1];
+                row[j + 1] = subst.min(row[j] + 1).min(diag + 1);
+            }
+        }
+        row[b.len()]
+    };
+    
+    // Ties favor the opcodes ending the same, e.g. with the condition of a jump
+    let ending = |op: &str| !opcode.chars().next_back().is_some_and(|c| op.ends_with(c.to_ascii_uppercase()));
+    
+    OPCODES.into_iter()
+        .map(|(op, operand)| (distance(opcode, op), op, operand))
+        .filter(|(d, _, _)| *d <= 2 && *d < opcode.len())
+        .min_by_key(|(d, op, _)| (*d, ending(op)))
+        .map(|(_, op, operand)| (op, operand))
+}
+
+#[test]
+fn closest_opcodes() {
+    assert_eq!(closest_opcode("JMPZ"), Some(("JUMZ", true)));
+    assert_eq!(closest_opcode("WRTIE"), Some(("WRITE", false)));
+    assert_eq!(closest_opcode("load"), Some(("LOAD", true)));
+    assert_eq!(closest_opcode("STOP"), Some(("STOP", false)));
+    assert_eq!(closest_opcode("HALT"), None);
+    assert_eq!(closest_opcode("X"), None);
+}
+
 #[test]
 #[cfg_attr(not(feature = "indirect_jumps"), should_panic = "the `indirect_jumps` feature is opted out")]
 fn parse_indirect() {
@@ -349,16 +424,22 @@ impl<T: Integer> Display for Value<T> {
 
 #[sealed]
 impl Loc for RoLoc {
+    const READ: bool = true;
+    
     fn raw(self) -> usize { self.0 }
 }
 
 #[sealed]
 impl Loc for WoLoc {
+    const READ: bool = false;
+    
     fn raw(self) -> usize { self.0 }
 }
 
 #[sealed]
 impl Loc for RwLoc {
+    const READ: bool = true;
+    
     fn raw(self) -> usize { self.0 }
 }
 
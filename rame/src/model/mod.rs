@@ -5,16 +5,21 @@ use std::error::Error;
 use std::fmt::{Debug, Display};
 use std::str::FromStr;
 
+mod asm;
+mod disasm;
 mod error;
 mod inst;
 mod ir;
 mod makro;
 mod ro;
+mod srcmap;
 
-pub use error::{ParseCodeError, ParseInstructionError};
+pub use disasm::Disassembly;
+pub use error::{ParseCodeError, ParseInstructionError, ParseSymbolError};
 pub use inst::{Address, Instruction, Loc, Register, RoLoc, RwLoc, Value, WoLoc};
 pub use ir::Ir;
 pub use ro::RoCode;
+pub use srcmap::{Location, SourceMap};
 
 /// The value type this model can works on.
 pub trait Integer: PrimInt
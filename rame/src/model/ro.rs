@@ -1,52 +1,188 @@
+use crate::model::asm;
 use crate::model::error::ParseCodeError;
-use crate::model::{Instruction, Integer, Ir};
+use crate::model::{Disassembly, Instruction, Integer, Ir, Location, SourceMap};
+use std::collections::HashMap;
 use std::fmt::{self, Display, Formatter};
+use std::hash::{Hash, Hasher};
 use std::fs::File;
 use std::io;
 use std::io::{BufRead, BufReader, BufWriter};
-use std::ops::Deref;
+use std::ops::{Deref, RangeInclusive};
 use std::path::Path;
 use std::str::FromStr;
+use std::sync::Arc;
 
 /// Represents a read-only code segment.
 ///
 /// It may be executed with [`Ram`](crate::runner::Ram), and modified with [`RwCode`](crate::optimizer::WoCode).
-#[derive(Clone, Eq, PartialEq, Hash, Debug)]
-pub struct RoCode<T: Integer>(Vec<Instruction<T>>);
+///
+/// Instructions may be annotated with a note, i.e. the `; comment` ending their line,
+/// with the lines they were parsed from, and with the [`Location`] they were compiled from.
+/// These annotations don't take part in comparisons.
+#[derive(Clone, Debug)]
+pub struct RoCode<T: Integer> {
+    insts: Vec<Instruction<T>>,
+    
+    /// The notes of some instructions.
+    notes: HashMap<Ir, String>,
+    
+    /// The source locations of some instructions.
+    locations: HashMap<Ir, Location>,
+    
+    /// The lines some instructions were parsed from, starting from 1.
+    /// An instruction folded from several ones spans all their lines.
+    lines: HashMap<Ir, RangeInclusive<usize>>,
+    
+    /// The file `self` was parsed from.
+    path: Option<Arc<Path>>,
+}
 
 impl<T: Integer> RoCode<T> {
-    /// Parses a file.
-    /// Blank lines and `; comments` are allowed.
-    pub fn parse(f: File) -> Result<RoCode<T>, ParseCodeError<T>> {
-        let mut insts = Vec::new();
-        
-        for (i, l) in BufReader::new(f).lines().enumerate() {
-            let l = l?;
-            
-            // Remove `; comments` and spaces
-            let stripped = match l.split_once(';') {
-                Some((code, _)) => code,
-                None => l.as_str(),
-            }.trim_ascii();
-            
-            if stripped.is_empty() {
-                continue;
-            }
-            
-            match Instruction::from_str(stripped) {
-                Ok(i) => insts.push(i),
-                Err(e) => return Err(ParseCodeError::Inst(i, l, e)),
-            }
+    /// Returns some code without annotations.
+    fn new(insts: Vec<Instruction<T>>) -> RoCode<T> {
+        RoCode {
+            insts,
+            notes: HashMap::new(),
+            locations: HashMap::new(),
+            lines: HashMap::new(),
+            path: None,
         }
-        
-        if insts.is_empty() {
-            Err(ParseCodeError::NoInst)
+    }
+    
+    /// Returns some other instructions, with the annotations of `self`.
+    fn with_insts<U: Integer>(&self, insts: Vec<Instruction<U>>) -> RoCode<U> {
+        RoCode {
+            insts,
+            notes: self.notes.clone(),
+            locations: self.locations.clone(),
+            lines: self.lines.clone(),
+            path: self.path.clone(),
         }
-        else {
-            Ok(RoCode(insts))
+    }
+    
+    /// Parses a file.
+    /// Blank lines, `; comments`, `label:`s, `.reg name = n` aliases and `.const name = v` constants are allowed.
+    ///
+    /// The path of the file isn't known, see [`RoCode::parse_file`] and [`RoCode::with_path`].
+    pub fn parse(f: File) -> Result<RoCode<T>, ParseCodeError<T>> {
+        RoCode::assemble(BufReader::new(f), false).map_err(first)
+    }
+    
+    /// Parses something like [`RoCode::parse`], but goes on past the invalid lines
+    /// and returns the errors of all of them, in order, e.g. to report them at once.
+    ///
+    /// Jumps past the end of the program, which [`RoCode::parse`] accepts, are also reported as
+    /// [`ParseInstructionError::InexistentJump`](crate::model::ParseInstructionError::InexistentJump).
+    pub fn parse_all<R: BufRead>(r: R) -> Result<RoCode<T>, Vec<ParseCodeError<T>>> {
+        RoCode::assemble(r, true)
+    }
+    
+    fn assemble<R: BufRead>(r: R, check_jumps: bool) -> Result<RoCode<T>, Vec<ParseCodeError<T>>> {
+        let (insts, notes, lines) = asm::assemble(r.lines(), check_jumps)?;
+        Ok(RoCode::try_from(insts).map_err(|e| vec![e])?.with_notes(notes).with_lines(lines))
+    }
+    
+    /// Opens and parses a file, see [`RoCode::parse`].
+    pub fn parse_file<P: AsRef<Path>>(path: P) -> Result<RoCode<T>, ParseCodeError<T>> {
+        let path = path.as_ref();
+        Ok(RoCode::parse(File::open(path)?)?.with_path(path))
+    }
+    
+    /// Returns the file `self` was parsed from, if known.
+    #[must_use]
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+    
+    /// Sets the file `self` was parsed from.
+    pub fn with_path<P: AsRef<Path>>(mut self, path: P) -> RoCode<T> {
+        self.path = Some(path.as_ref().into());
+        self
+    }
+    
+    /// Returns the lines the specified [`Ir`] was parsed from, if known.
+    #[must_use]
+    pub fn lines(&self, ir: Ir) -> Option<RangeInclusive<usize>> {
+        self.lines.get(&ir).cloned()
+    }
+    
+    /// Sets the lines some instructions were parsed from, starting from 1.
+    ///
+    /// Out-of-bounds IRs are ignored.
+    pub fn with_lines<I: IntoIterator<Item = (Ir, RangeInclusive<usize>)>>(mut self, lines: I) -> RoCode<T> {
+        let len = self.len();
+        self.lines.extend(lines.into_iter().filter(|(ir, _)| *ir < len));
+        self
+    }
+    
+    /// Returns where the specified [`Ir`] comes from, for diagnostics:
+    /// its source [`Location`] if known, e.g. `fibo.algo:5 in fibo()`,
+    /// else its [`RoCode::line_origin`].
+    #[must_use]
+    pub fn origin(&self, ir: Ir) -> String {
+        self.location(ir).map_or_else(|| self.line_origin(ir), Location::to_string)
+    }
+    
+    /// Returns the file and lines the specified [`Ir`] was parsed from, e.g. `fibo.ram:12-13`,
+    /// `anon` and `ir + 1` standing for an unknown file and line.
+    #[must_use]
+    pub fn line_origin(&self, ir: Ir) -> String {
+        let path = self.path().map_or_else(|| "anon".to_owned(), |path| path.display().to_string());
+        match self.lines(ir) {
+            Some(lines) if lines.start() != lines.end() => format!("{path}:{}-{}", lines.start(), lines.end()),
+            Some(lines) => format!("{path}:{}", lines.start()),
+            None => format!("{path}:{}", ir.inner() + 1),
         }
     }
     
+    /// Returns the note of the specified [`Ir`], if any.
+    #[must_use]
+    pub fn note(&self, ir: Ir) -> Option<&str> {
+        self.notes.get(&ir).map(String::as_str)
+    }
+    
+    /// Annotates some instructions, replacing their previous notes.
+    ///
+    /// Out-of-bounds IRs and empty notes are ignored.
+    pub fn with_notes<I: IntoIterator<Item = (Ir, String)>>(mut self, notes: I) -> RoCode<T> {
+        let len = self.len();
+        self.notes.extend(notes.into_iter().filter(|(ir, note)| *ir < len && !note.is_empty()));
+        self
+    }
+    
+    /// Returns the source location of the specified [`Ir`], if known.
+    #[must_use]
+    pub fn location(&self, ir: Ir) -> Option<&Location> {
+        self.locations.get(&ir)
+    }
+    
+    /// Sets the source location of some instructions.
+    ///
+    /// Out-of-bounds IRs are ignored.
+    pub fn with_locations<I: IntoIterator<Item = (Ir, Location)>>(mut self, locations: I) -> RoCode<T> {
+        let len = self.len();
+        self.locations.extend(locations.into_iter().filter(|(ir, _)| *ir < len));
+        self
+    }
+    
+    /// Sets the source location of all instructions from a [`SourceMap`].
+    pub fn with_source_map(self, map: &SourceMap) -> RoCode<T> {
+        let locations = (0..self.len()).map(Ir::new).filter_map(|ir| Some((ir, map.get(ir)?.clone()))).collect::<Vec<_>>();
+        self.with_locations(locations)
+    }
+    
+    /// Returns the [`SourceMap`] of `self`, empty if no location is known.
+    #[must_use]
+    pub fn source_map(&self) -> SourceMap {
+        (0..self.len()).map(Ir::new).map(|ir| (ir, self.location(ir).cloned())).collect()
+    }
+    
+    /// Returns a [`Display`]able listing of `self`, with labels at every jump target
+    /// and a blank line between basic blocks, that [`RoCode::parse`] reads back.
+    pub const fn disassemble(&self) -> Disassembly<'_, T> {
+        Disassembly::new(self)
+    }
+    
     /// Writes `self` into something.
     pub fn write<W: io::Write>(&self, mut w: W) -> io::Result<()> {
         w.write_all(self.to_string().as_bytes())
@@ -57,21 +193,21 @@ impl<T: Integer> RoCode<T> {
         let f = File::create(path)?;
         self.write(BufWriter::new(f))
     }
-
+    
     /// Maps a `RoCode<T>` to `RoCode<U>` by applying a function.
     #[must_use]
     #[inline]
     pub fn map<U: Integer, F: Fn(T) -> U>(&self, f: F) -> RoCode<U> {
-        RoCode(self.iter().map(|inst| inst.map(&f)).collect())
+        self.with_insts(self.iter().map(|inst| inst.map(&f)).collect())
     }
-
+    
     /// Maps a `RoCode<T>` to `RoCode<U>`.
     #[must_use]
     #[inline]
     pub fn cast<U: Integer + From<T>>(&self) -> RoCode<U> {
         self.map(<U as From<T>>::from)
     }
-
+    
     /// Maps a `RoCode<T>` to `RoCode<U>` by applying a function.
     #[inline]
     pub fn try_map<U: Integer, E, F: Fn(T) -> Result<U, E>>(&self, f: F) -> Result<RoCode<U>, E> {
@@ -79,9 +215,9 @@ impl<T: Integer> RoCode<T> {
         for v in self.iter() {
             vec.push(v.try_map(&f)?);
         }
-        Ok(RoCode(vec))
+        Ok(self.with_insts(vec))
     }
-
+    
     /// Maps a `RoCode<T>` to `RoCode<U>`.
     #[inline]
     pub fn try_cast<U: Integer + TryFrom<T>>(&self) -> Result<RoCode<U>, <U as TryFrom<T>>::Error> {
@@ -95,17 +231,17 @@ impl<T: Integer> RoCode<T> {
     pub fn optimize(&self) -> RoCode<T> {
         crate::optimizer::run_passes(self)
     }
-
+    
     #[inline]
     #[must_use]
     pub fn get(&self, ir: Ir) -> Option<Instruction<T>> {
         ir.index(self)
     }
-
+    
     pub fn iter(&self) -> impl Iterator<Item = Instruction<T>> + '_ {
         (**self).iter().copied()
     }
-
+    
     pub fn enumerate(&self) -> impl Iterator<Item = (Ir, Instruction<T>)> + '_ {
         Ir::enumerate(self.iter())
     }
@@ -115,51 +251,85 @@ impl<T: Integer> Deref for RoCode<T> {
     type Target = [Instruction<T>];
     
     fn deref(&self) -> &Self::Target {
-        self.0.deref()
+        self.insts.deref()
     }
 }
 
 impl<T: Integer> IntoIterator for RoCode<T> {
     type Item = Instruction<T>;
     type IntoIter = <Vec<Instruction<T>> as IntoIterator>::IntoIter;
-
+    
     fn into_iter(self) -> Self::IntoIter {
-        self.0.into_iter()
+        self.insts.into_iter()
     }
 }
 
 impl<T: Integer> Display for RoCode<T> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        let Some((last, insts)) = self.0.split_last() else {
+        if self.insts.is_empty() {
             return f.write_str("<no code>");
-        };
+        }
         
-        for inst in insts {
-            Display::fmt(inst, f)?;
-            writeln!(f)?;
+        for (ir, inst) in self.enumerate() {
+            if ir != 0 {
+                writeln!(f)?;
+            }
+            
+            Display::fmt(&inst, f)?;
+            if let Some(note) = self.note(ir) {
+                write!(f, " ; {note}")?;
+            }
         }
         
-        Display::fmt(last, f)
+        Ok(())
+    }
+}
+
+impl<T: Integer> FromStr for RoCode<T> {
+    type Err = ParseCodeError<T>;
+    
+    /// Parses some source, see [`RoCode::parse`].
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        RoCode::assemble(s.as_bytes(), false).map_err(first)
+    }
+}
+
+/// Returns the first of the errors of [`RoCode::assemble`].
+fn first<T: Integer>(errors: Vec<ParseCodeError<T>>) -> ParseCodeError<T> {
+    errors.into_iter().next().expect("an error is reported")
+}
+
+impl<T: Integer> PartialEq for RoCode<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.insts == other.insts
+    }
+}
+
+impl<T: Integer> Eq for RoCode<T> {}
+
+impl<T: Integer + Hash> Hash for RoCode<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.insts.hash(state);
     }
 }
 
 impl<T: Integer> Default for RoCode<T> {
     /// Returns a program with only a [`STOP` instruction.](`Instruction::Stop`)
     fn default() -> Self {
-        RoCode(vec![Instruction::Stop])
+        RoCode::new(vec![Instruction::Stop])
     }
 }
 
 impl<T: Integer> TryFrom<Vec<Instruction<T>>> for RoCode<T> {
     type Error = ParseCodeError<T>;
-
+    
     /// Transforms a vector of [`Instruction`]s into a [`RoCode`].
     fn try_from(value: Vec<Instruction<T>>) -> Result<RoCode<T>, ParseCodeError<T>> {
         if value.is_empty() {
             Err(ParseCodeError::NoInst)
         }
         else {
-            Ok(RoCode(value))
+            Ok(RoCode::new(value))
         }
     }
 }
@@ -167,13 +337,13 @@ impl<T: Integer> TryFrom<Vec<Instruction<T>>> for RoCode<T> {
 impl<T: Integer> From<&[Instruction<T>]> for RoCode<T> {
     /// Transforms an array of [`Instruction`]s into a [`RoCode`].
     fn from(value: &[Instruction<T>]) -> Self {
-        RoCode(value.into())
+        RoCode::new(value.into())
     }
 }
 
 impl<T: Integer, const N: usize> From<[Instruction<T>; N]> for RoCode<T> {
     /// Transforms an array of [`Instruction`]s into a [`RoCode`].
     fn from(value: [Instruction<T>; N]) -> Self {
-        RoCode(value.into())
+        RoCode::new(value.into())
     }
 }
@@ -0,0 +1,180 @@
+//! Maps RAM instructions back to the source they were compiled from.
+
+use crate::model::Ir;
+use std::fmt::{self, Display, Formatter};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// A line of source code, e.g. `fibo.algo:5 in fibo()`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Location {
+    /// The source file.
+    pub file: Arc<str>,
+    
+    /// The line, starting from 1.
+    pub line: usize,
+    
+    /// The enclosing function, if any.
+    pub function: Option<Arc<str>>,
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.file, self.line)?;
+        self.function.as_ref().map_or(Ok(()), |function| write!(f, " in {function}()"))
+    }
+}
+
+/// Maps each [`Ir`] to its [`Location`], as written by the compiler alongside the RAM code.
+///
+/// The file has one entry per line, `ir\tfile\tline\tfunction` or only `ir`,
+/// covering the instructions from `ir` up to the next entry.
+/// An entry with only an IR marks instructions without location, e.g. the compiler's runtime.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SourceMap(Vec<(Ir, Option<Location>)>);
+
+impl SourceMap {
+    /// Parses a file.
+    pub fn parse(f: File) -> io::Result<SourceMap> {
+        SourceMap::read(BufReader::new(f))
+    }
+    
+    /// Parses something.
+    pub fn read<R: BufRead>(r: R) -> io::Result<SourceMap> {
+        let invalid = |i: usize, msg: &str| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {msg}", i + 1));
+        
+        // Files are shared by all of their locations
+        let mut files = Vec::<Arc<str>>::new();
+        let mut entries = Vec::<(Ir, Option<Location>)>::new();
+        
+        for (i, l) in r.lines().enumerate() {
+            let l = l?;
+            if l.trim_ascii().is_empty() {
+                continue;
+            }
+            
+            let mut fields = l.split('\t');
+            let ir = fields.next().and_then(|ir| usize::from_str(ir).ok()).ok_or_else(|| invalid(i, "invalid IR"))?;
+            
+            let location = match (fields.next(), fields.next(), fields.next()) {
+                (None, _, _) => None,
+                (Some(file), Some(line), function) => {
+                    let line = usize::from_str(line).ok().filter(|line| *line > 0).ok_or_else(|| invalid(i, "invalid line"))?;
+                    if !files.iter().any(|f| **f == *file) {
+                        files.push(file.into());
+                    }
+                    let file = files.iter().find(|f| ***f == *file).map(Arc::clone).expect("the file was added");
+                    
+                    let function = function.filter(|function| !function.is_empty()).map(Arc::from);
+                    Some(Location { file, line, function })
+                },
+                (Some(_), None, _) => return Err(invalid(i, "missing line")),
+            };
+            
+            if entries.last().is_some_and(|(last, _)| *last > ir) {
+                return Err(invalid(i, "unordered IR"));
+            }
+            
+            // The last entry of an IR wins
+            if entries.last().is_some_and(|(last, _)| *last == ir) {
+                entries.pop();
+            }
+            entries.push((Ir::new(ir), location));
+        }
+        
+        Ok(SourceMap(entries))
+    }
+    
+    /// Returns the location of the specified [`Ir`], if known.
+    #[must_use]
+    pub fn get(&self, ir: Ir) -> Option<&Location> {
+        let i = self.0.partition_point(|(start, _)| *start <= ir);
+        self.0.get(i.checked_sub(1)?)?.1.as_ref()
+    }
+    
+    /// Returns `true` iff no instruction has a location.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|(_, location)| location.is_none())
+    }
+    
+    /// Writes `self` into something.
+    pub fn write<W: Write>(&self, mut w: W) -> io::Result<()> {
+        w.write_all(self.to_string().as_bytes())
+    }
+    
+    /// Write `self` into the specified file.
+    pub fn write_to_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let f = File::create(path)?;
+        self.write(BufWriter::new(f))
+    }
+}
+
+impl FromStr for SourceMap {
+    type Err = io::Error;
+    
+    /// Parses some source map, see [`SourceMap`].
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        SourceMap::read(s.as_bytes())
+    }
+}
+
+impl FromIterator<(Ir, Option<Location>)> for SourceMap {
+    /// Collects the location of each IR, in order.
+    ///
+    /// Consecutive IRs at the same location share an entry.
+    fn from_iter<I: IntoIterator<Item = (Ir, Option<Location>)>>(iter: I) -> Self {
+        let mut entries = Vec::<(Ir, Option<Location>)>::new();
+        
+        for (ir, location) in iter {
+            if !entries.last().is_some_and(|(_, last)| *last == location) {
+                entries.push((ir, location));
+            }
+        }
+        
+        SourceMap(entries)
+    }
+}
+
+impl Display for SourceMap {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (ir, location) in &self.0 {
+            match location {
+                Some(Location { file, line, function }) => {
+                    writeln!(f, "{ir}\t{file}\t{line}\t{}", function.as_deref().unwrap_or_default())?;
+                },
+                None => writeln!(f, "{ir}")?,
+            }
+        }
+        
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::model::{Ir, RoCode, SourceMap};
+    use crate::inst;
+    use std::str::FromStr;
+    
+    #[test]
+    fn source_map() {
+        let map = SourceMap::from_str("0\n3\tfibo.algo\t5\tfibo\n5\tfibo.algo\t6\tfibo\n5\tfibo.algo\t2\t\n7\n").unwrap();
+        
+        let location = |ir| map.get(Ir::new(ir)).map(ToString::to_string);
+        assert_eq!(location(2), None);
+        assert_eq!(location(3).as_deref(), Some("fibo.algo:5 in fibo()"));
+        assert_eq!(location(4).as_deref(), Some("fibo.algo:5 in fibo()"));
+        assert_eq!(location(6).as_deref(), Some("fibo.algo:2"));
+        assert_eq!(location(9), None);
+        
+        let code = RoCode::<i32>::from([inst!(NOP); 8]).with_source_map(&map);
+        assert_eq!(code.source_map().to_string(), "0\n3\tfibo.algo\t5\tfibo\n5\tfibo.algo\t2\t\n7\n");
+        
+        assert_eq!(SourceMap::from_str("1\tfibo.algo").unwrap_err().to_string(), "1: missing line");
+        assert_eq!(SourceMap::from_str("3\n1").unwrap_err().to_string(), "2: unordered IR");
+    }
+}
@@ -0,0 +1,21 @@
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+/// The error type returned by [`PassManager::select`](crate::optimizer::PassManager::select)
+/// and [`PassManager::disable`](crate::optimizer::PassManager::disable).
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct UnknownPass {
+    /// The requested pass.
+    pub name: String,
+    
+    /// The registered passes.
+    pub known: Vec<&'static str>,
+}
+
+impl Display for UnknownPass {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown pass `{}`; known passes are: {}", self.name, self.known.join(", "))
+    }
+}
+
+impl Error for UnknownPass {}
@@ -0,0 +1,290 @@
+//! Recovers the targets of indirect jumps, by propagating the constant values of the registers.
+
+use crate::model::{Instruction, Integer, Ir, Loc as _, Register, RoCode, RoLoc, Value};
+use std::collections::{BTreeSet, HashMap};
+
+/// Maximum count of values tracked for a register, before it's considered unknown.
+const MAX_VALUES: usize = 16;
+
+/// Count of merges into the state of an instruction, before the registers that still change are considered unknown.
+const MAX_MERGES: usize = 3;
+
+/// The possible values of a register.
+#[derive(Clone, Eq, PartialEq, Debug)]
+enum Values<T: Integer> {
+    /// One of these values, sorted, `None` standing for uninitialized.
+    Set(Vec<Option<T>>),
+    /// Any value.
+    Any,
+}
+
+impl<T: Integer> Values<T> {
+    /// No value, e.g. the values of an instruction that can't be run.
+    const fn none() -> Values<T> {
+        Values::Set(Vec::new())
+    }
+    
+    /// An uninitialized register.
+    fn uninit() -> Values<T> {
+        Values::Set(vec![None])
+    }
+    
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Values<T> {
+        let mut set = iter.into_iter().map(Some).collect::<Vec<_>>();
+        set.sort_unstable();
+        set.dedup();
+        
+        if set.len() > MAX_VALUES { Values::Any } else { Values::Set(set) }
+    }
+    
+    fn insert(&mut self, v: Option<T>) {
+        if let Values::Set(set) = self {
+            if let Err(i) = set.binary_search(&v) {
+                if set.len() == MAX_VALUES {
+                    *self = Values::Any;
+                } else {
+                    set.insert(i, v);
+                }
+            }
+        }
+    }
+    
+    /// Merges `other` into `self`, returning `true` iff `self` changed.
+    fn join(&mut self, other: &Values<T>) -> bool {
+        let old = self.clone();
+        match other {
+            Values::Set(set) => set.iter().for_each(|v| self.insert(*v)),
+            Values::Any => *self = Values::Any,
+        }
+        
+        *self != old
+    }
+    
+    /// Applies a fallible binary operation to all the pairs of values.
+    fn combine<F: Fn(&T, &T) -> Option<T>>(&self, other: &Values<T>, f: F) -> Values<T> {
+        match (self, other) {
+            (Values::Set(a), Values::Set(b)) => Values::from_iter(a.iter().flatten().flat_map(|a| b.iter().flatten().filter_map(|b| f(a, b)))),
+            _ => Values::Any,
+        }
+    }
+    
+    /// Returns these values without `None`, as reading an uninitialized register fails.
+    fn initialized(mut self) -> Values<T> {
+        if let Values::Set(set) = &mut self {
+            set.retain(Option::is_some);
+        }
+        
+        self
+    }
+    
+    /// The memory locations these values can point to, or `None` if unknown.
+    fn locs(&self) -> Option<Vec<usize>> {
+        match self {
+            Values::Set(set) => Some(set.iter().flatten().filter_map(|v| (*v).try_into().ok()).collect()),
+            Values::Any => None,
+        }
+    }
+}
+
+/// The possible values of all registers, right before executing an instruction.
+#[derive(Clone, Eq, PartialEq, Debug)]
+struct State<T: Integer> {
+    regs: HashMap<usize, Values<T>>,
+    /// The values of the registers missing from `regs`.
+    others: Values<T>,
+}
+
+impl<T: Integer> State<T> {
+    fn get(&self, loc: usize) -> &Values<T> {
+        self.regs.get(&loc).unwrap_or(&self.others)
+    }
+    
+    fn set(&mut self, loc: usize, values: Values<T>) {
+        self.regs.insert(loc, values);
+    }
+    
+    /// Returns the values of ACC, once read.
+    fn acc(&self) -> Values<T> {
+        self.get(0).clone().initialized()
+    }
+    
+    fn read(&self, reg: Register<RoLoc>) -> Values<T> {
+        let values = match reg {
+            Register::Direct(loc) => self.get(loc.raw()).clone(),
+            Register::Indirect(ptr) => self.get(ptr.raw()).locs().map_or(Values::Any, |locs| {
+                locs.into_iter().fold(Values::none(), |mut values, loc| {
+                    values.join(self.get(loc));
+                    values
+                })
+            }),
+        };
+        
+        values.initialized()
+    }
+    
+    fn eval(&self, v: Value<T>) -> Values<T> {
+        match v {
+            Value::Constant(v) => Values::Set(vec![Some(v)]),
+            Value::Register(reg) => self.read(reg),
+        }
+    }
+    
+    fn write<L: crate::model::Loc>(&mut self, reg: Register<L>, values: Values<T>) {
+        match reg {
+            Register::Direct(loc) => self.set(loc.raw(), values),
+            Register::Indirect(ptr) => match self.get(ptr.raw()).locs().as_deref() {
+                Some([loc]) => self.set(*loc, values),
+                Some(locs) => {
+                    for loc in locs {
+                        let mut joined = self.get(*loc).clone();
+                        joined.join(&values);
+                        self.set(*loc, joined);
+                    }
+                },
+                // Anything may be overwritten
+                None => {
+                    self.regs.clear();
+                    self.others = Values::Any;
+                },
+            },
+        }
+    }
+    
+    /// Merges `other` into `self`, returning `true` iff `self` changed.
+    ///
+    /// If `widen` is `true`, the registers that changed can now hold any value,
+    /// so that loops counting up to large values are quickly analyzed.
+    fn join(&mut self, other: &State<T>, widen: bool) -> bool {
+        let mut changed = false;
+        
+        for loc in other.regs.keys() {
+            if !self.regs.contains_key(loc) {
+                self.regs.insert(*loc, self.others.clone());
+            }
+        }
+        
+        let merge = |values: &mut Values<T>, other: &Values<T>| {
+            let changed = values.join(other);
+            if changed && widen {
+                *values = Values::Any;
+            }
+            
+            changed
+        };
+        
+        for (loc, values) in &mut self.regs {
+            changed |= merge(values, other.get(*loc));
+        }
+        
+        changed | merge(&mut self.others, &other.others)
+    }
+}
+
+/// Returns the possible targets of each reachable indirect jump, or `None` if
+/// the target of one of them can't be known.
+///
+/// Out-of-bounds targets are ignored, as jumping to them is an error.
+#[must_use]
+pub fn jump_targets<T: Integer>(code: &RoCode<T>) -> Option<HashMap<Ir, Vec<Ir>>> {
+    let jumps = jump_values(code)?;
+    Some(jumps.into_iter().map(|(ir, values)| {
+        let adrs = values.locs().expect("the values are known").into_iter().map(Ir::new).filter(|adr| *adr < code.len()).collect();
+        (ir, adrs)
+    }).collect())
+}
+
+/// Returns the target of each reachable indirect jump that always jumps to the same instruction,
+/// i.e. whose register is initialized and holds a single value, which is in bounds.
+pub(super) fn resolved_jumps<T: Integer>(code: &RoCode<T>) -> HashMap<Ir, Ir> {
+    let Some(jumps) = jump_values(code) else {
+        return HashMap::new();
+    };
+    
+    jumps.into_iter().filter_map(|(ir, values)| match values {
+        Values::Set(set) => match set[..] {
+            [Some(v)] => v.try_into().ok().map(Ir::new).filter(|adr| *adr < code.len()).map(|adr| (ir, adr)),
+            _ => None,
+        },
+        Values::Any => None,
+    }).collect()
+}
+
+/// Returns the possible values of the register of each reachable indirect jump, or `None` if
+/// the target of one of them can't be known.
+fn jump_values<T: Integer>(code: &RoCode<T>) -> Option<HashMap<Ir, Values<T>>> {
+    let mut jumps = HashMap::new();
+    if !code.iter().any(|inst| inst.indirect_jump().is_some()) {
+        return Some(jumps);
+    }
+    
+    let mut states: Vec<Option<State<T>>> = vec![None; code.len()];
+    let mut merges = vec![0; code.len()];
+    // Visiting the instructions in order converges faster
+    let mut worklist = BTreeSet::from([Ir::default()]);
+    
+    // The registers are initially uninitialized
+    states[0] = Some(State { regs: HashMap::new(), others: Values::uninit() });
+    
+    // The states should converge thanks to widening, but give up on pathological cases
+    let mut fuel = code.len().saturating_mul(4 * MAX_VALUES);
+    
+    while let Some(ir) = worklist.pop_first() {
+        fuel = fuel.checked_sub(1)?;
+        
+        let inst = code.get(ir).expect("only valid IRs are visited");
+        let mut state = states[ir.inner()].clone().expect("visited IRs have a state");
+        let mut next = vec![ir + 1];
+        
+        match inst {
+            Instruction::Read => state.set(0, Values::Any),
+            Instruction::Write | Instruction::Nop => {},
+            Instruction::Load(v) => state.set(0, state.eval(v)),
+            Instruction::Store(reg) => state.write(reg, state.acc()),
+            Instruction::Increment(reg) => state.write(reg, state.read(reg.downgrade()).combine(&Values::from_iter([T::one()]), T::checked_add)),
+            Instruction::Decrement(reg) => state.write(reg, state.read(reg.downgrade()).combine(&Values::from_iter([T::one()]), T::checked_sub)),
+            Instruction::Add(v) => state.set(0, state.get(0).combine(&state.eval(v), T::checked_add)),
+            Instruction::Sub(v) => state.set(0, state.get(0).combine(&state.eval(v), T::checked_sub)),
+            Instruction::Mul(v) => state.set(0, state.get(0).combine(&state.eval(v), T::checked_mul)),
+            Instruction::Div(v) => state.set(0, state.get(0).combine(&state.eval(v), T::checked_div)),
+            Instruction::Mod(v) => state.set(0, state.get(0).combine(&state.eval(v), T::checked_rem)),
+            Instruction::Stop => next.clear(),
+            Instruction::Jump(_) | Instruction::JumpZero(_) | Instruction::JumpLtz(_) | Instruction::JumpGtz(_) => {
+                if matches!(inst, Instruction::Jump(_)) {
+                    next.clear();
+                }
+                
+                if let Some(adr) = inst.target() {
+                    next.push(adr);
+                }
+                
+                #[cfg(feature = "indirect_jumps")]
+                if let Some(loc) = inst.indirect_jump() {
+                    let values = state.get(loc.raw());
+                    next.extend(values.locs()?.into_iter().map(Ir::new));
+                    jumps.insert(ir, values.clone());
+                }
+            },
+        }
+        
+        for adr in next {
+            let Some(slot) = states.get_mut(adr.inner()) else {
+                // falling off the code, or jumping to an inexistent instruction
+                continue;
+            };
+            
+            let changed = if let Some(old) = slot {
+                merges[adr.inner()] += 1;
+                old.join(&state, merges[adr.inner()] > MAX_MERGES)
+            } else {
+                *slot = Some(state.clone());
+                true
+            };
+            
+            if changed {
+                worklist.insert(adr);
+            }
+        }
+    }
+    
+    Some(jumps)
+}
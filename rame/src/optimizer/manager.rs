@@ -0,0 +1,232 @@
+use crate::model::{Integer, RoCode};
+use crate::optimizer::{pass, run_pass, Pass, UnknownPass};
+use std::fmt::{self, Display, Formatter};
+
+/// How hard the [`PassManager`] should try.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub enum OptLevel {
+    /// No optimization.
+    O0,
+    /// Cheap local optimizations.
+    O1,
+    /// All optimizations.
+    #[default] O2,
+    /// Optimize for size; currently the same as `O2`, as all passes shrink the code.
+    Os,
+}
+
+/// A named optimization pass.
+#[derive(Copy, Clone, Debug)]
+pub struct NamedPass<T: Integer> {
+    pub name: &'static str,
+    pub pass: Pass<T>,
+}
+
+/// Statistics about a pass.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct PassStats {
+    pub name: &'static str,
+    /// How many times the pass was run.
+    pub runs: usize,
+    /// How many times the pass modified the code.
+    pub changes: usize,
+    /// The count of instructions removed, minus the count of instructions added.
+    pub removed: isize,
+}
+
+/// Runs a list of passes until they don't change the code anymore.
+///
+/// # Example
+///
+/// ```
+/// # use rame::inst;
+/// # use rame::model::RoCode;
+/// # use rame::optimizer::PassManager;
+/// let code = RoCode::<i32>::from([
+///     inst!(NOP),
+///     inst!(ADD #1),
+///     inst!(ADD #2),
+/// ]);
+///
+/// let mut pm = PassManager::new();
+/// pm.select(["remove_nops"]).unwrap();
+/// assert_eq!(pm.run(&code), RoCode::from([inst!(ADD #1), inst!(ADD #2)]));
+/// ```
+#[derive(Clone, Debug)]
+#[must_use]
+pub struct PassManager<T: Integer> {
+    /// All the known passes.
+    registry: Vec<NamedPass<T>>,
+    
+    /// The indices of the passes to run, in order.
+    pipeline: Vec<usize>,
+    
+    /// Statistics of the last run, one for each pass of `pipeline`.
+    stats: Vec<PassStats>,
+    
+    /// How many times the pipeline was run during the last run.
+    iterations: usize,
+    
+    /// Maximum number of times the pipeline is run.
+    max_iterations: usize,
+}
+
+impl<T: Integer> PassManager<T> {
+    /// Creates a new pass manager with all the built-in passes registered, using [`OptLevel::O2`].
+    pub fn new() -> PassManager<T> {
+        PassManager::with_level(OptLevel::default())
+    }
+    
+    /// Creates a new pass manager with all the built-in passes registered,
+    /// selecting the passes according to the specified level.
+    pub fn with_level(level: OptLevel) -> PassManager<T> {
+        let mut pm = PassManager {
+            registry: Vec::new(),
+            pipeline: Vec::new(),
+            stats: Vec::new(),
+            iterations: 0,
+            max_iterations: 16,
+        };
+        
+        #[cfg(feature = "indirect_jumps")]
+        pm.register("resolve_indirect_jumps", pass::resolve_indirect_jumps);
+        pm.register("remove_nops", pass::remove_nops);
+        pm.register("simplify_jumps", pass::simplify_jumps);
+        pm.register("thread_jumps", pass::thread_jumps);
+        pm.register("remove_unreachable_code", pass::remove_unreachable_code);
+        pm.register("fold_consts", pass::fold_consts);
+        
+        let passes: &[&str] = match level {
+            OptLevel::O0 => &[],
+            OptLevel::O1 => &["remove_nops", "simplify_jumps", "remove_unreachable_code", "fold_consts"],
+            OptLevel::O2 | OptLevel::Os => &["remove_nops", "simplify_jumps", "thread_jumps", "remove_unreachable_code", "fold_consts"],
+        };
+        
+        // Direct jumps unpin the code, and are understood by the other passes
+        let resolve: &[&str] = if cfg!(feature = "indirect_jumps") && level != OptLevel::O0 { &["resolve_indirect_jumps"] } else { &[] };
+        
+        pm.select(resolve.iter().chain(passes)).expect("built-in passes should be registered");
+        pm
+    }
+    
+    /// Registers a new pass, without selecting it.
+    /// If a pass with the same name exists, it is replaced.
+    pub fn register(&mut self, name: &'static str, pass: Pass<T>) -> &mut Self {
+        match self.registry.iter_mut().find(|p| p.name == name) {
+            Some(p) => p.pass = pass,
+            None => self.registry.push(NamedPass { name, pass }),
+        }
+        
+        self
+    }
+    
+    /// Replaces the passes to run by the specified ones, in order.
+    pub fn select<S: AsRef<str>, I: IntoIterator<Item = S>>(&mut self, names: I) -> Result<&mut Self, UnknownPass> {
+        let pipeline = names.into_iter().map(|name| self.index(name.as_ref())).collect::<Result<Vec<_>, _>>()?;
+        self.pipeline = pipeline;
+        Ok(self)
+    }
+    
+    /// Removes the specified pass from the passes to run.
+    pub fn disable(&mut self, name: &str) -> Result<&mut Self, UnknownPass> {
+        let i = self.index(name)?;
+        self.pipeline.retain(|p| *p != i);
+        Ok(self)
+    }
+    
+    /// Sets the maximum number of times the passes are run, 16 by default.
+    pub fn max_iterations(&mut self, max_iterations: usize) -> &mut Self {
+        self.max_iterations = max_iterations;
+        self
+    }
+    
+    /// Returns all the known passes.
+    #[must_use]
+    pub fn registered(&self) -> &[NamedPass<T>] {
+        &self.registry
+    }
+    
+    /// Returns the passes to run, in order.
+    pub fn selected(&self) -> impl Iterator<Item = NamedPass<T>> + '_ {
+        self.pipeline.iter().map(|i| self.registry[*i])
+    }
+    
+    /// Runs the selected passes on the specified code until a fixed point is reached,
+    /// or they were run [`max_iterations`](PassManager::max_iterations) times, returning the code as is then.
+    pub fn run(&mut self, code: &RoCode<T>) -> RoCode<T> {
+        self.stats = self.selected().map(|p| PassStats { name: p.name, runs: 0, changes: 0, removed: 0 }).collect();
+        self.iterations = 0;
+        
+        let mut code = code.clone();
+        while self.iterations < self.max_iterations {
+            self.iterations += 1;
+            
+            let mut changed = false;
+            for (i, stats) in self.pipeline.iter().zip(self.stats.iter_mut()) {
+                let optimized = run_pass(&code, self.registry[*i].pass);
+                
+                stats.runs += 1;
+                if optimized != code {
+                    stats.changes += 1;
+                    stats.removed += len(&code) - len(&optimized);
+                    
+                    changed = true;
+                    code = optimized;
+                }
+            }
+            
+            if !changed {
+                break;
+            }
+        }
+        
+        code
+    }
+    
+    /// Returns the statistics of the last run.
+    #[must_use]
+    pub fn stats(&self) -> &[PassStats] {
+        &self.stats
+    }
+    
+    /// Returns a displayable summary of the last run.
+    pub const fn report(&self) -> Report<'_, T> {
+        Report(self)
+    }
+    
+    fn index(&self, name: &str) -> Result<usize, UnknownPass> {
+        self.registry.iter().position(|p| p.name == name).ok_or_else(|| UnknownPass {
+            name: name.to_owned(),
+            known: self.registry.iter().map(|p| p.name).collect(),
+        })
+    }
+}
+
+impl<T: Integer> Default for PassManager<T> {
+    fn default() -> Self {
+        PassManager::new()
+    }
+}
+
+/// The signed length of some code.
+fn len<T: Integer>(code: &RoCode<T>) -> isize {
+    isize::try_from(code.len()).expect("integer overflow")
+}
+
+/// The per-pass statistics of a [`PassManager`]'s last run.
+#[derive(Debug)]
+#[must_use]
+pub struct Report<'pm, T: Integer>(&'pm PassManager<T>);
+
+impl<T: Integer> Display for Report<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let width = self.0.stats.iter().map(|s| s.name.len()).max().unwrap_or_default().max("pass".len());
+        
+        writeln!(f, "{:width$}  {:>5}  {:>7}  {:>7}", "pass", "runs", "changed", "removed")?;
+        for s in &self.0.stats {
+            writeln!(f, "{:width$}  {:>5}  {:>7}  {:>7}", s.name, s.runs, s.changes, s.removed)?;
+        }
+        
+        write!(f, "{} iteration{}", self.0.iterations, if self.0.iterations == 1 { "" } else { "s" })
+    }
+}
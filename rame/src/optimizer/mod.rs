@@ -1,10 +1,20 @@
-#![cfg(not(feature = "indirect_jumps"))]
-
+mod error;
+#[cfg(feature = "indirect_jumps")]
+mod indirect;
+mod manager;
+mod reduce;
 mod rw;
+mod validate;
 
 use crate::model::{Integer, RoCode};
 
+pub use error::UnknownPass;
+#[cfg(feature = "indirect_jumps")]
+pub use indirect::jump_targets;
+pub use manager::{NamedPass, OptLevel, PassManager, PassStats, Report};
+pub use reduce::reduce;
 pub use rw::WoCode;
+pub use validate::{Mismatch, Outcome, Validator};
 
 pub type Pass<T> = fn(&mut WoCode<'_, T>);
 
@@ -15,25 +25,27 @@ pub fn run_pass<T: Integer>(target: &'_ RoCode<T>, pass: Pass<T>) -> RoCode<T> {
     (&target).into()
 }
 
-/// Run all optimization passes on the specified code.
+/// Run all optimization passes on the specified code, until a fixed point is reached.
+#[must_use]
 pub fn run_passes<T: Integer>(target: &'_ RoCode<T>) -> RoCode<T> {
-    // FIXME: avoid copying
-    let target = run_pass( target, pass::remove_nops);
-    let target = run_pass(&target, pass::simplify_jumps);
-    let target = run_pass(&target, pass::remove_unreachable_code);
-    let target = run_pass(&target, pass::fold_consts);
-    target
+    PassManager::new().run(target)
 }
 
 pub mod pass {
-    use crate::model::{Address, Instruction, Integer};
+    use crate::model::{Instruction, Integer, Ir};
     use crate::optimizer::WoCode;
     
     mod dce;
+    #[cfg(feature = "indirect_jumps")]
+    mod devirt;
     mod fold;
+    mod thread;
     
     pub use dce::remove_unreachable_code;
+    #[cfg(feature = "indirect_jumps")]
+    pub use devirt::resolve_indirect_jumps;
     pub use fold::fold_consts;
+    pub use thread::thread_jumps;
     
     /// Remove all [`Instruction::Nop`].
     pub fn remove_nops<T: Integer>(target: &mut WoCode<'_, T>) {
@@ -47,11 +59,11 @@ pub mod pass {
     /// Simplify jumps by following unconditional ones.
     pub fn simplify_jumps<T: Integer>(target: &mut WoCode<'_, T>) {
         // Follow jumps, returning an unconditional jump target.
-        let final_adr = |initial_adr: Address| -> Option<Address> {
+        let final_adr = |initial_adr: Ir| -> Option<Ir> {
             let mut path = vec![initial_adr];
             
             let mut adr = initial_adr;
-            while let Some(Instruction::Jump(to)) = target.inner.get(adr) {
+            while let Some(to) = target.inner.get(adr).filter(|inst| matches!(inst, Instruction::Jump(_))).and_then(Instruction::target) {
                 if path.contains(&to) {
                     // infinite loop detected!
                     return None;
@@ -65,7 +77,7 @@ pub mod pass {
         };
         
         for (ir, inst) in target.inner.enumerate() {
-            if let Some(adr) = inst.jump().and_then(final_adr) {
+            if let Some(adr) = inst.target().and_then(final_adr) {
                 target.set_ir(ir, inst.map_adr(|_| adr));
             }
         }
@@ -76,7 +88,9 @@ pub mod pass {
 mod test {
     use super::*;
     use crate::inst;
-    use crate::model::Ir;
+    use crate::model::{Ir, SourceMap};
+    use crate::runner::RunError;
+    use std::str::FromStr;
     
     #[test]
     fn remove_nops() {
@@ -111,6 +125,68 @@ mod test {
         assert!(!rewriter.can_combine(Ir::new(1), Ir::new(2)));
     }
     
+    #[test]
+    fn insert_and_delete() {
+        let code = RoCode::<i32>::from([
+            inst!(READ),
+            inst!(JUMZ 4),
+            inst!(MUL #2),
+            inst!(JUMP 5),
+            inst!(LOAD #1),
+            inst!(WRITE),
+            inst!(STOP),
+        ]);
+        
+        let mut rewriter = WoCode::from(&code);
+        rewriter.set_ir(Ir::new(2), inst!(STORE 1));
+        rewriter.insert_after(Ir::new(2), inst!(ADD 1));
+        rewriter.insert_before(Ir::new(4), inst!(WRITE));
+        rewriter.delete_ir(Ir::new(0));
+        rewriter.delete_ir(Ir::new(0));
+        rewriter.insert_before(Ir::new(0), inst!(LOAD #3));
+        
+        let expected = RoCode::<i32>::from([
+            inst!(LOAD #3),
+            inst!(JUMZ 5),
+            inst!(STORE 1),
+            inst!(ADD 1),
+            inst!(JUMP 7),
+            inst!(WRITE),
+            inst!(LOAD #1),
+            inst!(WRITE),
+            inst!(STOP),
+        ]);
+        
+        assert_eq!(RoCode::from(&rewriter), expected);
+    }
+    
+    #[test]
+    fn insert_jump() {
+        let code = RoCode::<i32>::from([
+            inst!(READ),
+            inst!(NOP),
+            inst!(NOP),
+            inst!(WRITE),
+            inst!(STOP),
+        ]);
+        
+        let mut rewriter = WoCode::from(&code);
+        rewriter.delete_ir(Ir::new(1));
+        rewriter.delete_ir(Ir::new(2));
+        rewriter.insert_after(Ir::new(0), inst!(JUMZ 3));
+        rewriter.insert_before(Ir::new(3), inst!(LOAD #0));
+        
+        let expected = RoCode::<i32>::from([
+            inst!(READ),
+            inst!(JUMZ 2),
+            inst!(LOAD #0),
+            inst!(WRITE),
+            inst!(STOP),
+        ]);
+        
+        assert_eq!(RoCode::from(&rewriter), expected);
+    }
+    
     #[test]
     fn remove_identities() {
         let a = RoCode::<i32>::from([
@@ -144,6 +220,41 @@ mod test {
         assert_eq!(run_pass(&a, pass::fold_consts), b);
     }
     
+    #[test]
+    fn keep_notes() {
+        let a = RoCode::<i32>::from_str("
+            READ    ; x
+            NOP     ; TEST (x > 0)
+            ADD #1  ; x + 1
+            ADD #2  ; + 2
+            JUMP 5
+            WRITE   ; PRINT x
+        ").unwrap();
+        
+        assert_eq!(run_passes(&a).to_string(), "READ ; x\nADD #3 ; x + 1, + 2\nWRITE ; PRINT x");
+    }
+    
+    #[test]
+    fn keep_locations() {
+        let map = SourceMap::from_str("0\ta.algo\t1\t\n1\ta.algo\t2\t\n3\ta.algo\t3\t\n5\ta.algo\t4\t").unwrap();
+        let a = RoCode::<i32>::from([
+            inst!(READ),
+            inst!(ADD #1),
+            inst!(ADD #2),
+            inst!(NOP),
+            inst!(JUMP 5),
+            inst!(WRITE),
+        ]).with_source_map(&map);
+        
+        assert_eq!(run_passes(&a).source_map().to_string(), "0\ta.algo\t1\t\n1\ta.algo\t2\t\n2\ta.algo\t4\t\n");
+        
+        let a = RoCode::<i32>::from_str("READ\n\nADD #1\nADD #2\nWRITE").unwrap().with_path("a.ram");
+        let b = run_passes(&a);
+        assert_eq!(b.lines(Ir::new(1)), Some(3..=4));
+        assert_eq!(b.origin(Ir::new(1)), "a.ram:3-4");
+        assert_eq!(b.origin(Ir::new(2)), "a.ram:5");
+    }
+    
     #[test]
     fn jump_block_combine_consts() {
         let a = RoCode::<i32>::from([
@@ -193,6 +304,328 @@ mod test {
         assert_eq!(run_pass(&a, pass::simplify_jumps), a);
     }
     
+    #[test]
+    fn thread_comparisons() {
+        // IF x < 3 THEN PRINT x FI
+        let a = RoCode::<i32>::from([
+            inst!(READ),
+            inst!(SUB #3),
+            inst!(JUML 5),
+            inst!(LOAD #0),
+            inst!(JUMP 6),
+            inst!(LOAD #1),
+            inst!(JUMZ 9),
+            inst!(WRITE),
+            inst!(STOP),
+            inst!(STOP),
+        ]);
+        
+        let b = RoCode::<i32>::from([
+            inst!(READ),
+            inst!(SUB #3),
+            inst!(JUML 5),
+            inst!(LOAD #0),
+            inst!(JUMP 8),
+            inst!(LOAD #1),
+            inst!(WRITE),
+            inst!(STOP),
+            inst!(STOP),
+        ]);
+        
+        assert_eq!(run_pass(&run_pass(&a, pass::thread_jumps), pass::thread_jumps), b);
+        
+        let mut pm = PassManager::new();
+        pm.select(["thread_jumps"]).unwrap();
+        assert_eq!(pm.run(&a), b);
+        assert_eq!(pm.stats(), [PassStats { name: "thread_jumps", runs: 3, changes: 2, removed: 1 }]);
+    }
+    
+    #[test]
+    fn remove_jumps_to_next() {
+        let a = RoCode::<i32>::from([
+            inst!(READ),
+            inst!(JUMZ 3),
+            inst!(JUMP 3),
+            inst!(WRITE),
+            inst!(JUMP 5),
+            inst!(STOP),
+        ]);
+        
+        let b = RoCode::<i32>::from([
+            inst!(READ),
+            inst!(WRITE),
+            inst!(STOP),
+        ]);
+        
+        assert_eq!(run_pass(&a, pass::thread_jumps), b);
+    }
+    
+    #[test]
+    fn invert_branches() {
+        let a = RoCode::<i32>::from([
+            inst!(READ),
+            inst!(JUML 5),
+            inst!(JUMZ 4),
+            inst!(JUMP 6),
+            inst!(WRITE),
+            inst!(STOP),
+            inst!(STOP),
+        ]);
+        
+        let b = RoCode::<i32>::from([
+            inst!(READ),
+            inst!(JUML 4),
+            inst!(JUMG 5),
+            inst!(WRITE),
+            inst!(STOP),
+            inst!(STOP),
+        ]);
+        
+        assert_eq!(run_pass(&a, pass::thread_jumps), b);
+    }
+    
+    #[test]
+    fn select_passes() {
+        let code = RoCode::<i32>::from([
+            inst!(NOP),
+            inst!(ADD #1),
+            inst!(ADD #2),
+            inst!(STOP),
+        ]);
+        
+        let mut pm = PassManager::with_level(OptLevel::O0);
+        assert_eq!(pm.run(&code), code);
+        
+        pm.select(["fold_consts", "remove_nops"]).unwrap();
+        assert_eq!(pm.run(&code), RoCode::from([inst!(ADD #3), inst!(STOP)]));
+        
+        pm.disable("fold_consts").unwrap();
+        assert_eq!(pm.run(&code), RoCode::from([inst!(ADD #1), inst!(ADD #2), inst!(STOP)]));
+        
+        pm.select(["fold_const"]).unwrap_err();
+        pm.disable("nop").unwrap_err();
+    }
+    
+    #[test]
+    #[cfg(feature = "indirect_jumps")]
+    fn indirect_jump_targets() {
+        let code = RoCode::<i32>::from([
+            inst!(READ),
+            inst!(JUMZ 4),
+            inst!(LOAD #7),
+            inst!(JUMP 5),
+            inst!(LOAD #8),
+            inst!(STORE 1),
+            inst!(JUMP @1),
+            inst!(WRITE),
+            inst!(STOP),
+        ]);
+        
+        let targets = jump_targets(&code).unwrap();
+        assert_eq!(targets.len(), 1);
+        assert_eq!(targets[&Ir::new(6)], [Ir::new(7), Ir::new(8)]);
+        
+        let unknown = RoCode::<i32>::from([
+            inst!(READ),
+            inst!(JUMP @0),
+        ]);
+        
+        assert_eq!(jump_targets(&unknown), None);
+    }
+    
+    #[test]
+    #[cfg(feature = "indirect_jumps")]
+    fn resolve_indirect_jumps() {
+        use crate::model::{Instruction, Value};
+        
+        let a = RoCode::<i32>::from([
+            inst!(LOAD #4),
+            inst!(STORE 1),
+            inst!(READ),
+            inst!(JUMP @1),
+            inst!(WRITE),
+            inst!(STOP),
+        ]);
+        
+        let b = RoCode::<i32>::from([
+            inst!(LOAD #4),
+            inst!(STORE 1),
+            inst!(READ),
+            inst!(JUMP 4),
+            inst!(WRITE),
+            inst!(STOP),
+        ]);
+        
+        assert_eq!(run_pass(&a, pass::resolve_indirect_jumps), b);
+        assert_eq!(run_passes(&a), RoCode::from([
+            inst!(LOAD #4),
+            inst!(STORE 1),
+            inst!(READ),
+            inst!(WRITE),
+            inst!(STOP),
+        ]));
+        
+        // R1 may be uninitialized
+        let c = RoCode::<i32>::from([
+            inst!(READ),
+            inst!(JUMZ 4),
+            inst!(LOAD #5),
+            inst!(STORE 1),
+            inst!(JUMP @1),
+            inst!(STOP),
+        ]);
+        
+        assert_eq!(run_pass(&c, pass::resolve_indirect_jumps), c);
+        
+        // R1 may be negative or past the end
+        for v in [-1, 100] {
+            let d = RoCode::<i32>::from([
+                inst!(READ),
+                inst!(JUMZ 4),
+                Instruction::Load(Value::Constant(v)),
+                inst!(JUMP 5),
+                inst!(LOAD #7),
+                inst!(STORE 1),
+                inst!(JUMP @1),
+                inst!(WRITE),
+                inst!(STOP),
+            ]);
+            
+            assert_eq!(run_pass(&d, pass::resolve_indirect_jumps), d);
+        }
+    }
+    
+    #[test]
+    #[cfg(feature = "indirect_jumps")]
+    fn pinned_code() {
+        let a = RoCode::<i32>::from([
+            inst!(READ),
+            inst!(JUMZ 4),
+            inst!(LOAD #7),
+            inst!(JUMP 5),
+            inst!(LOAD #9),
+            inst!(STORE 1),
+            inst!(JUMP @1),
+            inst!(ADD #1),
+            inst!(ADD #2),
+            inst!(NOP),
+            inst!(WRITE),
+            inst!(STOP),
+        ]);
+        
+        let b = RoCode::<i32>::from([
+            inst!(READ),
+            inst!(JUMZ 4),
+            inst!(LOAD #7),
+            inst!(JUMP 5),
+            inst!(LOAD #9),
+            inst!(STORE 1),
+            inst!(JUMP @1),
+            inst!(ADD #3),
+            inst!(NOP),
+            inst!(NOP),
+            inst!(WRITE),
+            inst!(STOP),
+        ]);
+        
+        assert!(WoCode::from(&a).is_pinned());
+        assert_eq!(run_passes(&a), b);
+        
+        // Nothing moves, even new jump targets
+        let mut rewriter = WoCode::from(&a);
+        rewriter.delete_ir(Ir::new(8));
+        rewriter.set_ir(Ir::new(2), inst!(JUMP 10));
+        
+        let mut expected = a.to_vec();
+        expected[2] = inst!(JUMP 10);
+        expected[8] = inst!(NOP);
+        assert_eq!(RoCode::from(&rewriter), RoCode::from(expected.as_slice()));
+        
+        // Any instruction may be jumped to
+        let c = RoCode::<i32>::from([
+            inst!(READ),
+            inst!(STORE 1),
+            inst!(ADD #1),
+            inst!(ADD #2),
+            inst!(JUMP @1),
+        ]);
+        
+        assert_eq!(run_passes(&c), c);
+    }
+    
+    #[test]
+    fn validate_passes() {
+        let code = RoCode::<i8>::from([
+            inst!(READ),
+            inst!(SUB #3),
+            inst!(JUML 5),
+            inst!(LOAD #0),
+            inst!(JUMP 6),
+            inst!(LOAD #1),
+            inst!(JUMZ 10),
+            inst!(MUL #2),
+            inst!(MUL #3),
+            inst!(WRITE),
+            inst!(STOP),
+        ]);
+        
+        let optimized = run_passes(&code);
+        Validator::new(&code, &optimized).check_random(1000, 0).unwrap();
+        
+        let looping = RoCode::<i8>::from([
+            inst!(READ),
+            inst!(JUMP 1),
+        ]);
+        
+        let mismatch = Validator::new(&code, &looping).steps(100).check_all([[1], [2]]).unwrap_err();
+        assert_eq!(mismatch.input, [1]);
+        assert!(matches!(mismatch.optimized, Outcome::Timeout { steps: 200 }));
+        
+        Validator::new(&looping, &code).check(&[1]).unwrap();
+    }
+    
+    #[test]
+    fn reduce_programs() {
+        // Deletes the loop's decrement
+        let broken = |code: &RoCode<i32>| run_pass(code, |target| for (ir, inst) in target.inner.enumerate() {
+            if inst == inst!(SUB #1) {
+                target.delete_ir(ir);
+            }
+        });
+        
+        let interesting = |code: &RoCode<i32>| {
+            matches!(Outcome::of(code, &[5], 100), Outcome::Stopped { .. }) && matches!(Outcome::of(&broken(code), &[5], 100), Outcome::Timeout { .. })
+        };
+        
+        let code = RoCode::<i32>::from([
+            inst!(READ),
+            inst!(STORE 1),
+            inst!(LOAD #3),
+            inst!(JUMZ 7),
+            inst!(SUB #1),
+            inst!(JUMP 3),
+            inst!(LOAD #4),
+            inst!(LOAD 1),
+            inst!(WRITE),
+            inst!(STOP),
+        ]);
+        
+        let reduced = reduce(&code, interesting).unwrap();
+        assert_eq!(reduced, RoCode::from([
+            inst!(READ),
+            inst!(JUMZ 4),
+            inst!(SUB #1),
+            inst!(JUMP 1),
+            inst!(STOP),
+        ]));
+        assert_eq!(reduce(&reduced, |_| false), None);
+        
+        // Jumps past the end are kept past the end
+        let code = RoCode::<i32>::from([inst!(JUMP 5), inst!(STOP)]);
+        let reduced = reduce(&code, |code| matches!(Outcome::of(code, &[], 100), Outcome::Failed { err: RunError::InexistentJump, .. })).unwrap();
+        assert_eq!(reduced, RoCode::from([inst!(JUMP 1)]));
+    }
+    
     #[test]
     fn remove_dead_code() {
         let a = RoCode::<i32>::from([
@@ -17,18 +17,30 @@ pub fn remove_unreachable_code<T: Integer>(target: &mut WoCode<'_, T>) {
 
 /// Mark reachable code starting at the specified instruction into the specified vector.
 fn find_reachable_code_ir<T: Integer>(target: &WoCode<'_, T>, mut ir: Ir, reachable: &mut Vec<bool>) {
-    if reachable[ir.inner()] {
+    if reachable.get(ir.inner()) != Some(&false) {
         return;
     }
     
     while let Some(inst) = target.inner.get(ir) {
+        if reachable[ir.inner()] {
+            return;
+        }
+        
         reachable[ir.inner()] = true;
         ir += 1;
         
-        if let Some(adr) = inst.jump() {
+        if let Some(adr) = inst.target() {
             find_reachable_code_ir(target, adr, reachable);
         }
         
+        #[cfg(feature = "indirect_jumps")]
+        if inst.indirect_jump().is_some() {
+            // may jump to any entrypoint
+            for adr in target.entrypoints() {
+                find_reachable_code_ir(target, adr, reachable);
+            }
+        }
+        
         if matches!(inst, Instruction::Stop | Instruction::Jump(_)) {
             return;
         }
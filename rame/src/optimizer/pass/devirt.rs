@@ -0,0 +1,22 @@
+//! Pass: Indirect Jump Resolution
+
+use crate::model::{Address, Instruction, Integer};
+use crate::optimizer::indirect::resolved_jumps;
+use crate::optimizer::WoCode;
+
+/// Replace indirect jumps that always go to the same instruction by direct jumps.
+///
+/// Jumps that may fail, e.g. through an uninitialized register, are kept.
+pub fn resolve_indirect_jumps<T: Integer>(target: &mut WoCode<'_, T>) {
+    for (ir, adr) in resolved_jumps(target.inner) {
+        let inst = match target.inner.get(ir) {
+            Some(Instruction::Jump(_)) => Instruction::Jump(Address::from(adr)),
+            Some(Instruction::JumpZero(_)) => Instruction::JumpZero(Address::from(adr)),
+            Some(Instruction::JumpLtz(_)) => Instruction::JumpLtz(Address::from(adr)),
+            Some(Instruction::JumpGtz(_)) => Instruction::JumpGtz(Address::from(adr)),
+            _ => unreachable!("only jumps have targets"),
+        };
+        
+        target.set_ir(ir, inst);
+    }
+}
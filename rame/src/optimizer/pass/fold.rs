@@ -1,4 +1,3 @@
-use std::cmp::Ordering;
 use crate::model::{Instruction, Integer, Ir, Value};
 use crate::optimizer::WoCode;
 
@@ -17,45 +16,65 @@ pub fn fold_consts<T: Integer>(target: &mut WoCode<'_, T>) {
     }
 }
 
+/// Returns the constant added by an instruction, as its sign (`true` if negative) and magnitude.
+fn addend<T: Integer>(inst: Option<Instruction<T>>) -> Option<(bool, T)> {
+    match inst? {
+        Instruction::Add(Value::Constant(v)) if v < T::zero() => Some((true, v.checked_neg()?)),
+        Instruction::Add(Value::Constant(v)) => Some((false, v)),
+        Instruction::Sub(Value::Constant(v)) if v < T::zero() => Some((false, v.checked_neg()?)),
+        Instruction::Sub(Value::Constant(v)) => Some((true, v)),
+        _ => None,
+    }
+}
+
+/// Returns `true` iff `v` is `-1`.
+///
+/// Multiplying or dividing by `-1` can overflow on its own, so such factors are never folded.
+fn is_minus_one<T: Integer>(v: T) -> bool {
+    T::zero().checked_sub(&T::one()) == Some(v)
+}
+
 /// Simplifies add/subs; returns where to continue the search.
+///
+/// Only constants of the same sign are folded, as `ADD #1; SUB #1` overflows where `ADD #0` doesn't.
 fn fold_adds<T: Integer>(target: &mut WoCode<'_, T>, ir0: Ir) -> Ir {
-    let mut folded = match target.inner.get(ir0) {
-        Some(Instruction::Add(Value::Constant(v))) => v,
-        Some(Instruction::Sub(Value::Constant(v))) => v.checked_neg().unwrap(),
-        _ => return ir0,
+    let Some((mut negative, mut folded)) = addend(target.inner.get(ir0)) else {
+        return ir0;
     };
     
     let mut ir1 = ir0 + 1;
-    if !target.can_combine(ir0, ir1) {
-        if folded.is_zero() {
-            target.delete_ir(ir0);
-        }
-        
-        return ir1;
-    }
-    
-    loop {
+    while target.can_combine(ir0, ir1) {
         let rhs = match target.inner.get(ir1) {
-            Some(Instruction::Add(Value::Constant(v))) => v,
-            Some(Instruction::Sub(Value::Constant(v))) => v.checked_neg().unwrap(),
-            Some(Instruction::Mul(Value::Constant(v))) if v == T::one() => T::zero(),
-            Some(Instruction::Div(Value::Constant(v))) if v == T::one() => T::zero(),
-            _ => break,
+            Some(Instruction::Mul(Value::Constant(v)) | Instruction::Div(Value::Constant(v))) if v.is_one() => Some((false, T::zero())),
+            inst => addend(inst),
+        };
+        
+        let Some((rhs_negative, rhs)) = rhs else {
+            break;
         };
         
-        target.delete_ir(ir1);
-        folded = folded + rhs;
+        if !folded.is_zero() && !rhs.is_zero() && negative != rhs_negative {
+            break;
+        }
         
-        ir1 += 1;
-        if !target.can_combine(ir0, ir1) {
+        let Some(sum) = folded.checked_add(&rhs) else {
             break;
+        };
+        
+        if folded.is_zero() {
+            negative = rhs_negative;
         }
+        
+        target.merge_ir(ir1, ir0);
+        folded = sum;
+        ir1 += 1;
     }
     
-    match folded.cmp(&T::zero()) {
-        Ordering::Less => target.set_ir(ir0, Instruction::Sub(Value::Constant(folded.checked_neg().unwrap()))),
-        Ordering::Equal => target.delete_ir(ir0),
-        Ordering::Greater => target.set_ir(ir0, Instruction::Add(Value::Constant(folded))),
+    if folded.is_zero() {
+        target.delete_ir(ir0);
+    }
+    else if ir1 != ir0 + 1 {
+        target.set_ir(ir0, if negative { Instruction::Sub(Value::Constant(folded)) } else { Instruction::Add(Value::Constant(folded)) });
     }
     
     ir1
@@ -73,35 +92,29 @@ fn fold_muls<T: Integer>(target: &mut WoCode<'_, T>, ir0: Ir) -> Ir {
         return ir1;
     }
     
-    if !target.can_combine(ir0, ir1) {
-        if folded.is_one() {
-            target.delete_ir(ir0);
-        }
-        
-        return ir1;
-    }
-    
-    loop {
+    while !is_minus_one(folded) && target.can_combine(ir0, ir1) {
         let Some(Instruction::Mul(Value::Constant(rhs))) = target.inner.get(ir1) else {
             break;
         };
         
-        target.delete_ir(ir1);
-        folded = folded * rhs;
-        
-        ir1 += 1;
-        if !target.can_combine(ir0, ir1) {
+        // `MUL #0` doesn't overflow where the previous ones did
+        if rhs.is_zero() || is_minus_one(rhs) {
             break;
         }
+        
+        let Some(product) = folded.checked_mul(&rhs) else {
+            break;
+        };
+        
+        target.merge_ir(ir1, ir0);
+        folded = product;
+        ir1 += 1;
     }
     
     if folded.is_one() {
         target.delete_ir(ir0);
     }
-    else if folded.is_zero() {
-        target.set_ir(ir0, Instruction::Load(Value::Constant(T::zero())));
-    }
-    else {
+    else if ir1 != ir0 + 1 {
         target.set_ir(ir0, Instruction::Mul(Value::Constant(folded)));
     }
     
@@ -115,32 +128,29 @@ fn fold_divs<T: Integer>(target: &mut WoCode<'_, T>, ir0: Ir) -> Ir {
     };
     
     let mut ir1 = ir0 + 1;
-    if !target.can_combine(ir0, ir1) {
-        if folded.is_one() {
-            target.delete_ir(ir0);
-        }
-        
-        return ir1;
-    }
-    
-    loop {
+    while !folded.is_zero() && !is_minus_one(folded) && target.can_combine(ir0, ir1) {
         let Some(Instruction::Div(Value::Constant(rhs))) = target.inner.get(ir1) else {
             break;
         };
         
-        target.delete_ir(ir1);
-        folded = folded * rhs;
-        
-        ir1 += 1;
-        if !target.can_combine(ir0, ir1) {
+        if rhs.is_zero() || is_minus_one(rhs) {
             break;
         }
+        
+        // `(x / a) / b == x / (a * b)`, unless `a * b` overflows
+        let Some(product) = folded.checked_mul(&rhs) else {
+            break;
+        };
+        
+        target.merge_ir(ir1, ir0);
+        folded = product;
+        ir1 += 1;
     }
     
     if folded.is_one() {
         target.delete_ir(ir0);
     }
-    else {
+    else if ir1 != ir0 + 1 {
         target.set_ir(ir0, Instruction::Div(Value::Constant(folded)));
     }
     
@@ -0,0 +1,209 @@
+//! Pass: Jump Threading
+
+use crate::model::{Address, Instruction, Integer, Ir, Loc as _, Register, Value};
+use crate::optimizer::WoCode;
+use std::ops::{BitAnd, Not};
+
+/// The possible signs of ACC.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+struct Signs(u8);
+
+impl Signs {
+    const NONE: Signs = Signs(0);
+    const NEG: Signs = Signs(0b001);
+    const ZERO: Signs = Signs(0b010);
+    const POS: Signs = Signs(0b100);
+    const ALL: Signs = Signs(0b111);
+    
+    /// The sign of a constant.
+    fn of<T: Integer>(v: T) -> Signs {
+        if v < T::zero() {
+            Signs::NEG
+        }
+        else if v.is_zero() {
+            Signs::ZERO
+        }
+        else {
+            Signs::POS
+        }
+    }
+    
+    /// The signs for which the specified instruction jumps, if it is a jump.
+    const fn taken<T: Integer>(inst: Instruction<T>) -> Signs {
+        match inst {
+            Instruction::Jump(_) => Signs::ALL,
+            Instruction::JumpZero(_) => Signs::ZERO,
+            Instruction::JumpLtz(_) => Signs::NEG,
+            Instruction::JumpGtz(_) => Signs::POS,
+            _ => Signs::NONE,
+        }
+    }
+    
+    /// Returns the conditional jump taken iff ACC has exactly these signs.
+    fn jump<T: Integer>(self, adr: Ir) -> Option<Instruction<T>> {
+        let adr = Address::from(adr);
+        match self {
+            Signs::NEG => Some(Instruction::JumpLtz(adr)),
+            Signs::ZERO => Some(Instruction::JumpZero(adr)),
+            Signs::POS => Some(Instruction::JumpGtz(adr)),
+            Signs::ALL => Some(Instruction::Jump(adr)),
+            _ => None,
+        }
+    }
+    
+    const fn is_subset(self, other: Signs) -> bool {
+        self.0 & !other.0 == 0
+    }
+}
+
+impl BitAnd for Signs {
+    type Output = Signs;
+    
+    fn bitand(self, rhs: Signs) -> Signs {
+        Signs(self.0 & rhs.0)
+    }
+}
+
+impl Not for Signs {
+    type Output = Signs;
+    
+    fn not(self) -> Signs {
+        Signs(!self.0 & Signs::ALL.0)
+    }
+}
+
+/// Thread jumps through conditional jumps whose outcome is known, remove jumps to
+/// the next instruction, and invert `JUMx a; JUMP b; a:` into a single branch when possible.
+///
+/// Targets the comparison lowering of `arc`:
+/// ```text
+/// JUML 4      ; x < y
+/// LOAD #0
+/// JUMP 5
+/// LOAD #1
+/// JUMZ else
+/// ```
+pub fn thread_jumps<T: Integer>(target: &mut WoCode<'_, T>) {
+    for ir in (0..target.inner.len()).map(Ir::new) {
+        let Some(inst) = target.get(ir) else {
+            continue;
+        };
+        
+        let Some(adr) = inst.target() else {
+            continue;
+        };
+        
+        // What's known about ACC when reaching `ir`
+        let known = signs_before(target, ir);
+        let taken = Signs::taken(inst);
+        
+        if (known & taken) == Signs::NONE {
+            // never taken
+            target.delete_ir(ir);
+            continue;
+        }
+        
+        // always taken?
+        let inst = if known.is_subset(taken) { Instruction::Jump(Address::from(adr)) } else { inst };
+        let taken = known & Signs::taken(inst);
+        
+        let adr = follow(target, adr, taken);
+        let next = target.next_ir(ir);
+        if adr == next {
+            // jumping to the next instruction
+            target.delete_ir(ir);
+            continue;
+        }
+        
+        // `JUMx a; JUMP b; a:`
+        if let Some(other @ Instruction::Jump(_)) = target.get(next).filter(|_| taken != known && target.can_combine(ir, next)) {
+            if other.target() == Some(adr) {
+                // `JUMx a; JUMP a`
+                target.delete_ir(ir);
+                continue;
+            }
+            
+            if let Some(other) = other.target().filter(|_| adr == target.next_ir(next)) {
+                if let Some(inverted) = (known & !taken).jump(other) {
+                    target.set_ir(ir, inverted);
+                    target.delete_ir(next);
+                    continue;
+                }
+            }
+        }
+        
+        let inst = inst.map_adr(|_| adr);
+        if target.get(ir) != Some(inst) {
+            target.set_ir(ir, inst);
+        }
+    }
+}
+
+/// Returns the possible signs of ACC right before executing the specified instruction,
+/// by looking at the instructions falling through into it.
+fn signs_before<T: Integer>(target: &WoCode<'_, T>, mut ir: Ir) -> Signs {
+    let mut known = Signs::ALL;
+    
+    while ir > 0 {
+        let prev = Ir::new(ir.inner() - 1);
+        if !target.can_combine(prev, ir) {
+            // something else jumps here
+            break;
+        }
+        
+        match target.get(prev) {
+            // doesn't touch ACC
+            None | Some(Instruction::Nop | Instruction::Store(_)) => {},
+            Some(Instruction::Increment(Register::Direct(loc)) | Instruction::Decrement(Register::Direct(loc))) if loc.raw() != 0 => {},
+            
+            Some(Instruction::Load(Value::Constant(v))) => return known & Signs::of(v),
+            Some(inst @ (Instruction::JumpZero(_) | Instruction::JumpLtz(_) | Instruction::JumpGtz(_))) => known = known & !Signs::taken(inst),
+            _ => break,
+        }
+        
+        ir = prev;
+    }
+    
+    known
+}
+
+/// Follows jumps whose outcome is known, returning the final address.
+fn follow<T: Integer>(target: &WoCode<'_, T>, initial_adr: Ir, known: Signs) -> Ir {
+    let mut path = vec![initial_adr];
+    let mut adr = initial_adr;
+    
+    loop {
+        let next = match target.get(adr) {
+            // deleted instructions are void
+            None | Some(Instruction::Nop) => adr + 1,
+            Some(inst @ (Instruction::Jump(_) | Instruction::JumpZero(_) | Instruction::JumpLtz(_) | Instruction::JumpGtz(_))) => {
+                let Some(to) = inst.target() else {
+                    // indirect jump
+                    break;
+                };
+                
+                let taken = Signs::taken(inst);
+                if known.is_subset(taken) {
+                    to
+                }
+                else if (known & taken) == Signs::NONE {
+                    adr + 1
+                }
+                else {
+                    break;
+                }
+            },
+            _ => break,
+        };
+        
+        if next >= target.inner.len() || path.contains(&next) {
+            // falling off the code, or infinite loop detected!
+            break;
+        }
+        
+        adr = next;
+        path.push(adr);
+    }
+    
+    adr
+}
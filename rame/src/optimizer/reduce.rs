@@ -0,0 +1,153 @@
+//! Shrinks programs while they keep a property, by delta debugging.
+
+use crate::model::{Instruction, Integer, Ir, Loc as _, Register, RoCode, Value, WoLoc, RwLoc};
+use crate::optimizer::WoCode;
+
+/// Shrinks some code while `interesting` holds, e.g. while its optimized code misbehaves.
+///
+/// Chunks of instructions are deleted, then operands are simplified and jumps are retargeted,
+/// until none of these changes keeps the code interesting.
+///
+/// Returns `None` if `code` itself isn't interesting.
+///
+/// # Example
+///
+/// ```
+/// # use rame::inst;
+/// # use rame::model::RoCode;
+/// # use rame::optimizer::{reduce, Outcome};
+/// # use rame::runner::RunError;
+/// let code = RoCode::<i32>::from([
+///     inst!(READ),
+///     inst!(STORE 1),
+///     inst!(ADD #2),
+///     inst!(WRITE),
+///     inst!(DIV #0),
+///     inst!(STOP),
+/// ]);
+///
+/// let reduced = reduce(&code, |code| matches!(Outcome::of(code, &[7], 100), Outcome::Failed { err: RunError::IntegerOverfow, .. })).unwrap();
+/// assert_eq!(reduced, RoCode::from([inst!(READ), inst!(DIV #0)]));
+/// ```
+pub fn reduce<T: Integer, P: FnMut(&RoCode<T>) -> bool>(code: &RoCode<T>, mut interesting: P) -> Option<RoCode<T>> {
+    if !interesting(code) {
+        return None;
+    }
+    
+    let mut code = code.clone();
+    while delete_chunks(&mut code, &mut interesting) | simplify(&mut code, &mut interesting) {}
+    
+    Some(code)
+}
+
+/// Applies some changes to a copy of `code`.
+fn rewrite<T: Integer, F: FnOnce(&mut WoCode<'_, T>)>(code: &RoCode<T>, f: F) -> RoCode<T> {
+    let mut target = WoCode::from(code);
+    f(&mut target);
+    (&target).into()
+}
+
+/// Deletes chunks of instructions, from halves of the code to single instructions.
+///
+/// Returns `true` iff some instructions were deleted.
+fn delete_chunks<T: Integer, P: FnMut(&RoCode<T>) -> bool>(code: &mut RoCode<T>, interesting: &mut P) -> bool {
+    let mut changed = false;
+    let mut chunk = code.len().div_ceil(2);
+    
+    while chunk > 0 {
+        let mut start = 0;
+        while start < code.len() {
+            let end = code.len().min(start + chunk);
+            let candidate = rewrite(code, |target| (start..end).for_each(|ir| target.delete_ir(Ir::new(ir))));
+            
+            // Pinned code keeps its length, deleted instructions become `NOP`
+            if candidate != *code && interesting(&candidate) {
+                *code = candidate;
+                changed = true;
+            }
+            else {
+                start += chunk;
+            }
+        }
+        
+        chunk /= 2;
+    }
+    
+    changed
+}
+
+/// Replaces instructions by simpler ones, one at a time.
+///
+/// Returns `true` iff some instructions were replaced.
+fn simplify<T: Integer, P: FnMut(&RoCode<T>) -> bool>(code: &mut RoCode<T>, interesting: &mut P) -> bool {
+    let mut changed = false;
+    let mut ir = Ir::default();
+    
+    while let Some(inst) = code.get(ir) {
+        let simplified = simpler(inst, ir).into_iter()
+            .map(|inst| rewrite(code, |target| target.set_ir(ir, inst)))
+            .find(|candidate| interesting(candidate));
+        
+        match simplified {
+            // The new instruction may be simplified further
+            Some(candidate) => {
+                *code = candidate;
+                changed = true;
+            },
+            None => ir += 1,
+        }
+    }
+    
+    changed
+}
+
+/// Returns the simpler variants of an instruction, the simplest first.
+///
+/// A variant is never simplified back into the original instruction, so that the reduction terminates.
+fn simpler<T: Integer>(inst: Instruction<T>, ir: Ir) -> Vec<Instruction<T>> {
+    let mut variants = match inst {
+        Instruction::Load(v) => simpler_values(v).into_iter().map(Instruction::Load).collect(),
+        Instruction::Add(v) => simpler_values(v).into_iter().map(Instruction::Add).collect(),
+        Instruction::Sub(v) => simpler_values(v).into_iter().map(Instruction::Sub).collect(),
+        Instruction::Mul(v) => simpler_values(v).into_iter().map(Instruction::Mul).collect(),
+        Instruction::Div(v) => simpler_values(v).into_iter().map(Instruction::Div).collect(),
+        Instruction::Mod(v) => simpler_values(v).into_iter().map(Instruction::Mod).collect(),
+        Instruction::Store(Register::Indirect(ptr)) => vec![Instruction::Store(Register::Direct(WoLoc::from(ptr.raw())))],
+        Instruction::Increment(Register::Indirect(ptr)) => vec![Instruction::Increment(Register::Direct(RwLoc::from(ptr.raw())))],
+        Instruction::Decrement(Register::Indirect(ptr)) => vec![Instruction::Decrement(Register::Direct(RwLoc::from(ptr.raw())))],
+        Instruction::JumpZero(adr) | Instruction::JumpLtz(adr) | Instruction::JumpGtz(adr) => vec![Instruction::Jump(adr)],
+        _ => Vec::new(),
+    };
+    
+    // A jump to the next instruction can then be deleted
+    if inst.target().is_some_and(|adr| adr != ir + 1) {
+        variants.push(inst.map_adr(|_| ir + 1));
+    }
+    
+    variants
+}
+
+/// Returns the simpler variants of a value, the simplest first.
+fn simpler_values<T: Integer>(v: Value<T>) -> Vec<Value<T>> {
+    match v {
+        Value::Constant(c) => {
+            let half = c / (T::one() + T::one());
+            
+            // Going towards zero, `-1` being simplified into `1`
+            let mut values = Vec::new();
+            if c != T::zero() {
+                values.push(T::zero());
+            }
+            if c != T::zero() && c != T::one() {
+                values.push(T::one());
+            }
+            if half != T::zero() && half != T::one() {
+                values.push(half);
+            }
+            
+            values.into_iter().map(Value::Constant).collect()
+        },
+        Value::Register(Register::Indirect(ptr)) => vec![Value::Constant(T::zero()), Value::Register(Register::Direct(ptr))],
+        Value::Register(Register::Direct(_)) => vec![Value::Constant(T::zero())],
+    }
+}
@@ -1,5 +1,10 @@
 use std::collections::HashMap;
-use crate::model::{Address, Instruction, Integer, Ir, RoCode};
+use std::iter;
+use std::ops::RangeInclusive;
+use crate::model::{Instruction, Integer, Ir, Location, RoCode};
+
+#[cfg(feature = "indirect_jumps")]
+use crate::model::Address;
 
 /// Represents a write-only code segment.
 #[derive(Debug)]
@@ -9,18 +14,47 @@ pub struct WoCode<'ro, T: Integer> {
     
     /// First element is the index of a jumped-to instruction, second is the count of instructions added/removed up to that instruction.
     /// This vector is sorted by `Ir`.
-    deltas: Vec<(Address, isize)>,
+    deltas: Vec<(Ir, isize)>,
     
     /// List of deleted IRs.
     deleted_ir: Vec<Ir>,
     
     /// List of modified IRs + their new instruction.
     modified_ir: HashMap<Ir, Instruction<T>>,
+    
+    /// List of IRs + the instructions inserted right before them.
+    /// Jumping to such an IR lands on the first inserted instruction.
+    inserted_before: HashMap<Ir, Vec<Instruction<T>>>,
+    
+    /// List of IRs + the instructions inserted right after them.
+    inserted_after: HashMap<Ir, Vec<Instruction<T>>>,
+    
+    /// List of IRs + the deleted IRs merged into them, whose notes are appended to theirs.
+    merged_ir: HashMap<Ir, Vec<Ir>>,
+    
+    /// `true` iff the instructions can't move, see [`WoCode::is_pinned`].
+    pinned: bool,
 }
 
 impl<'ro, T: Integer> From<&'ro RoCode<T>> for WoCode<'ro, T> {
     fn from(target: &'ro RoCode<T>) -> Self {
-        let mut deltas = target.iter().filter_map(Instruction::jump).map(|entrypoint| (entrypoint, 0)).collect::<Vec<_>>();
+        let mut deltas = target.iter().filter_map(Instruction::target).map(|entrypoint| (entrypoint, 0)).collect::<Vec<_>>();
+        
+        // The values of the registers can't be rewritten, so the code can't move
+        #[cfg(feature = "indirect_jumps")]
+        let pinned = target.iter().any(|inst| inst.indirect_jump().is_some());
+        #[cfg(not(feature = "indirect_jumps"))]
+        let pinned = false;
+        
+        #[cfg(feature = "indirect_jumps")]
+        if pinned {
+            match super::jump_targets(target) {
+                Some(targets) => deltas.extend(targets.into_values().flatten().map(|entrypoint| (entrypoint, 0))),
+                // any instruction may be jumped to
+                None => deltas.extend((0..target.len()).map(|entrypoint| (Ir::new(entrypoint), 0))),
+            }
+        }
+        
         deltas.sort_unstable();
         deltas.push((Ir::new(target.len()), 0));
         deltas.dedup();
@@ -30,6 +64,10 @@ impl<'ro, T: Integer> From<&'ro RoCode<T>> for WoCode<'ro, T> {
             deltas,
             deleted_ir: Vec::new(),
             modified_ir: HashMap::new(),
+            inserted_before: HashMap::new(),
+            inserted_after: HashMap::new(),
+            merged_ir: HashMap::new(),
+            pinned,
         }
     }
 }
@@ -41,38 +79,140 @@ impl<T: Integer> WoCode<'_, T> {
         
         if let Err(i) = self.deleted_ir.binary_search(&ir) {
             self.deleted_ir.insert(i, ir);
+            
+            // Shift instructions left
+            if !self.pinned {
+                self.shift(ir, -1);
+            }
         }
+    }
+    
+    /// Mark the specified [`Ir`] as deleted, because it was combined into `into`.
+    ///
+    /// Its note is appended to the note of `into`, while the notes of [deleted IRs](WoCode::delete_ir) are dropped.
+    pub fn merge_ir(&mut self, ir: Ir, into: Ir) {
+        self.delete_ir(ir);
+        self.merged_ir.entry(into).or_default().push(ir);
+    }
+    
+    /// Edit the specified [`Ir`]'s [`Instruction`], keeping its note.
+    pub fn set_ir(&mut self, ir: Ir, inst: Instruction<T>) {
+        assert!(self.deleted_ir.binary_search(&ir).is_err());
+        
+        self.add_entrypoint(inst);
+        self.modified_ir.insert(ir, inst);
+    }
+    
+    /// Insert an instruction right before the specified [`Ir`], and after
+    /// the instructions previously inserted before it.
+    ///
+    /// Jumps to `ir` will execute the inserted instruction.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the code [is pinned.](WoCode::is_pinned)
+    pub fn insert_before(&mut self, ir: Ir, inst: Instruction<T>) {
+        assert!(ir < self.inner.len());
+        assert!(!self.pinned, "can't move pinned code");
+        
+        self.add_entrypoint(inst);
+        self.inserted_before.entry(ir).or_default().push(inst);
+        
+        // Shift instructions right
+        self.shift(ir, 1);
+    }
+    
+    /// Insert an instruction right after the specified [`Ir`], and after
+    /// the instructions previously inserted after it.
+    ///
+    /// Jumps to `ir + 1` will not execute the inserted instruction.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the code [is pinned.](WoCode::is_pinned)
+    pub fn insert_after(&mut self, ir: Ir, inst: Instruction<T>) {
+        assert!(ir < self.inner.len());
+        assert!(!self.pinned, "can't move pinned code");
         
-        // Shift instructions left
-        for (entry_point, delta) in self.deltas.iter_mut().rev() {
-            if *entry_point > ir {
-                *delta -= 1;
+        self.add_entrypoint(inst);
+        self.inserted_after.entry(ir).or_default().push(inst);
+        
+        // Shift instructions right
+        self.shift(ir, 1);
+    }
+    
+    /// Adds `delta` to the entrypoints after the specified [`Ir`].
+    fn shift(&mut self, ir: Ir, delta: isize) {
+        for (entrypoint, d) in self.deltas.iter_mut().rev() {
+            if *entrypoint > ir {
+                *d += delta;
             } else {
                 break;
             }
         }
     }
     
-    /// Edit the specified [`Ir`]'s [`Instruction`].
-    pub fn set_ir(&mut self, ir: Ir, inst: Instruction<T>) {
-        assert!(self.deleted_ir.binary_search(&ir).is_err());
-        self.modified_ir.insert(ir, inst);
+    /// Registers the target of a new or retargeted jump as an entrypoint.
+    fn add_entrypoint(&mut self, inst: Instruction<T>) {
+        let Some(adr) = inst.target() else {
+            return;
+        };
+        
+        let Err(i) = self.deltas.binary_search_by_key(&adr, |(entrypoint, _)| *entrypoint) else {
+            return;
+        };
+        
+        if self.pinned {
+            // Nothing moves
+            self.deltas.insert(i, (adr, 0));
+            return;
+        }
+        
+        let deleted = self.deleted_ir.partition_point(|deleted| *deleted < adr);
+        let inserted = self.inserted_before.iter().chain(self.inserted_after.iter())
+            .filter(|(ir, _)| **ir < adr)
+            .map(|(_, insts)| insts.len())
+            .sum::<usize>();
+        
+        let delta = isize::try_from(inserted).expect("integer overflow") - isize::try_from(deleted).expect("integer overflow");
+        self.deltas.insert(i, (adr, delta));
+    }
+    
+    /// Returns the current instruction at the specified [`Ir`],
+    /// or `None` if it was deleted or is out of bounds.
+    #[must_use]
+    pub fn get(&self, ir: Ir) -> Option<Instruction<T>> {
+        if self.deleted_ir.binary_search(&ir).is_ok() {
+            return None;
+        }
+        
+        self.modified_ir.get(&ir).copied().or_else(|| self.inner.get(ir))
     }
     
     /// Returns the next non-deleted IR.
-    pub fn next_ir(&mut self, ir0: Ir) -> Ir {
-        match self.deleted_ir.binary_search(&(ir0 + 1)) {
-            Err(_) => ir0 + 1,
-            Ok(i) => {
-                let mut d = 1;
-                loop {
-                    d += 1;
-                    if self.deleted_ir[i + d] > ir0 + d {
-                        break ir0 + d
-                    }
-                }
-            }
+    pub fn next_ir(&self, ir0: Ir) -> Ir {
+        let mut ir = ir0 + 1;
+        while self.deleted_ir.binary_search(&ir).is_ok() {
+            ir += 1;
         }
+        
+        ir
+    }
+    
+    /// Returns `true` iff the instructions can't move, because the code has
+    /// indirect jumps.
+    ///
+    /// Deleted instructions are then replaced by [`Instruction::Nop`],
+    /// and no instruction can be inserted.
+    #[must_use]
+    pub const fn is_pinned(&self) -> bool {
+        self.pinned
+    }
+    
+    /// Returns all the instructions that may be jumped to.
+    #[cfg(feature = "indirect_jumps")]
+    pub(super) fn entrypoints(&self) -> impl Iterator<Item = Ir> + '_ {
+        self.deltas.iter().map(|(entrypoint, _)| *entrypoint).filter(|entrypoint| *entrypoint < self.inner.len())
     }
     
     /// Returns `true` iff there's no jump entrypoints in `]ir0, ir1]`.
@@ -95,8 +235,17 @@ impl<T: Integer> WoCode<'_, T> {
 }
 
 impl<T: Integer> Instruction<T> {
-    /// Map all jump targets.
-    pub(super) fn map_adr<F: Fn(Address) -> Address>(self, f: F) -> Instruction<T> {
+    /// Map all constant jump targets.
+    pub(super) fn map_adr<F: Fn(Ir) -> Ir>(self, f: F) -> Instruction<T> {
+        #[cfg(not(feature = "indirect_jumps"))]
+        let f = &f;
+        
+        #[cfg(feature = "indirect_jumps")]
+        let f = |adr| match adr {
+            Address::Constant(ir) => Address::Constant(f(ir)),
+            Address::Register(_) => adr,
+        };
+        
         match self {
             Instruction::Jump(adr) => Instruction::Jump(f(adr)),
             Instruction::JumpZero(adr) => Instruction::JumpZero(f(adr)),
@@ -110,7 +259,7 @@ impl<T: Integer> Instruction<T> {
 impl<'ro, T: Integer> From<&WoCode<'ro, T>> for RoCode<T> {
     fn from(code: &WoCode<'ro, T>) -> Self {
         /// Rewrite an jump address.
-        fn update_adr(deltas: &Vec<(Ir, isize)>, adr: Address) -> Address {
+        fn update_adr(deltas: &Vec<(Ir, isize)>, adr: Ir) -> Ir {
             let mut delta = 0;
             while delta < deltas.len() && deltas[delta].0 < adr {
                 delta += 1;
@@ -119,14 +268,63 @@ impl<'ro, T: Integer> From<&WoCode<'ro, T>> for RoCode<T> {
             adr.checked_add_signed(deltas[delta].1).expect("integer overflow")
         }
         
-        code.inner.enumerate()
-            // delete
-            .filter(|(ir, _)| code.deleted_ir.binary_search(ir).is_err())
-            // edit
-            .map(|(ir, inst)| (ir, code.modified_ir.get(&ir).copied().unwrap_or(inst)))
-            // rewrite adrs
-            .map(|(_, inst)| inst.map_adr(|adr| update_adr(&code.deltas, adr)))
-            // collect
-            .collect::<Vec<_>>().as_slice().into()
+        /// Returns the instructions inserted at the specified IR, without notes but at its lines and source location.
+        fn inserted<'c, T: Integer>(code: &'c WoCode<'_, T>, inserted: &'c HashMap<Ir, Vec<Instruction<T>>>, ir: Ir) -> impl Iterator<Item = Emitted<'c, T>> + 'c {
+            inserted.get(&ir).into_iter().flatten().map(move |inst| (*inst, None, code.inner.lines(ir), code.inner.location(ir)))
+        }
+        
+        /// Returns the note of the specified IR, followed by the notes of the IRs merged into it.
+        fn note<T: Integer>(code: &WoCode<'_, T>, ir: Ir) -> Option<String> {
+            let merged = code.merged_ir.get(&ir).into_iter().flatten();
+            let notes = iter::once(&ir).chain(merged).filter_map(|ir| code.inner.note(*ir)).collect::<Vec<_>>();
+            
+            (!notes.is_empty()).then(|| notes.join(", "))
+        }
+        
+        /// Returns the lines of the specified IR, spanning the lines of the IRs merged into it.
+        fn span<T: Integer>(code: &WoCode<'_, T>, ir: Ir) -> Option<RangeInclusive<usize>> {
+            let merged = code.merged_ir.get(&ir).into_iter().flatten();
+            iter::once(&ir).chain(merged)
+                .filter_map(|ir| code.inner.lines(*ir))
+                .reduce(|a, b| *a.start().min(b.start())..=*a.end().max(b.end()))
+        }
+        
+        /// An instruction, its note, its lines and its source location.
+        type Emitted<'c, T> = (Instruction<T>, Option<String>, Option<RangeInclusive<usize>>, Option<&'c Location>);
+        
+        let mut notes = Vec::new();
+        let mut lines = Vec::new();
+        let mut locations = Vec::new();
+        let insts = code.inner.enumerate()
+            // delete and edit
+            .map(|(ir, inst)| (ir, match code.deleted_ir.binary_search(&ir) {
+                Err(_) => Some((code.modified_ir.get(&ir).copied().unwrap_or(inst), note(code, ir), span(code, ir), code.inner.location(ir))),
+                Ok(_) if code.pinned => Some((Instruction::Nop, None, None, None)),
+                Ok(_) => None,
+            }))
+            // insert
+            .flat_map(|(ir, inst)| inserted(code, &code.inserted_before, ir).chain(inst).chain(inserted(code, &code.inserted_after, ir)))
+            // rewrite adrs, and collect the notes, lines and locations
+            .enumerate()
+            .map(|(i, (inst, note, span, location))| {
+                if let Some(note) = note {
+                    notes.push((Ir::new(i), note));
+                }
+                if let Some(span) = span {
+                    lines.push((Ir::new(i), span));
+                }
+                if let Some(location) = location {
+                    locations.push((Ir::new(i), location.clone()));
+                }
+                
+                inst.map_adr(|adr| update_adr(&code.deltas, adr))
+            })
+            .collect::<Vec<_>>();
+        
+        let rewritten = RoCode::from(insts.as_slice()).with_notes(notes).with_lines(lines).with_locations(locations);
+        match code.inner.path() {
+            Some(path) => rewritten.with_path(path),
+            None => rewritten,
+        }
     }
 }
@@ -0,0 +1,175 @@
+use crate::fuzz::Rng;
+use crate::model::{Integer, RoCode};
+use crate::runner::{Ram, RunError};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::mem;
+
+/// How a program ended.
+#[derive(Debug)]
+pub enum Outcome<T: Integer> {
+    /// The program stopped.
+    Stopped { output: Vec<T> },
+    
+    /// The program failed.
+    Failed { output: Vec<T>, err: RunError<T> },
+    
+    /// The program was still running after the specified count of steps.
+    Timeout { steps: usize },
+}
+
+impl<T: Integer> Outcome<T> {
+    /// Runs some code on the specified input, for at most `steps` instructions.
+    pub fn of(code: &RoCode<T>, input: &[T], steps: usize) -> Outcome<T> {
+        if code.is_empty() {
+            // `Ram` can't be created without instructions
+            return Outcome::Failed { output: Vec::new(), err: RunError::Eof };
+        }
+        
+        let mut ram = Ram::new(code.clone(), input.iter().copied());
+        match ram.run_for(steps) {
+            Ok(true) => Outcome::Stopped { output: ram.into() },
+            Ok(false) => Outcome::Timeout { steps },
+            Err(err) => Outcome::Failed { output: ram.into(), err },
+        }
+    }
+    
+    /// Returns `true` iff both outcomes have the same output, and the same kind of error.
+    #[must_use]
+    pub fn matches(&self, other: &Outcome<T>) -> bool {
+        match (self, other) {
+            (Outcome::Stopped { output: a }, Outcome::Stopped { output: b }) => a == b,
+            (Outcome::Failed { output: a, err: e }, Outcome::Failed { output: b, err: f }) => a == b && mem::discriminant(e) == mem::discriminant(f),
+            (Outcome::Timeout { .. }, Outcome::Timeout { .. }) => true,
+            _ => false,
+        }
+    }
+}
+
+impl<T: Integer> Display for Outcome<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Stopped { output } => write!(f, "stopped with output {output:?}"),
+            Outcome::Failed { output, err } => write!(f, "failed with `{err}` after output {output:?}"),
+            Outcome::Timeout { steps } => write!(f, "didn't stop after {steps} steps"),
+        }
+    }
+}
+
+/// The error type returned by [`Validator`], reporting an input for which
+/// the optimized code doesn't behave as the original code.
+#[derive(Debug)]
+pub struct Mismatch<T: Integer> {
+    pub input: Vec<T>,
+    pub original: Outcome<T>,
+    pub optimized: Outcome<T>,
+}
+
+impl<T: Integer> Display for Mismatch<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "with input {:?}, the original code {}, but the optimized code {}", self.input, self.original, self.optimized)
+    }
+}
+
+impl<T: Integer> Error for Mismatch<T> {}
+
+/// Checks that optimized code behaves like its original code, by running both.
+///
+/// Inputs for which the original code doesn't stop within the step budget are inconclusive, and are skipped.
+///
+/// # Example
+///
+/// ```
+/// # use rame::inst;
+/// # use rame::model::RoCode;
+/// # use rame::optimizer::Validator;
+/// let code = RoCode::<i32>::from([
+///     inst!(READ),
+///     inst!(DIV #2),
+///     inst!(DIV #3),
+///     inst!(WRITE),
+///     inst!(STOP),
+/// ]);
+///
+/// let wrong = RoCode::<i32>::from([
+///     inst!(READ),
+///     inst!(DIV #5),
+///     inst!(WRITE),
+///     inst!(STOP),
+/// ]);
+///
+/// let validator = Validator::new(&code, &wrong);
+/// validator.check(&[12]).unwrap();
+///
+/// let mismatch = validator.check(&[30]).unwrap_err();
+/// assert_eq!(mismatch.to_string(), "with input [30], the original code stopped with output [5], but the optimized code stopped with output [6]");
+/// ```
+#[derive(Copy, Clone, Debug)]
+#[must_use]
+pub struct Validator<'c, T: Integer> {
+    original: &'c RoCode<T>,
+    optimized: &'c RoCode<T>,
+    
+    /// Maximum count of instructions to run for the original code.
+    steps: usize,
+}
+
+impl<'c, T: Integer> Validator<'c, T> {
+    /// Creates a new validator, with a budget of `100_000` steps.
+    pub const fn new(original: &'c RoCode<T>, optimized: &'c RoCode<T>) -> Validator<'c, T> {
+        Validator {
+            original,
+            optimized,
+            steps: 100_000,
+        }
+    }
+    
+    /// Sets the maximum count of instructions to run for the original code.
+    ///
+    /// The optimized code may run twice as many instructions.
+    pub const fn steps(&mut self, steps: usize) -> &mut Self {
+        self.steps = steps;
+        self
+    }
+    
+    /// Compares both codes on the specified input.
+    pub fn check(&self, input: &[T]) -> Result<(), Mismatch<T>> {
+        let original = Outcome::of(self.original, input, self.steps);
+        if let Outcome::Timeout { .. } = original {
+            return Ok(());
+        }
+        
+        let optimized = Outcome::of(self.optimized, input, self.steps.saturating_mul(2));
+        if original.matches(&optimized) {
+            Ok(())
+        }
+        else {
+            Err(Mismatch {
+                input: input.to_vec(),
+                original,
+                optimized,
+            })
+        }
+    }
+    
+    /// Compares both codes on the specified inputs, stopping at the first mismatch.
+    pub fn check_all<I: IntoIterator<Item: AsRef<[T]>>>(&self, inputs: I) -> Result<(), Mismatch<T>> {
+        inputs.into_iter().try_for_each(|input| self.check(input.as_ref()))
+    }
+    
+    /// Compares both codes on `count` random inputs, stopping at the first mismatch.
+    ///
+    /// The inputs are generated from `seed`, and contains from `0` to `8` values,
+    /// mixing small values with values near the bounds of `T`.
+    pub fn check_random(&self, count: usize, seed: u64) -> Result<(), Mismatch<T>> {
+        let mut rng = Rng::new(seed);
+        
+        for _ in 0..count {
+            let len = rng.below(9);
+            let input = (0..len).map(|_| rng.value()).collect::<Vec<T>>();
+            self.check(&input)?;
+        }
+        
+        Ok(())
+    }
+}
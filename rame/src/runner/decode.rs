@@ -0,0 +1,233 @@
+//! Instructions decoded for [`Ram::run`], see [`Op`].
+
+use crate::model::{Instruction, Integer, Ir, Loc as _, Register, RoCode, Value};
+use crate::runner::mem::Loc;
+use crate::runner::Ram;
+use std::cell::Cell;
+
+/// Where an operand is read from.
+#[derive(Copy, Clone, Debug)]
+pub(super) enum Operand<T: Integer> {
+    Constant(T),
+    Direct(usize),
+    /// The register whose value is the address of the operand.
+    Indirect(usize),
+}
+
+/// Where an operand is written to.
+#[derive(Copy, Clone, Debug)]
+pub(super) enum Target {
+    Direct(usize),
+    /// The register whose value is the address of the target.
+    Indirect(usize),
+}
+
+/// An instruction with its operand and jump target resolved.
+///
+/// Every instruction that may fail or access registers past the memory is still run by [`Ram::step`],
+/// so that the errors are reported the same way.
+#[derive(Copy, Clone, Debug)]
+pub(super) enum Op<T: Integer> {
+    Read,
+    Write,
+    Load(Operand<T>),
+    Store(Target),
+    Increment(Target),
+    Decrement(Target),
+    Add(Operand<T>),
+    Sub(Operand<T>),
+    Mul(Operand<T>),
+    Div(Operand<T>),
+    Mod(Operand<T>),
+    Jump(usize),
+    JumpZero(usize),
+    JumpLtz(usize),
+    JumpGtz(usize),
+    Stop,
+    Nop,
+    
+    /// Always run by [`Ram::step`], e.g. a jump past the end of the program.
+    Step,
+}
+
+impl<T: Integer> Operand<T> {
+    fn new(v: Value<T>) -> Operand<T> {
+        match v {
+            Value::Constant(v) => Operand::Constant(v),
+            Value::Register(Register::Direct(adr)) => Operand::Direct(adr.raw()),
+            Value::Register(Register::Indirect(ptr)) => Operand::Indirect(ptr.raw()),
+        }
+    }
+    
+    /// Returns the value of the operand, `None` if [`Ram::step`] should read it.
+    #[inline(always)]
+    fn get(self, cells: &[Cell<Loc<T>>]) -> Option<T> {
+        match self {
+            Operand::Constant(v) => Some(v),
+            Operand::Direct(adr) => read(cells, adr),
+            Operand::Indirect(ptr) => read(cells, read(cells, ptr)?.try_into().ok()?),
+        }
+    }
+}
+
+impl Target {
+    fn new<L: crate::model::Loc>(reg: Register<L>) -> Target {
+        match reg {
+            Register::Direct(adr) => Target::Direct(adr.raw()),
+            Register::Indirect(ptr) => Target::Indirect(ptr.raw()),
+        }
+    }
+    
+    /// Returns the written register, `None` if [`Ram::step`] should write it.
+    #[inline(always)]
+    fn get<T: Integer>(self, cells: &[Cell<Loc<T>>]) -> Option<&Cell<Loc<T>>> {
+        match self {
+            Target::Direct(adr) => cells.get(adr),
+            Target::Indirect(ptr) => cells.get(read(cells, ptr)?.try_into().ok()?),
+        }
+    }
+}
+
+#[inline(always)]
+fn read<T: Integer>(cells: &[Cell<Loc<T>>], adr: usize) -> Option<T> {
+    cells.get(adr)?.get().value()
+}
+
+/// Decodes some code for [`Ram::run`].
+pub(super) fn decode<T: Integer>(code: &RoCode<T>) -> Box<[Op<T>]> {
+    let len = code.len();
+    let target = |adr: Ir| adr.inner() < len;
+    
+    code.enumerate().map(|(ir, inst)| {
+        // Running past the end of the program fails
+        let falls = !matches!(inst, Instruction::Stop | Instruction::Jump(_));
+        if falls && ir.inner() + 1 == len {
+            return Op::Step;
+        }
+        
+        match inst {
+            Instruction::Read => Op::Read,
+            Instruction::Write => Op::Write,
+            Instruction::Load(v) => Op::Load(Operand::new(v)),
+            Instruction::Store(reg) => Op::Store(Target::new(reg)),
+            Instruction::Increment(reg) => Op::Increment(Target::new(reg)),
+            Instruction::Decrement(reg) => Op::Decrement(Target::new(reg)),
+            Instruction::Add(v) => Op::Add(Operand::new(v)),
+            Instruction::Sub(v) => Op::Sub(Operand::new(v)),
+            Instruction::Mul(v) => Op::Mul(Operand::new(v)),
+            Instruction::Div(v) => Op::Div(Operand::new(v)),
+            Instruction::Mod(v) => Op::Mod(Operand::new(v)),
+            Instruction::Stop => Op::Stop,
+            Instruction::Nop => Op::Nop,
+            Instruction::Jump(_) | Instruction::JumpZero(_) | Instruction::JumpLtz(_) | Instruction::JumpGtz(_) => {
+                let Some(adr) = inst.target().filter(|adr| target(*adr)) else {
+                    // Either indirect or inexistent
+                    return Op::Step;
+                };
+                
+                let adr = adr.inner();
+                match inst {
+                    Instruction::Jump(_) => Op::Jump(adr),
+                    Instruction::JumpZero(_) => Op::JumpZero(adr),
+                    Instruction::JumpLtz(_) => Op::JumpLtz(adr),
+                    _ => Op::JumpGtz(adr),
+                }
+            },
+        }
+    }).collect()
+}
+
+impl<T: Integer, I: Iterator<Item = T>> Ram<T, I> {
+    /// Runs the decoded code until the program stops, returning `true`,
+    /// or until an instruction must be run by [`Ram::step`], returning `false`.
+    ///
+    /// Only dense memory is supported, without any memcheck, read-only register or watchpoint.
+    pub(super) fn run_decoded(&mut self) -> bool {
+        if self.hooked() {
+            return false;
+        }
+        
+        let Some(memory) = self.memory.get_mut().as_dense() else {
+            return false;
+        };
+        
+        let code = &self.code;
+        let ops = self.decoded.get_or_insert_with(|| decode(code));
+        let cells = memory.cells();
+        let mut ir = self.ir.inner();
+        
+        macro_rules! arith {
+            ($v:expr, $f:path) => {{
+                let Some(acc) = read(cells, 0) else { break false };
+                let Some(v) = $v.get(cells) else { break false };
+                let Some(r) = $f(&acc, &v) else { break false };
+                cells[0].set(Loc::Init(r));
+            }};
+        }
+        
+        macro_rules! unop {
+            ($reg:expr, $f:path) => {{
+                let Some(cell) = $reg.get(cells) else { break false };
+                let Some(v) = cell.get().value() else { break false };
+                let Some(r) = $f(&v, &T::one()) else { break false };
+                cell.set(Loc::Init(r));
+            }};
+        }
+        
+        macro_rules! jump_if {
+            ($adr:expr, $cond:expr) => {{
+                let Some(acc) = read(cells, 0) else { break false };
+                if $cond(acc) {
+                    ir = $adr;
+                    continue;
+                }
+            }};
+        }
+        
+        let stopped = loop {
+            match ops[ir] {
+                Op::Read => {
+                    let Some(v) = self.input.next() else { break false };
+                    cells[0].set(Loc::Init(v));
+                },
+                Op::Write => {
+                    let Some(acc) = read(cells, 0) else { break false };
+                    self.output.push(acc);
+                },
+                Op::Load(v) => {
+                    let Some(v) = v.get(cells) else { break false };
+                    cells[0].set(Loc::Init(v));
+                },
+                Op::Store(reg) => {
+                    let Some(acc) = read(cells, 0) else { break false };
+                    let Some(cell) = reg.get(cells) else { break false };
+                    cell.set(Loc::Init(acc));
+                },
+                Op::Increment(reg) => unop!(reg, T::checked_add),
+                Op::Decrement(reg) => unop!(reg, T::checked_sub),
+                Op::Add(v) => arith!(v, T::checked_add),
+                Op::Sub(v) => arith!(v, T::checked_sub),
+                Op::Mul(v) => arith!(v, T::checked_mul),
+                Op::Div(v) => arith!(v, T::checked_div),
+                Op::Mod(v) => arith!(v, T::checked_rem),
+                Op::Jump(adr) => {
+                    ir = adr;
+                    continue;
+                },
+                Op::JumpZero(adr) => jump_if!(adr, |acc: T| acc.is_zero()),
+                Op::JumpLtz(adr) => jump_if!(adr, |acc: T| acc < T::zero()),
+                Op::JumpGtz(adr) => jump_if!(adr, |acc: T| acc > T::zero()),
+                Op::Stop => break true,
+                Op::Nop => {},
+                Op::Step => break false,
+            }
+            
+            // Instructions that may run past the end are `Op::Step`
+            ir += 1;
+        };
+        
+        self.ir = Ir::new(ir);
+        self.inst = self.code.get(self.ir).expect("decoded code only jumps to existing instructions");
+        stopped
+    }
+}
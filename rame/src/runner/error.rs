@@ -16,6 +16,12 @@ pub enum RunError<T: Integer> {
     /// but the intermediate register's value wasn't a valid address.
     InvalidAddress { adr: T, err: <T as TryInto<usize>>::Error },
     
+    /// A register past the [memory limit](crate::runner::Ram::with_memory_limit) was written.
+    MemoryLimit { adr: usize, limit: usize },
+    
+    /// A [read-only](crate::runner::Ram::with_read_only) register was written.
+    ReadOnly { adr: usize },
+    
     /// An arithmetic instruction overflowed.
     IntegerOverfow,
     
@@ -41,6 +47,8 @@ impl<T: Integer> Clone for RunError<T> where <T as TryInto<usize>>::Error: Clone
             RunError::ReadEof => RunError::ReadEof,
             RunError::ReadUninit { adr } => RunError::ReadUninit { adr: *adr },
             RunError::InvalidAddress { adr, err } => RunError::InvalidAddress { adr: *adr, err: err.clone() },
+            RunError::MemoryLimit { adr, limit } => RunError::MemoryLimit { adr: *adr, limit: *limit },
+            RunError::ReadOnly { adr } => RunError::ReadOnly { adr: *adr },
             RunError::IntegerOverfow => RunError::IntegerOverfow,
             RunError::InexistentJump => RunError::InexistentJump,
             #[cfg(feature = "indirect_jumps")] RunError::InvalidJump { err } => RunError::InvalidJump { err: err.clone() },
@@ -57,6 +65,8 @@ impl<T: Integer> PartialEq for RunError<T> where <T as TryInto<usize>>::Error: P
             RunError::ReadEof => matches!(other, RunError::ReadEof),
             RunError::ReadUninit { adr } => matches!(other, RunError::ReadUninit { adr: adr1 } if adr == adr1),
             RunError::InvalidAddress { adr, err } => matches!(other, RunError::InvalidAddress { adr: adr1, err: err1 } if adr == adr1 && err == err1),
+            RunError::MemoryLimit { adr, limit } => matches!(other, RunError::MemoryLimit { adr: adr1, limit: limit1 } if adr == adr1 && limit == limit1),
+            RunError::ReadOnly { adr } => matches!(other, RunError::ReadOnly { adr: adr1 } if adr == adr1),
             RunError::IntegerOverfow => matches!(other, RunError::IntegerOverfow),
             RunError::InexistentJump => matches!(other, RunError::InexistentJump),
             #[cfg(feature = "indirect_jumps")]  RunError::InvalidJump { err } => matches!(other, RunError::InvalidJump { err: err1 } if err == err1),
@@ -65,12 +75,33 @@ impl<T: Integer> PartialEq for RunError<T> where <T as TryInto<usize>>::Error: P
     }
 }
 
+impl<T: Integer> RunError<T> {
+    /// Returns the name of `self`'s variant, e.g. `IntegerOverfow`.
+    #[must_use]
+    pub const fn kind(&self) -> &'static str {
+        match self {
+            RunError::ReadEof => "ReadEof",
+            RunError::ReadUninit { .. } => "ReadUninit",
+            RunError::InvalidAddress { .. } => "InvalidAddress",
+            RunError::MemoryLimit { .. } => "MemoryLimit",
+            RunError::ReadOnly { .. } => "ReadOnly",
+            RunError::IntegerOverfow => "IntegerOverfow",
+            RunError::InexistentJump => "InexistentJump",
+            #[cfg(feature = "indirect_jumps")]
+            RunError::InvalidJump { .. } => "InvalidJump",
+            RunError::Eof => "Eof",
+        }
+    }
+}
+
 impl<T: Integer> Display for RunError<T> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
             RunError::ReadEof => f.write_str("nothing left to read"),
             RunError::ReadUninit { ref adr } => write!(f, "reading uninitialized memory R{adr}"),
             RunError::InvalidAddress { ref adr, err } => write!(f, "invalid address R{adr}: {err}"),
+            RunError::MemoryLimit { adr, limit } => write!(f, "accessing R{adr} exceeds the memory limit of {limit} registers"),
+            RunError::ReadOnly { adr } => write!(f, "writing read-only memory R{adr}"),
             RunError::IntegerOverfow => f.write_str("integer overflow"),
             RunError::InexistentJump => f.write_str("jumping to an inexistent location"),
             #[cfg(feature = "indirect_jumps")]
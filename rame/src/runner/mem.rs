@@ -1,15 +1,298 @@
+//! The memory of a [`Ram`], and how it's stored, see [`Memory`].
+
 use crate::model::{self, Address, Instruction, Integer, Ir, Register, RoLoc, WoLoc, Value, RwLoc};
 use crate::runner::{Ram, RunError};
 use std::cell::Cell;
+use std::collections::HashMap;
 use std::fmt;
-use std::fmt::{Display, Formatter};
+use std::fmt::{Debug, Display, Formatter};
+use std::mem;
 
+/// A register.
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
-pub(super) enum Loc<T: Integer> {
+pub enum Loc<T: Integer> {
     #[default] Uninit,
     Init(T)
 }
 
+impl<T: Integer> Loc<T> {
+    /// Returns the value, `None` if uninitialized.
+    pub const fn value(self) -> Option<T> {
+        match self {
+            Loc::Uninit => None,
+            Loc::Init(v) => Some(v),
+        }
+    }
+}
+
+/// Stores the registers of a [`Ram`], see [`Ram::with_memory_backend`].
+///
+/// A register that isn't allocated is [`Loc::Uninit`], like an allocated register that was never written.
+/// ACC, the register `0`, is always allocated.
+pub trait Memory<T: Integer>: Debug {
+    /// Returns the register at `adr`, `None` if it isn't allocated.
+    fn get(&self, adr: usize) -> Option<&Cell<Loc<T>>>;
+    
+    /// Returns the register at `adr`, allocating it if needed,
+    /// or `None` if more than `limit` registers would then be allocated.
+    fn get_or_alloc(&mut self, adr: usize, limit: usize) -> Option<&Cell<Loc<T>>>;
+    
+    /// Returns ACC.
+    fn acc(&self) -> &Cell<Loc<T>>;
+    
+    /// Returns `self` if it's a [`DenseMemory`], which [`Ram::run`] accesses directly.
+    fn as_dense(&mut self) -> Option<&mut DenseMemory<T>> {
+        None
+    }
+}
+
+/// How a [`Ram`]'s memory is stored, see [`Ram::with_memory`].
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub enum MemoryLayout {
+    /// [`AutoMemory`].
+    #[default] Auto,
+    
+    /// [`DenseMemory`].
+    Dense,
+    
+    /// [`SparseMemory`].
+    Sparse,
+}
+
+impl MemoryLayout {
+    /// Returns an empty memory with this layout.
+    #[must_use]
+    pub fn memory<T: Integer>(self) -> Box<dyn Memory<T>> {
+        match self {
+            MemoryLayout::Auto => Box::new(AutoMemory::default()),
+            MemoryLayout::Dense => Box::new(DenseMemory::default()),
+            MemoryLayout::Sparse => Box::new(SparseMemory::default()),
+        }
+    }
+}
+
+/// The default maximum count of registers of a [`Ram`], see [`Ram::with_memory_limit`].
+pub const DEFAULT_MEMORY_LIMIT: usize = 1 << 20;
+
+/// A vector up to the highest register accessed, the fastest for most programs.
+///
+/// All the registers up to `adr` count towards the memory limit when `adr` is accessed.
+#[derive(Clone, Debug)]
+pub struct DenseMemory<T: Integer> {
+    cells: Vec<Cell<Loc<T>>>,
+}
+
+impl<T: Integer> Default for DenseMemory<T> {
+    fn default() -> Self {
+        DenseMemory { cells: vec![Cell::new(Loc::Uninit)] }
+    }
+}
+
+impl<T: Integer> DenseMemory<T> {
+    /// Returns the allocated registers.
+    pub(super) fn cells(&self) -> &[Cell<Loc<T>>] {
+        &self.cells
+    }
+}
+
+impl<T: Integer> Memory<T> for DenseMemory<T> {
+    #[inline]
+    fn get(&self, adr: usize) -> Option<&Cell<Loc<T>>> {
+        self.cells.get(adr)
+    }
+    
+    #[inline]
+    fn get_or_alloc(&mut self, adr: usize, limit: usize) -> Option<&Cell<Loc<T>>> {
+        if adr >= self.cells.len() {
+            #[cold]
+            #[inline(never)]
+            fn resize_mem<T: Integer>(cells: &mut Vec<Cell<Loc<T>>>, new_len: usize) {
+                cells.resize(new_len, Cell::new(Loc::Uninit));
+            }
+            
+            if adr >= limit {
+                return None;
+            }
+            
+            resize_mem(&mut self.cells, adr + 1);
+        }
+        
+        self.cells.get(adr)
+    }
+    
+    #[inline]
+    fn acc(&self) -> &Cell<Loc<T>> {
+        &self.cells[0]
+    }
+    
+    fn as_dense(&mut self) -> Option<&mut DenseMemory<T>> {
+        Some(self)
+    }
+}
+
+/// The count of registers of a page of [`SparseMemory`].
+const PAGE_LEN: usize = 256;
+
+/// Only the pages of 256 registers that were accessed, for programs using far-apart addresses.
+///
+/// All the registers of a page count towards the memory limit when one of them is accessed,
+/// like ACC, which is stored apart.
+#[derive(Clone, Debug)]
+pub struct SparseMemory<T: Integer> {
+    acc: Cell<Loc<T>>,
+    pages: HashMap<usize, Box<[Cell<Loc<T>>]>>,
+}
+
+impl<T: Integer> SparseMemory<T> {
+    fn page() -> Box<[Cell<Loc<T>>]> {
+        vec![Cell::new(Loc::Uninit); PAGE_LEN].into_boxed_slice()
+    }
+}
+
+impl<T: Integer> Default for SparseMemory<T> {
+    fn default() -> Self {
+        SparseMemory { acc: Cell::new(Loc::Uninit), pages: HashMap::new() }
+    }
+}
+
+impl<T: Integer> From<DenseMemory<T>> for SparseMemory<T> {
+    /// Keeps ACC, and the pages holding initialized registers.
+    fn from(dense: DenseMemory<T>) -> Self {
+        let mut sparse = SparseMemory { acc: dense.cells[0].clone(), pages: HashMap::new() };
+        for (adr, cell) in dense.cells.into_iter().enumerate().skip(1) {
+            if cell.get() != Loc::Uninit {
+                sparse.pages.entry(adr / PAGE_LEN).or_insert_with(Self::page)[adr % PAGE_LEN] = cell;
+            }
+        }
+        
+        sparse
+    }
+}
+
+impl<T: Integer> Memory<T> for SparseMemory<T> {
+    fn get(&self, adr: usize) -> Option<&Cell<Loc<T>>> {
+        if adr == 0 {
+            return Some(&self.acc);
+        }
+        
+        self.pages.get(&(adr / PAGE_LEN)).map(|page| &page[adr % PAGE_LEN])
+    }
+    
+    fn get_or_alloc(&mut self, adr: usize, limit: usize) -> Option<&Cell<Loc<T>>> {
+        if adr == 0 {
+            return Some(&self.acc);
+        }
+        
+        let pages = self.pages.len();
+        if !self.pages.contains_key(&(adr / PAGE_LEN)) && 1 + (pages + 1) * PAGE_LEN > limit {
+            return None;
+        }
+        
+        let page = self.pages.entry(adr / PAGE_LEN).or_insert_with(Self::page);
+        Some(&page[adr % PAGE_LEN])
+    }
+    
+    fn acc(&self) -> &Cell<Loc<T>> {
+        &self.acc
+    }
+}
+
+/// Accessing a register from this address, twice as far as the dense memory's length,
+/// makes [`AutoMemory`] switch to sparse memory.
+const SPARSE_FROM: usize = 1 << 16;
+
+/// [`DenseMemory`], until a register far past the others is accessed, e.g. by `STORE @1` with a huge `R1`,
+/// then [`SparseMemory`].
+#[derive(Clone, Debug)]
+pub enum AutoMemory<T: Integer> {
+    Dense(DenseMemory<T>),
+    Sparse(SparseMemory<T>),
+}
+
+impl<T: Integer> Default for AutoMemory<T> {
+    fn default() -> Self {
+        AutoMemory::Dense(DenseMemory::default())
+    }
+}
+
+impl<T: Integer> Memory<T> for AutoMemory<T> {
+    #[inline]
+    fn get(&self, adr: usize) -> Option<&Cell<Loc<T>>> {
+        match self {
+            AutoMemory::Dense(memory) => memory.get(adr),
+            AutoMemory::Sparse(memory) => memory.get(adr),
+        }
+    }
+    
+    #[inline]
+    fn get_or_alloc(&mut self, adr: usize, limit: usize) -> Option<&Cell<Loc<T>>> {
+        if let AutoMemory::Dense(dense) = self {
+            if adr >= SPARSE_FROM && adr / 2 >= dense.cells.len() {
+                let AutoMemory::Dense(dense) = mem::replace(self, AutoMemory::Sparse(SparseMemory::default())) else {
+                    unreachable!()
+                };
+                *self = AutoMemory::Sparse(dense.into());
+            }
+        }
+        
+        match self {
+            AutoMemory::Dense(memory) => memory.get_or_alloc(adr, limit),
+            AutoMemory::Sparse(memory) => memory.get_or_alloc(adr, limit),
+        }
+    }
+    
+    #[inline]
+    fn acc(&self) -> &Cell<Loc<T>> {
+        match self {
+            AutoMemory::Dense(memory) => memory.acc(),
+            AutoMemory::Sparse(memory) => memory.acc(),
+        }
+    }
+    
+    fn as_dense(&mut self) -> Option<&mut DenseMemory<T>> {
+        match self {
+            AutoMemory::Dense(memory) => Some(memory),
+            AutoMemory::Sparse(_) => None,
+        }
+    }
+}
+
+/// How a register is accessed.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Access {
+    Read,
+    Write,
+    /// Both read and written, e.g. by `INC`.
+    ReadWrite,
+}
+
+impl Access {
+    /// Returns `true` iff `self` and `other` have an access in common, e.g. `Read` and `ReadWrite`.
+    #[must_use]
+    pub const fn overlaps(self, other: Access) -> bool {
+        !matches!((self, other), (Access::Read, Access::Write) | (Access::Write, Access::Read))
+    }
+}
+
+/// An access to a watched register, see [`Ram::with_watchpoint`].
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Watch {
+    /// The instruction that accessed the register.
+    pub ir: Ir,
+    pub adr: usize,
+    pub access: Access,
+}
+
+/// Where the value of a memory location comes from, see [`Ram::with_memcheck`].
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub(super) struct Shadow {
+    /// The instruction that last wrote the location.
+    pub written: Ir,
+    
+    /// The instruction that computed the value, e.g. the `ADD` before a `STORE`.
+    pub computed: Ir,
+}
+
 #[derive(Clone, Eq, PartialEq, Debug)]
 pub(super) struct LocEntry<'ram, T: Integer, L: model::Loc> {
     pub adr: L,
@@ -72,12 +355,12 @@ impl<T: Integer> Value<T> {
 impl<L: model::Loc> Register<L> {
     pub(super) fn loc<'ram, T: Integer, I: Iterator<Item = T>>(&self, ram: &'ram Ram<T, I>) -> Result<LocEntry<'ram, T, L>, RunError<T>> {
         match *self {
-            Register::Direct(n) => Ok(ram.loc(n)),
+            Register::Direct(n) => ram.loc(n),
             Register::Indirect(n) => {
-                let adr = ram.loc(n).get()?;
+                let adr = ram.loc(n)?.get()?;
                 
                 match adr.try_into() {
-                    Ok(adr) => Ok(ram.loc(L::from(adr))),
+                    Ok(adr) => ram.loc(L::from(adr)),
                     Err(err) => Err(RunError::InvalidAddress { adr, err }),
                 }
             }
@@ -103,12 +386,12 @@ impl Address {
     pub(super) fn get<T: Integer, I: Iterator<Item = T>>(&self, ram: &Ram<T, I>) -> Result<(Ir, Instruction<T>), RunError<T>> {
         #[cfg(not(feature = "indirect_jumps"))]
         let ir = *self;
-
+        
         #[cfg(feature = "indirect_jumps")]
         let ir = match *self {
             Address::Constant(adr) => adr,
             Address::Register(adr) => {
-                let adr = ram.loc(adr).get()?;
+                let adr = ram.loc(adr)?.get()?;
                 adr.try_into().map(Ir::new).map_err(|err| RunError::InvalidJump { err })?
             }
         };
@@ -127,6 +410,16 @@ impl<T: Integer> Display for Loc<T> {
     }
 }
 
+impl Display for Watch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.access {
+            Access::Read => write!(f, "R{} is read", self.adr),
+            Access::Write => write!(f, "R{} is written", self.adr),
+            Access::ReadWrite => write!(f, "R{} is read and written", self.adr),
+        }
+    }
+}
+
 impl<T: Integer, L: model::Loc> Display for LocEntry<'_, T, L> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         write!(f, "R{} = {}", self.adr, self.inner.get())
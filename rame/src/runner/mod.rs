@@ -1,19 +1,21 @@
 //! An emulator for RAM programs.
 
-use crate::error::{format_err, format_help};
-use crate::model::{self, Address, Instruction, Integer, Ir, ParseCodeError, Register, RoCode, RoLoc, RwLoc, Value};
-use crate::runner::mem::{Loc, LocEntry};
-use std::cell::{Cell, UnsafeCell};
-use std::hint::assert_unchecked;
+use crate::diagnostic::{Diagnostic, Format, Level};
+use crate::model::{self, Address, Instruction, Integer, Ir, Loc as _, ParseCodeError, Register, RoCode, RoLoc, RwLoc, Value};
+use crate::runner::decode::Op;
+use crate::runner::mem::{LocEntry, Memory, Shadow};
+use std::cell::UnsafeCell;
+use std::collections::BTreeMap;
 use std::iter::{self, Fuse};
+use std::ops::Range;
 use std::process::exit;
 
+mod decode;
 mod error;
-mod mem;
+pub mod mem;
 
 pub use error::RunError;
-
-type Memory<T> = UnsafeCell<Vec<Cell<Loc<T>>>>;
+pub use mem::{Access, MemoryLayout, Watch, DEFAULT_MEMORY_LIMIT};
 
 /// A process for a RAM program.
 ///
@@ -40,13 +42,32 @@ type Memory<T> = UnsafeCell<Vec<Cell<Loc<T>>>>;
 pub struct Ram<T: Integer, I: Iterator<Item = T>> {
     input: Fuse<I>,
     output: Vec<T>,
-    memory: Memory<T>,
+    memory: UnsafeCell<Box<dyn Memory<T>>>,
     code: RoCode<T>,
     
     /// The next instruction to run.
     inst: Instruction<T>,
     /// Instruction register (the index of `inst`).
     ir: Ir,
+    
+    /// Where the value of each memory location comes from, if enabled.
+    shadow: Option<BTreeMap<usize, Shadow>>,
+    
+    /// The maximum count of registers.
+    memory_limit: usize,
+    /// How the memory is stored, `None` for a custom [`Memory`].
+    layout: Option<MemoryLayout>,
+    
+    /// `code` decoded for [`Ram::run`], once run.
+    decoded: Option<Box<[Op<T>]>>,
+    
+    /// The ranges of registers that can't be written.
+    read_only: Vec<Range<usize>>,
+    /// The watched registers, and how.
+    watchpoints: BTreeMap<usize, Access>,
+    
+    /// How [`Ram::run`] prints its errors and watchpoints.
+    error_format: Format,
 }
 
 impl<T: Integer, I: Iterator<Item = T>> Ram<T, I> {
@@ -65,15 +86,165 @@ impl<T: Integer, I: Iterator<Item = T>> Ram<T, I> {
         Ram {
             input: input.into_iter().fuse(),
             output: Vec::default(),
-            memory: Memory::default(),
+            memory: UnsafeCell::new(MemoryLayout::default().memory()),
             code,
             inst,
             ir: Ir::default(),
+            shadow: None,
+            memory_limit: DEFAULT_MEMORY_LIMIT,
+            layout: Some(MemoryLayout::default()),
+            decoded: None,
+            read_only: Vec::new(),
+            watchpoints: BTreeMap::new(),
+            error_format: Format::default(),
         }
     }
     
+    /// Tracks where the value of each memory location comes from, like memcheck.
+    ///
+    /// Reading uninitialized memory through an indirect register then reports the instruction
+    /// that computed the address, the last write to the pointer register, and whether the target
+    /// was ever written, e.g. for compiled array indexing such as `LOAD 1`, `ADD #base`, `LOAD @0`.
+    pub fn with_memcheck(mut self, memcheck: bool) -> Ram<T, I> {
+        self.shadow = memcheck.then(BTreeMap::new);
+        self
+    }
+    
+    /// Replaces the memory with an empty one stored as specified, [`MemoryLayout::Auto`] by default.
+    pub fn with_memory(mut self, layout: MemoryLayout) -> Ram<T, I> {
+        self.memory = UnsafeCell::new(layout.memory());
+        self.layout = Some(layout);
+        self
+    }
+    
+    /// Replaces the memory with the specified one.
+    pub fn with_memory_backend<M: Memory<T> + 'static>(mut self, memory: M) -> Ram<T, I> {
+        self.memory = UnsafeCell::new(Box::new(memory));
+        self.layout = None;
+        self
+    }
+    
+    /// Sets the maximum count of registers, ACC included, [`DEFAULT_MEMORY_LIMIT`] by default.
+    ///
+    /// Allocating more registers fails with [`RunError::MemoryLimit`], see the [`Memory`] implementations
+    /// for what's allocated when a register is written. Registers are never allocated to be read,
+    /// reading a register that isn't allocated failing with [`RunError::ReadUninit`] whatever the memory.
+    pub const fn with_memory_limit(mut self, limit: usize) -> Ram<T, I> {
+        self.memory_limit = limit;
+        self
+    }
+    
+    /// Sets how [`Ram::run`] prints its errors and watchpoints, [`Format::Human`] by default.
+    pub const fn with_error_format(mut self, format: Format) -> Ram<T, I> {
+        self.error_format = format;
+        self
+    }
+    
+    /// Makes writing to the specified registers fail with [`RunError::ReadOnly`],
+    /// e.g. for registers holding constants.
+    pub fn with_read_only(mut self, registers: Range<usize>) -> Ram<T, I> {
+        self.read_only.push(registers);
+        self
+    }
+    
+    /// Makes [`Ram::step`] return a [`Watch`] when the register at `adr` is accessed as specified,
+    /// e.g. to find which instruction overwrites a stack pointer.
+    pub fn with_watchpoint(mut self, adr: usize, access: Access) -> Ram<T, I> {
+        self.watchpoints.insert(adr, access);
+        self
+    }
+    
     /// Executes the next instruction.
-    pub fn step(&mut self) -> Result<(), RunError<T>> {
+    ///
+    /// Returns the first access of the instruction to a watched register, if any, see [`Ram::with_watchpoint`].
+    pub fn step(&mut self) -> Result<Option<Watch>, RunError<T>> {
+        if !self.hooked() {
+            return self.exec().map(|()| None);
+        }
+        
+        let ir = self.ir;
+        let write = self.shadow_write();
+        if let Some((adr, _)) = write.filter(|(adr, _)| self.read_only.iter().any(|range| range.contains(adr))) {
+            return Err(RunError::ReadOnly { adr });
+        }
+        
+        let reads = if self.watchpoints.is_empty() { Vec::new() } else { self.reads() };
+        self.exec()?;
+        
+        if let (Some((adr, shadow)), Some(shadows)) = (write, &mut self.shadow) {
+            shadows.insert(adr, shadow);
+        }
+        
+        let write = write.map(|(adr, _)| adr);
+        let watch = reads.iter().copied().chain(write).find_map(|adr| {
+            let access = match (reads.contains(&adr), write == Some(adr)) {
+                (true, true) => Access::ReadWrite,
+                (true, false) => Access::Read,
+                (false, _) => Access::Write,
+            };
+            
+            let watched = self.watchpoints.get(&adr).is_some_and(|watch| watch.overlaps(access));
+            watched.then_some(Watch { ir, adr, access })
+        });
+        
+        Ok(watch)
+    }
+    
+    /// Returns `true` iff memcheck, read-only registers or watchpoints are enabled.
+    fn hooked(&self) -> bool {
+        self.shadow.is_some() || !self.read_only.is_empty() || !self.watchpoints.is_empty()
+    }
+    
+    /// Returns the memory locations the next instruction reads, the pointers of indirect registers included.
+    fn reads(&self) -> Vec<usize> {
+        let mut reads = Vec::with_capacity(3);
+        if self.inst.should_print_acc() || matches!(self.inst, Instruction::Write | Instruction::Store(_)) {
+            reads.push(0);
+        }
+        
+        if let Some(reg) = self.inst.register() {
+            if let Register::Indirect(ptr) = reg {
+                reads.push(ptr.raw());
+            }
+            reads.extend(self.resolve(reg));
+        }
+        
+        if let Instruction::Store(Register::Indirect(ptr)) = self.inst {
+            reads.push(ptr.raw());
+        }
+        
+        reads
+    }
+    
+    /// Returns the memory location the next instruction writes, and where its value comes from.
+    fn shadow_write(&self) -> Option<(usize, Shadow)> {
+        let ir = self.ir;
+        let shadow = |adr: usize| self.shadow.as_ref().and_then(|shadows| shadows.get(&adr).copied());
+        
+        // A copied value keeps its origin
+        let copied = |adr: Option<usize>| adr.and_then(shadow).map_or(ir, |shadow| shadow.computed);
+        
+        let (adr, computed) = match self.inst {
+            Instruction::Read | Instruction::Load(Value::Constant(_)) | Instruction::Add(_) | Instruction::Sub(_) | Instruction::Mul(_) | Instruction::Div(_) | Instruction::Mod(_) => (0, ir),
+            Instruction::Load(Value::Register(reg)) => (0, copied(self.resolve(reg))),
+            Instruction::Store(reg) => (self.resolve(reg)?, copied(Some(0))),
+            Instruction::Increment(reg) | Instruction::Decrement(reg) => (self.resolve(reg)?, ir),
+            _ => return None,
+        };
+        
+        Some((adr, Shadow { written: ir, computed }))
+    }
+    
+    /// Returns the memory location of a register, if valid.
+    fn resolve<L: model::Loc>(&self, reg: Register<L>) -> Option<usize> {
+        match reg {
+            Register::Direct(adr) => Some(adr.raw()),
+            Register::Indirect(ptr) => self.peek(ptr.raw())?.try_into().ok(),
+        }
+    }
+    
+    /// Executes the next instruction, without shadow memory.
+    fn exec(&mut self) -> Result<(), RunError<T>> {
         match self.inst {
             Instruction::Read => {
                 let Some(v) = self.input.next() else {
@@ -173,73 +344,144 @@ impl<T: Integer, I: Iterator<Item = T>> Ram<T, I> {
     }
     
     /// Runs the whole program, and returns its output.
+    ///
+    /// Errors are reported before exiting, and the accesses to watched registers are reported as notes.
+    ///
+    /// The code is decoded on the first run, and unless some debugging is enabled, run from its decoded form,
+    /// which is faster than [`Ram::step`].
     pub fn run(&mut self) -> &[T] {
         loop {
+            if self.run_decoded() {
+                break &self.output;
+            }
+            
             let ir = self.ir;
             
             match self.step() {
-                Ok(()) if self.inst == Instruction::Stop => break &self.output,
-                Ok(()) => continue,
+                Ok(watch) => {
+                    if let Some(watch) = watch {
+                        let diag = Diagnostic::new(Level::Note, watch).code(&self.code, watch.ir).kind("Watch");
+                        diag.register(watch.adr, self.peek(watch.adr)).emit(self.error_format);
+                    }
+                    
+                    if self.inst == Instruction::Stop {
+                        break &self.output;
+                    }
+                },
                 Err(e) => self.emit_err(ir, e),
             }
         }
     }
     
+    /// Runs at most `steps` instructions, and returns `true` iff the program stopped.
+    ///
+    /// Unlike [`Ram::run`], errors are returned instead of being reported, and watchpoints are ignored.
+    pub fn run_for(&mut self, steps: usize) -> Result<bool, RunError<T>> {
+        for _ in 0..steps {
+            self.step()?;
+            
+            if self.inst == Instruction::Stop {
+                return Ok(true);
+            }
+        }
+        
+        Ok(false)
+    }
+    
     #[expect(clippy::needless_pass_by_value)]
     fn emit_err(&self, ir: Ir, e: RunError<T>) -> ! {
-        let path = "anon".as_ref();
+        let diag = self.diagnostic(ir, &e);
+        if cfg!(test) { panic!("{diag}") } else { diag.emit(self.error_format) };
+        exit(1);
+    }
+    
+    /// Reports the error `e` of the instruction `ir`, with the values it used.
+    fn diagnostic(&self, ir: Ir, e: &RunError<T>) -> Diagnostic {
         let inst = self.code.get(ir);
-        let snip = inst.map(|inst| inst.to_string()).unwrap_or_default();
-        let mut err = format_err(path, &snip, ir.inner(), e.to_string());
-        
-        if !matches!(e, RunError::Eof | RunError::ReadUninit { .. }) {
-            if let Some(inst) = inst {
-                // Show ACC value
-                if inst.should_print_acc() {
-                    err.push('\n');
-                    err.push_str(&format_help(path, ir.inner(), format!("ACC = {}", self.acc::<RoLoc>().inner.get())));
-                }
-                
-                // Show register value
-                match inst.register() {
-                    Some(Register::Direct(adr)) if !matches!(e, RunError::ReadUninit { .. }) => {
-                        let loc = self.loc(adr);
-                        
-                        err.push('\n');
-                        err.push_str(&format_help(path, ir.inner(), loc));
-                    },
-                    Some(Register::Indirect(adr)) => {
-                        let val = self.loc(adr).get().unwrap();
-                        let loc = match val.try_into() {
-                            Ok(adr) => Ok(self.loc(RoLoc::from(adr))),
-                            Err(err) => Err(RunError::InvalidAddress { adr: val, err }),
-                        };
-                        
-                        let msg = loc.map_or_else(|err| format!("<{err}>"), |val| val.to_string());
-                        
-                        err.push('\n');
-                        err.push_str(&format_help(path, ir.inner(), msg));
-                        
-                    },
-                    _ => {},
-                }
+        let mut diag = Diagnostic::error(e).code(&self.code, ir).kind(e.kind());
+        
+        // The values are always reported, but only shown when the error or `memcheck` doesn't already tell them
+        let show = !matches!(e, RunError::Eof | RunError::ReadUninit { .. });
+        let register = |diag: Diagnostic, adr: usize, value: Option<T>| if show { diag.register(adr, value) } else { diag.register_value(adr, value) };
+        
+        if let Some(inst) = inst {
+            // ACC value
+            let acc = self.peek(0);
+            diag = if show && inst.should_print_acc() { diag.acc(acc) } else { diag.acc_value(acc) };
+            
+            // Register value, including the register `STORE` writes through
+            let reg = match inst {
+                Instruction::Store(Register::Indirect(ptr)) => Some(Register::Indirect(ptr)),
+                _ => inst.register(),
+            };
+            match reg {
+                Some(Register::Direct(adr)) => {
+                    diag = register(diag, adr.raw(), self.peek(adr.raw()));
+                },
+                Some(Register::Indirect(adr)) => {
+                    diag = match self.peek(adr.raw()) {
+                        Some(val) => match val.try_into() {
+                            Ok(adr) => register(diag, adr, self.peek(adr)),
+                            Err(err) if show => diag.note(format_args!("<{}>", RunError::InvalidAddress { adr: val, err })),
+                            Err(_) => diag,
+                        },
+                        // The pointer isn't allocated
+                        None => register(diag, adr.raw(), None),
+                    };
+                },
+                _ => {},
             }
         }
         
+        if let (RunError::ReadUninit { adr }, Some(inst)) = (e, inst) {
+            diag = self.memcheck(diag, inst, adr.raw());
+        }
+        
         match e {
             RunError::IntegerOverfow => {
-                err.push('\n');
-                err.push_str(&format_help(path, ir.inner(), format!("using `--bits={}`; only values from {} to {} are accepted.", size_of::<T>() * 8, &T::min_value(), &T::max_value())));
+                diag = diag.help(format!("using `--bits={}`; only values from {} to {} are accepted.", size_of::<T>() * 8, &T::min_value(), &T::max_value()));
             },
             RunError::Eof => {
-                err.push('\n');
-                err.push_str(&format_help(path, ir.inner(), "missing `STOP`?"));
+                diag = diag.help("missing `STOP`?");
+            },
+            RunError::MemoryLimit { .. } => {
+                diag = match self.layout {
+                    Some(MemoryLayout::Dense) => diag.help("using `--memory=dense`; try `--memory=sparse` if the program uses far-apart registers, or raise `--memory-limit`"),
+                    _ => diag.help("raise `--memory-limit` if the program needs more registers"),
+                };
             },
             _ => {}
         }
         
-        if cfg!(test) { panic!("{err}") } else { eprintln!("{err}") };
-        exit(1);
+        diag
+    }
+    
+    /// Explains an uninitialized read of `adr` through the indirect register of `inst`,
+    /// if memcheck is enabled, see [`Ram::with_memcheck`].
+    fn memcheck(&self, mut diag: Diagnostic, inst: Instruction<T>, adr: usize) -> Diagnostic {
+        let Some(shadows) = &self.shadow else { return diag };
+        let Some(Register::Indirect(ptr)) = inst.register() else { return diag };
+        let Some(value) = self.peek(ptr.raw()) else { return diag };
+        if value.try_into().ok() != Some(adr) {
+            // ACC was read
+            return diag;
+        }
+        
+        let describe = |ir: Ir| format!("`{}` at {}", self.code.get(ir).unwrap_or_default(), self.code.origin(ir));
+        
+        if let Some(shadow) = shadows.get(&ptr.raw()) {
+            diag = diag.note(format_args!("the address R{ptr} = {value} was computed by {}", describe(shadow.computed)));
+            if shadow.written != shadow.computed {
+                diag = diag.note(format_args!("R{ptr} was last written by {}", describe(shadow.written)));
+            }
+        }
+        
+        // ACC is written too often to be of any help
+        let nearest = shadows.keys().copied().filter(|i| *i != 0).min_by_key(|i| i.abs_diff(adr));
+        match nearest {
+            Some(nearest) => diag.note(format_args!("R{adr} was never written; the nearest written register is R{nearest}")),
+            None => diag.note(format_args!("R{adr} was never written")),
+        }
     }
     
     /// Returns `self`'s current output.
@@ -248,36 +490,41 @@ impl<T: Integer, I: Iterator<Item = T>> Ram<T, I> {
         &self.output
     }
     
-    fn loc<L: model::Loc>(&self, adr: L) -> LocEntry<'_, T, L> {
+    fn loc<L: model::Loc>(&self, adr: L) -> Result<LocEntry<'_, T, L>, RunError<T>> {
         // SAFETY: we are not `Sync`,
         //  we don't call code that could call this function a 2nd time,
         //  so there's no references that point to our emulated memory.
         let memory = unsafe { &mut *self.memory.get() };
         
+        // Only writes allocate, so that reading a register fails the same way with any memory layout
         let raw_adr = adr.raw();
-        if raw_adr >= memory.len() {
-            #[cold]
-            #[inline(never)]
-            fn resize_mem<T: Integer>(memory: &mut Vec<Cell<Loc<T>>>, new_len: usize) {
-                memory.resize(new_len, Cell::new(Loc::Uninit));
-            }
-            
-            resize_mem(memory, raw_adr + 1);
+        let inner = if L::READ {
+            memory.get(raw_adr).ok_or(RunError::ReadUninit { adr: RoLoc::from(raw_adr) })?
+        } else {
+            memory.get_or_alloc(raw_adr, self.memory_limit)
+                .ok_or(RunError::MemoryLimit { adr: raw_adr, limit: self.memory_limit })?
         };
         
-        // SAFETY: if `adr >= memory.len()`, we resize so that `memory.len() == adr + 1`,
-        //  so `memory.len() > adr`.
-        unsafe { assert_unchecked(raw_adr < memory.len()) };
-        
+        Ok(LocEntry { adr, inner })
+    }
+    
+    #[inline]
+    fn acc<L: model::Loc>(&self) -> LocEntry<'_, T, L> {
         LocEntry {
-            adr,
-            inner: &memory[raw_adr],
+            adr: L::from(0),
+            inner: self.memory().acc(),
         }
     }
     
+    /// Returns the value of a register without allocating it, `None` if uninitialized.
+    fn peek(&self, adr: usize) -> Option<T> {
+        self.memory().get(adr).and_then(|loc| loc.get().value())
+    }
+    
     #[inline]
-    fn acc<L: model::Loc>(&self) -> LocEntry<'_, T, L> {
-        self.loc(L::from(0))
+    fn memory(&self) -> &dyn Memory<T> {
+        // SAFETY: the mutable references made by `Ram::loc` don't outlive it.
+        unsafe { &**self.memory.get() }
     }
     
     /// Returns `self`'s source code.
@@ -294,10 +541,17 @@ impl<T: Integer, I: Iterator<Item = T> + Default> Default for Ram<T, I> {
         Ram {
             input: I::default().fuse(),
             output: Vec::default(),
-            memory: Memory::default(),
+            memory: UnsafeCell::new(MemoryLayout::default().memory()),
             code: RoCode::default(),
             inst: Instruction::Stop,
             ir: Ir::default(),
+            shadow: None,
+            memory_limit: DEFAULT_MEMORY_LIMIT,
+            layout: Some(MemoryLayout::default()),
+            decoded: None,
+            read_only: Vec::new(),
+            watchpoints: BTreeMap::new(),
+            error_format: Format::default(),
         }
     }
 }
@@ -347,6 +601,46 @@ impl<T: Integer, I: Iterator<Item = T>> From<Ram<T, I>> for Vec<T> {
     }
 }
 
+impl Diagnostic {
+    /// Shows the instructions around the specified [`Ir`] and underlines it,
+    /// setting the position and the instruction of `self`.
+    ///
+    /// The instructions are shown with the lines of the RAM code,
+    /// the source location being added as a note if known.
+    /// The position is the source line if known, else the line of the RAM code.
+    pub fn code<T: Integer>(mut self, code: &RoCode<T>, ir: Ir) -> Diagnostic {
+        /// How many instructions are shown before and after the specified one.
+        const CONTEXT: usize = 2;
+        
+        let first = ir.inner().saturating_sub(CONTEXT);
+        let last = (ir.inner() + CONTEXT).min(code.len().saturating_sub(1));
+        
+        let (file, line) = code.location(ir).map_or_else(
+            || (code.path().map(|path| path.display().to_string()), code.lines(ir).map_or_else(|| ir.inner() + 1, |lines| *lines.start())),
+            |location| (Some(location.file.to_string()), location.line),
+        );
+        
+        self = self.at(code.line_origin(ir)).position(file, line);
+        if let Some(inst) = code.get(ir) {
+            self = self.inst(Some(ir.inner()), inst);
+        }
+        
+        for i in (first..=last).map(Ir::new) {
+            let Some(inst) = code.get(i) else { break };
+            let nb = code.lines(i).map_or_else(|| i.inner() + 1, |lines| *lines.start());
+            let inst = inst.to_string();
+            let text = code.note(i).map_or_else(|| inst.clone(), |note| format!("{inst} ; {note}"));
+            
+            self = if i == ir { self.marked_line(nb, text, 0..inst.len()) } else { self.line(nb, text) };
+        }
+        
+        match code.location(ir) {
+            Some(location) => self.note(format_args!("compiled from {location}")),
+            None => self,
+        }
+    }
+}
+
 impl<T: Integer> Instruction<T> {
     pub(crate) const fn should_print_acc(&self) -> bool {
         match self {
@@ -358,8 +652,10 @@ impl<T: Integer> Instruction<T> {
 
 #[cfg(test)]
 mod test {
-    use crate::runner::Ram;
+    use crate::model::{Instruction, Ir, RoCode, RoLoc, SourceMap};
+    use crate::runner::{Access, MemoryLayout, Ram, RunError};
     use crate::{inst, ram};
+    use std::str::FromStr;
     
     #[test]
     #[should_panic = "empty file"]
@@ -412,6 +708,170 @@ mod test {
         ].into());
     }
     
+    #[test]
+    #[should_panic = "  = note: the address R1 = 12 was computed by `ADD #10` at anon:5\n  = note: R1 was last written by `STORE 1` at anon:6\n  = note: R12 was never written; the nearest written register is R11"]
+    fn memcheck() {
+        let code = RoCode::<i32>::from([
+            inst!(LOAD #5),
+            inst!(STORE 11),
+            inst!(READ),
+            inst!(LOAD 0),
+            inst!(ADD #10),
+            inst!(STORE 1),
+            inst!(LOAD @1),
+            inst!(STOP),
+        ]);
+        
+        Ram::new(code, [2]).with_memcheck(true).run();
+    }
+    
+    #[test]
+    fn memory_far() {
+        let code = RoCode::<i64>::from([
+            inst!(LOAD #4_000_000_000),
+            inst!(STORE 1),
+            inst!(LOAD #7),
+            inst!(STORE @1),
+            inst!(LOAD @1),
+            inst!(WRITE),
+            inst!(STOP),
+        ]);
+        
+        assert_eq!(Ram::from(code.clone()).run(), [7]);
+        assert_eq!(Ram::from(code.clone()).with_memory(MemoryLayout::Sparse).run(), [7]);
+        
+        let mut ram = Ram::from(code).with_memory(MemoryLayout::Dense);
+        assert_eq!(ram.run_for(10), Err(RunError::MemoryLimit { adr: 4_000_000_000, limit: 1 << 20 }));
+    }
+    
+    #[test]
+    fn memory_limit() {
+        let code = RoCode::<i32>::from([
+            inst!(LOAD #1),
+            inst!(STORE 1),
+            inst!(STORE 300),
+            inst!(STOP),
+        ]);
+        
+        // Dense memory allocates up to R300, sparse memory a 2nd page
+        for layout in [MemoryLayout::Auto, MemoryLayout::Dense, MemoryLayout::Sparse] {
+            let mut ram = Ram::from(code.clone()).with_memory(layout).with_memory_limit(300);
+            assert_eq!(ram.run_for(10), Err(RunError::MemoryLimit { adr: 300, limit: 300 }));
+        }
+        
+        // Sparse memory allocates the 1st page on accessing R1, not ACC
+        let code = RoCode::<i32>::from([inst!(LOAD #1), inst!(STORE 1), inst!(STOP)]);
+        let mut ram = Ram::from(code.clone()).with_memory(MemoryLayout::Sparse).with_memory_limit(100);
+        assert_eq!(ram.run_for(10), Err(RunError::MemoryLimit { adr: 1, limit: 100 }));
+        assert_eq!(Ram::from(code).with_memory(MemoryLayout::Dense).with_memory_limit(100).run_for(10), Ok(true));
+    }
+    
+    #[test]
+    #[should_panic = "  = note: R200 = <uninitialized>\n  = help: raise `--memory-limit` if the program needs more registers"]
+    fn memory_limit_pointer() {
+        let ram = Ram::from(RoCode::<i32>::from([inst!(LOAD @200), inst!(STOP)])).with_memory_limit(100);
+        ram.emit_err(Ir::new(0), RunError::MemoryLimit { adr: 200, limit: 100 });
+    }
+    
+    #[test]
+    fn error_json() {
+        let mut ram = Ram::from(RoCode::<i32>::from([inst!(LOAD #1), inst!(STORE @2_000_000), inst!(STOP)]));
+        let err = ram.run_for(10).unwrap_err();
+        assert_eq!(
+            ram.diagnostic(Ir::new(1), &err).to_json(),
+            r#"{"level":"error","kind":"ReadUninit","message":"reading uninitialized memory R2000000","file":null,"line":2,"ir":1,"instruction":"STORE @2000000","acc":1,"registers":[{"register":2000000,"value":null}],"help":[],"notes":[]}"#,
+        );
+    }
+    
+    #[test]
+    fn memory_read_unallocated() {
+        let codes = [
+            RoCode::<i64>::from([inst!(LOAD 2_000_000), inst!(STOP)]),
+            RoCode::from([inst!(INC 2_000_000), inst!(STOP)]),
+            RoCode::from([inst!(LOAD #2_000_000), inst!(STORE 1), inst!(LOAD @1), inst!(STOP)]),
+        ];
+        
+        // Reading a register past the memory limit fails like reading an uninitialized one
+        for code in codes {
+            for layout in [MemoryLayout::Auto, MemoryLayout::Dense, MemoryLayout::Sparse] {
+                let mut ram = Ram::from(code.clone()).with_memory(layout);
+                assert_eq!(ram.run_for(10), Err(RunError::ReadUninit { adr: RoLoc::from(2_000_000) }), "{layout:?}:\n{code}");
+            }
+        }
+    }
+    
+    #[test]
+    fn memory_uninit() {
+        let code = RoCode::<i64>::from([
+            inst!(LOAD #1),
+            inst!(STORE 1),
+            inst!(LOAD #70_000),
+            inst!(STORE 2),
+            inst!(LOAD 1),
+            inst!(STORE @2),
+            inst!(LOAD 3),
+            inst!(STOP),
+        ]);
+        
+        // Unallocated registers, and the other registers of a page, are uninitialized
+        for layout in [MemoryLayout::Auto, MemoryLayout::Dense, MemoryLayout::Sparse] {
+            let mut ram = Ram::from(code.clone()).with_memory(layout);
+            assert_eq!(ram.run_for(10), Err(RunError::ReadUninit { adr: RoLoc::from(3) }), "{layout:?}");
+        }
+    }
+    
+    #[test]
+    fn decoded() {
+        let code = RoCode::<i32>::from_str(include_str!("../../benches/fibo.ram")).unwrap();
+        
+        for n in 0..15 {
+            let mut ram = Ram::new(code.clone(), [n]);
+            assert_eq!(ram.run_for(usize::MAX), Ok(true));
+            
+            // The stack growing, the decoded code falls back to `Ram::step` a few times
+            assert_eq!(Ram::new(code.clone(), [n]).run(), ram.output(), "fibo({n})");
+        }
+    }
+    
+    #[test]
+    fn read_only() {
+        let code = RoCode::<i32>::from([
+            inst!(LOAD #5),
+            inst!(STORE 1),
+            inst!(STORE 4),
+            inst!(STORE @1),
+            inst!(STOP),
+        ]);
+        
+        let mut ram = Ram::from(code).with_read_only(5..8);
+        assert_eq!(ram.run_for(10), Err(RunError::ReadOnly { adr: 5 }));
+        assert_eq!(ram.peek(5), None);
+    }
+    
+    #[test]
+    fn watchpoints() {
+        let code = RoCode::<i32>::from([
+            inst!(LOAD #2),
+            inst!(STORE 2),
+            inst!(LOAD 2),
+            inst!(INC 2),
+            inst!(STORE @2),
+            inst!(STOP),
+        ]);
+        
+        let mut ram = Ram::from(code).with_watchpoint(2, Access::Write).with_watchpoint(3, Access::ReadWrite);
+        let mut watches = Vec::new();
+        while ram.inst != Instruction::Stop {
+            watches.extend(ram.step().unwrap().map(|watch| (watch.ir.inner(), watch.adr, watch.access)));
+        }
+        
+        assert_eq!(watches, [
+            (1, 2, Access::Write),
+            (3, 2, Access::ReadWrite),
+            (4, 3, Access::Write),
+        ]);
+    }
+    
     #[test]
     #[should_panic = "invalid address R-10"]
     fn load_indirect_negative() {
@@ -431,6 +891,23 @@ mod test {
         ].into());
     }
     
+    #[test]
+    #[should_panic = "error: integer overflow\n --> anon:2\n  |\n1 | LOAD #1\n2 | DIV #0\n  | ^^^^^^\n  |\n  = note: compiled from fibo.algo:2 in fibo()"]
+    fn error_location() {
+        let map = SourceMap::from_str("0\tfibo.algo\t1\tfibo\n1\tfibo.algo\t2\tfibo").unwrap();
+        Ram::<i32, _>::run(&mut RoCode::from([
+            inst!(LOAD #1),
+            inst!(DIV #0),
+        ]).with_source_map(&map).into());
+    }
+    
+    #[test]
+    #[should_panic = "3 | LOAD #1 ; 1 / 0\n4 | DIV #0\n  | ^^^^^^\n"]
+    fn error_path() {
+        let code = RoCode::from_str("; 1 / 0\n\nLOAD #1 ; 1 / 0\nDIV #0").unwrap();
+        Ram::<i32, _>::run(&mut code.with_path("a.ram").into());
+    }
+    
     #[test]
     #[should_panic = "integer overflow"]
     fn dec_overflow() {
@@ -468,7 +945,7 @@ mod test {
             inst!(DIV #0),
         ].into());
     }
-
+    
     #[test]
     #[should_panic = "integer overflow"]
     fn rem_zero() {
@@ -477,7 +954,7 @@ mod test {
             inst!(MOD #0),
         ].into());
     }
-
+    
     #[test]
     fn rem() {
         // -5 % 2,
@@ -525,7 +1002,7 @@ mod test {
     #[should_panic = "jumping to an invalid location"]
     #[cfg(feature = "indirect_jumps")]
     fn jump_negative() {
-        Ram::<_, _>::run([
+        Ram::<i32, _>::run(&mut [
             inst!(LOAD #-2),
             inst!(JUMP @0),
         ].into());